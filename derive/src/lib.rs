@@ -0,0 +1,603 @@
+/*!
+Derive `FromProtobuf` for types that also derive `sval::Value`.
+
+This crate shouldn't be used directly. Enable the `derive` feature of `sval_protobuf` instead.
+
+Field numbers are assigned in the same way as `sval_derive` assigns indexes, so a type can be
+encoded with `#[derive(Value)]` and decoded with `#[derive(FromProtobuf)]` from the same
+definition. The following `#[sval]` attributes are understood:
+
+- `index` on fields and enum variants sets the field number.
+- `data_tag` on fields, and `tag` on enum variants and newtypes, sets the encoding of numbers.
+- `flatten` on fields decodes the fields of the value directly from the parent message.
+- `skip` on fields leaves them as their `Default` value.
+
+All other `#[sval]` attributes are ignored.
+*/
+
+extern crate proc_macro;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Expr, ExprLit, ExprUnary,
+    Fields, Lit, LitBool, LitStr, UnOp,
+};
+
+/**
+Derive `FromProtobuf`.
+*/
+#[proc_macro_derive(FromProtobuf, attributes(sval))]
+pub fn derive_from_protobuf(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match derive(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn derive(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let attrs = Attrs::from_attrs(&input.attrs)?;
+
+    let body = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                derive_newtype(&fields.unnamed[0], attrs.tag.as_ref())?
+            }
+            Fields::Unit => derive_unit_struct(),
+            fields => derive_struct(fields)?,
+        },
+        Data::Enum(data) => {
+            let variants = data.variants.iter().collect::<Vec<_>>();
+
+            // The first variant is used as the empty value
+            if variants.is_empty() {
+                return Err(syn::Error::new_spanned(
+                    input,
+                    "FromProtobuf can't be derived for enums without variants",
+                ));
+            }
+
+            if variants
+                .iter()
+                .all(|variant| matches!(variant.fields, Fields::Unit))
+            {
+                derive_unit_enum(&variants)?
+            } else if variants.iter().all(|variant| {
+                matches!(&variant.fields, Fields::Unnamed(fields) if fields.unnamed.len() == 1)
+            }) {
+                derive_oneof_enum(&variants)?
+            } else {
+                return Err(syn::Error::new(
+                    input.ident.span(),
+                    "`FromProtobuf` can only be derived for enums where either all variants are units, or all variants have a single unnamed field",
+                ));
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new(
+                input.ident.span(),
+                "`FromProtobuf` can't be derived for unions",
+            ))
+        }
+    };
+
+    let ident = &input.ident;
+
+    let mut generics = input.generics.clone();
+    for param in generics.type_params_mut() {
+        param
+            .bounds
+            .push(syn::parse_quote!(::sval_protobuf::decode::FromProtobuf));
+    }
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        const _: () = {
+            use ::sval_protobuf::{
+                decode::FromProtobuf as __FromProtobuf,
+                raw::{Fields as __Fields, Payload as __Payload, WireType as __WireType},
+                Error as __Error, ErrorKind as __ErrorKind,
+            };
+
+            #[automatically_derived]
+            impl #impl_generics __FromProtobuf for #ident #ty_generics #where_clause {
+                #body
+            }
+        };
+    })
+}
+
+/**
+Messages are merged from length-prefixed fields, and decode their own fields through `merge_flattened`.
+*/
+fn message_methods() -> TokenStream {
+    quote! {
+        fn merge_field(
+            &mut self,
+            payload: __Payload<&[u8]>,
+            _: ::core::option::Option<&::sval_protobuf::__private::sval::Tag>,
+        ) -> ::core::result::Result<(), __Error> {
+            match payload {
+                __Payload::Len(buf) => __FromProtobuf::merge_message(self, buf),
+                _ => ::core::result::Result::Err(__Error::from(__ErrorKind::InvalidWireType)),
+            }
+        }
+
+        fn merge_message(&mut self, buf: &[u8]) -> ::core::result::Result<(), __Error> {
+            for field in __Fields::new(buf) {
+                let field = field?;
+
+                // Fields that aren't part of the message are ignored
+                let _ = __FromProtobuf::merge_flattened(self, 0, field.number(), field.into_payload())?;
+            }
+
+            ::core::result::Result::Ok(())
+        }
+    }
+}
+
+fn derive_struct(fields: &Fields) -> syn::Result<TokenStream> {
+    let mut allocator = IndexAllocator::new();
+
+    let mut empty = Vec::new();
+    let mut merge = Vec::new();
+
+    for (i, field) in fields.iter().enumerate() {
+        let attrs = Attrs::from_attrs(&field.attrs)?;
+
+        let member = match &field.ident {
+            Some(ident) => quote!(#ident),
+            None => {
+                let index = syn::Index::from(i);
+                quote!(#index)
+            }
+        };
+
+        // Skipped fields aren't streamed, so they don't get a field number
+        if attrs.skip {
+            empty.push(quote!(#member: ::core::default::Default::default()));
+            continue;
+        }
+
+        let ty = &field.ty;
+        empty.push(quote!(#member: <#ty as __FromProtobuf>::empty()));
+
+        if attrs.flatten {
+            let offset = allocator.flatten(attrs.index, field.span())?;
+
+            merge.push(quote! {
+                if __FromProtobuf::merge_flattened(&mut self.#member, offset + #offset, number, payload)? {
+                    return ::core::result::Result::Ok(true);
+                }
+            });
+        } else {
+            let number = allocator.next(attrs.index, field.span())?;
+            let tag = quote_optional_tag(attrs.data_tag.as_ref());
+
+            merge.push(quote! {
+                if number == #number {
+                    __FromProtobuf::merge_field(&mut self.#member, payload, #tag)?;
+
+                    return ::core::result::Result::Ok(true);
+                }
+            });
+        }
+    }
+
+    let message_methods = message_methods();
+
+    Ok(quote! {
+        fn empty() -> Self {
+            Self {
+                #(#empty,)*
+            }
+        }
+
+        #[allow(unused_variables)]
+        fn merge_flattened(
+            &mut self,
+            offset: u64,
+            number: u64,
+            payload: __Payload<&[u8]>,
+        ) -> ::core::result::Result<bool, __Error> {
+            #(#merge)*
+
+            ::core::result::Result::Ok(false)
+        }
+
+        #message_methods
+    })
+}
+
+fn derive_newtype(field: &syn::Field, tag: Option<&Expr>) -> syn::Result<TokenStream> {
+    let ty = &field.ty;
+
+    // A tag on the newtype itself overrides the tag of the field it's in
+    let tag = match tag {
+        Some(tag) => quote!(::core::option::Option::Some(&#tag)),
+        None => quote!(tag),
+    };
+
+    Ok(quote! {
+        fn empty() -> Self {
+            Self(<#ty as __FromProtobuf>::empty())
+        }
+
+        fn merge_field(
+            &mut self,
+            payload: __Payload<&[u8]>,
+            tag: ::core::option::Option<&::sval_protobuf::__private::sval::Tag>,
+        ) -> ::core::result::Result<(), __Error> {
+            __FromProtobuf::merge_field(&mut self.0, payload, #tag)
+        }
+
+        fn merge_flattened(
+            &mut self,
+            offset: u64,
+            number: u64,
+            payload: __Payload<&[u8]>,
+        ) -> ::core::result::Result<bool, __Error> {
+            __FromProtobuf::merge_flattened(&mut self.0, offset, number, payload)
+        }
+
+        fn merge_message(&mut self, buf: &[u8]) -> ::core::result::Result<(), __Error> {
+            __FromProtobuf::merge_message(&mut self.0, buf)
+        }
+
+        fn packed_wire_type(
+            tag: ::core::option::Option<&::sval_protobuf::__private::sval::Tag>,
+        ) -> ::core::option::Option<__WireType> {
+            <#ty as __FromProtobuf>::packed_wire_type(#tag)
+        }
+    })
+}
+
+fn derive_unit_struct() -> TokenStream {
+    quote! {
+        fn empty() -> Self {
+            Self
+        }
+
+        fn merge_field(
+            &mut self,
+            _: __Payload<&[u8]>,
+            _: ::core::option::Option<&::sval_protobuf::__private::sval::Tag>,
+        ) -> ::core::result::Result<(), __Error> {
+            ::core::result::Result::Ok(())
+        }
+    }
+}
+
+fn derive_unit_enum(variants: &[&syn::Variant]) -> syn::Result<TokenStream> {
+    let mut allocator = IndexAllocator::new();
+
+    let mut values = Vec::new();
+    for variant in variants {
+        let attrs = Attrs::from_attrs(&variant.attrs)?;
+
+        let explicit = variant_index(attrs.index, variant);
+
+        // Protobuf enums are streamed as their index directly, without offsetting them
+        let value = match allocator.next_index(explicit) {
+            Index::Implicit(index) => quote!(#index),
+            Index::Explicit(index) => index,
+        };
+
+        values.push((&variant.ident, value));
+    }
+
+    let empty = values.iter().rev().fold(
+        {
+            let (first, _) = &values[0];
+            quote!(Self::#first)
+        },
+        |rest, (ident, value)| {
+            quote! {
+                if (#value) as i32 == 0 {
+                    Self::#ident
+                } else {
+                    #rest
+                }
+            }
+        },
+    );
+
+    let merge = values.iter().map(|(ident, value)| {
+        quote! {
+            if v == (#value) as i32 {
+                *self = Self::#ident;
+
+                return ::core::result::Result::Ok(());
+            }
+        }
+    });
+
+    Ok(quote! {
+        fn empty() -> Self {
+            #empty
+        }
+
+        fn merge_field(
+            &mut self,
+            payload: __Payload<&[u8]>,
+            tag: ::core::option::Option<&::sval_protobuf::__private::sval::Tag>,
+        ) -> ::core::result::Result<(), __Error> {
+            let mut v = 0i32;
+            __FromProtobuf::merge_field(&mut v, payload, tag)?;
+
+            #(#merge)*
+
+            ::core::result::Result::Err(__Error::from(__ErrorKind::InvalidValue))
+        }
+
+        fn packed_wire_type(
+            tag: ::core::option::Option<&::sval_protobuf::__private::sval::Tag>,
+        ) -> ::core::option::Option<__WireType> {
+            <i32 as __FromProtobuf>::packed_wire_type(tag)
+        }
+    })
+}
+
+fn derive_oneof_enum(variants: &[&syn::Variant]) -> syn::Result<TokenStream> {
+    let mut allocator = IndexAllocator::new();
+
+    let mut merge = Vec::new();
+    for variant in variants {
+        let attrs = Attrs::from_attrs(&variant.attrs)?;
+
+        let explicit = variant_index(attrs.index, variant);
+
+        let ident = &variant.ident;
+        let ty = &variant.fields.iter().next().unwrap().ty;
+        let number = quote_number(allocator.next_index(explicit));
+        let tag = quote_optional_tag(attrs.tag.as_ref());
+
+        merge.push(quote! {
+            if number == #number {
+                // Setting a different member of a oneof replaces the current one
+                if let Self::#ident(value) = self {
+                    __FromProtobuf::merge_field(value, payload, #tag)?;
+                } else {
+                    let mut value = <#ty as __FromProtobuf>::empty();
+                    __FromProtobuf::merge_field(&mut value, payload, #tag)?;
+
+                    *self = Self::#ident(value);
+                }
+
+                return ::core::result::Result::Ok(true);
+            }
+        });
+    }
+
+    let first = &variants[0].ident;
+    let first_ty = &variants[0].fields.iter().next().unwrap().ty;
+
+    let message_methods = message_methods();
+
+    Ok(quote! {
+        fn empty() -> Self {
+            Self::#first(<#first_ty as __FromProtobuf>::empty())
+        }
+
+        #[allow(irrefutable_let_patterns)]
+        fn merge_flattened(
+            &mut self,
+            offset: u64,
+            number: u64,
+            payload: __Payload<&[u8]>,
+        ) -> ::core::result::Result<bool, __Error> {
+            #(#merge)*
+
+            ::core::result::Result::Ok(false)
+        }
+
+        #message_methods
+    })
+}
+
+/**
+Variants use their discriminant as an index unless one is given explicitly.
+*/
+fn variant_index(index: Option<IndexValue>, variant: &syn::Variant) -> Option<IndexValue> {
+    index.or_else(|| {
+        variant
+            .discriminant
+            .as_ref()
+            .map(|(_, discriminant)| IndexValue::from_expr(discriminant))
+    })
+}
+
+fn quote_optional_tag(tag: Option<&Expr>) -> TokenStream {
+    match tag {
+        Some(tag) => quote!(::core::option::Option::Some(&#tag)),
+        None => quote!(::core::option::Option::None),
+    }
+}
+
+/**
+Quote the field number for an index.
+
+Implicit indexes are zero-based offsets that are shifted by the position of the value
+in its parent when it's flattened.
+*/
+fn quote_number(index: Index) -> TokenStream {
+    match index {
+        Index::Implicit(index) => {
+            let index = index as u64;
+            quote!((offset + #index + 1))
+        }
+        Index::Explicit(index) => quote!(((#index) as u64)),
+    }
+}
+
+#[derive(Default)]
+struct Attrs {
+    index: Option<IndexValue>,
+    tag: Option<Expr>,
+    data_tag: Option<Expr>,
+    flatten: bool,
+    skip: bool,
+}
+
+impl Attrs {
+    fn from_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut parsed = Attrs::default();
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("sval")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("index") {
+                    parsed.index = Some(IndexValue::from_expr(&meta.value()?.parse()?));
+                } else if meta.path.is_ident("tag") {
+                    parsed.tag = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                } else if meta.path.is_ident("data_tag") {
+                    parsed.data_tag = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                } else if meta.path.is_ident("flatten") {
+                    parsed.flatten = parse_flag(&meta)?;
+                } else if meta.path.is_ident("skip") {
+                    parsed.skip = parse_flag(&meta)?;
+                } else if meta.input.peek(syn::Token![=]) {
+                    let _: Expr = meta.value()?.parse()?;
+                } else if meta.input.peek(syn::token::Paren) {
+                    let content;
+                    syn::parenthesized!(content in meta.input);
+                    let _: TokenStream = content.parse()?;
+                }
+
+                Ok(())
+            })?;
+        }
+
+        Ok(parsed)
+    }
+}
+
+fn parse_flag(meta: &syn::meta::ParseNestedMeta) -> syn::Result<bool> {
+    if meta.input.peek(syn::Token![=]) {
+        Ok(meta.value()?.parse::<LitBool>()?.value)
+    } else {
+        Ok(true)
+    }
+}
+
+enum IndexValue {
+    Const(isize),
+    Expr(Expr),
+}
+
+impl IndexValue {
+    fn from_expr(expr: &Expr) -> Self {
+        fn parse_int(expr: &Expr) -> Option<isize> {
+            match expr {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Int(lit), ..
+                }) => lit.base10_parse().ok(),
+                Expr::Unary(ExprUnary {
+                    op: UnOp::Neg(_),
+                    expr,
+                    ..
+                }) => parse_int(expr).map(|v| -v),
+                _ => None,
+            }
+        }
+
+        match parse_int(expr) {
+            Some(index) => IndexValue::Const(index),
+            None => IndexValue::Expr(expr.clone()),
+        }
+    }
+}
+
+enum Index {
+    Implicit(isize),
+    Explicit(TokenStream),
+}
+
+/**
+Assigns indexes to fields and variants in the same way `sval_derive` does.
+
+Indexes start as implicit zero-based offsets. Once an explicit index is seen, any
+following implicit indexes continue on from it as explicit indexes.
+*/
+struct IndexAllocator {
+    next_index: isize,
+    explicit: bool,
+    // The number of implicit indexes assigned at runtime,
+    // which is unknown after a value is flattened
+    runtime_index: Option<isize>,
+}
+
+impl IndexAllocator {
+    fn new() -> Self {
+        IndexAllocator {
+            next_index: 0,
+            explicit: false,
+            runtime_index: Some(0),
+        }
+    }
+
+    fn next_index(&mut self, explicit: Option<IndexValue>) -> Index {
+        match explicit {
+            Some(IndexValue::Const(index)) => {
+                self.explicit = true;
+                self.next_index = index + 1;
+
+                Index::Explicit(quote!(#index))
+            }
+            Some(IndexValue::Expr(index)) => {
+                self.explicit = true;
+                self.next_index += 1;
+
+                Index::Explicit(quote!(#index))
+            }
+            None => {
+                let index = self.next_index;
+                self.next_index += 1;
+
+                if self.explicit {
+                    Index::Explicit(quote!(#index))
+                } else {
+                    Index::Implicit(index)
+                }
+            }
+        }
+    }
+
+    fn next(
+        &mut self,
+        explicit: Option<IndexValue>,
+        span: proc_macro2::Span,
+    ) -> syn::Result<TokenStream> {
+        match self.next_index(explicit) {
+            Index::Implicit(_) => {
+                let index = self.runtime_index.ok_or_else(|| {
+                    syn::Error::new(
+                        span,
+                        "fields following a flattened field need an explicit `#[sval(index)]`",
+                    )
+                })?;
+
+                self.runtime_index = Some(index + 1);
+
+                Ok(quote_number(Index::Implicit(index)))
+            }
+            explicit => Ok(quote_number(explicit)),
+        }
+    }
+
+    fn flatten(
+        &mut self,
+        explicit: Option<IndexValue>,
+        span: proc_macro2::Span,
+    ) -> syn::Result<TokenStream> {
+        let _ = self.next_index(explicit);
+
+        let offset = self.runtime_index.take().ok_or_else(|| {
+            syn::Error::new(span, "a message can only contain a single flattened field")
+        })? as u64;
+
+        Ok(quote!(#offset))
+    }
+}
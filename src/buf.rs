@@ -7,14 +7,15 @@ value without necessarily knowing the final size upfront.
 
 use crate::raw::{VarInt, WireType, I32, I64};
 use alloc::{borrow::Cow, boxed::Box, vec::Vec};
-use core::cmp;
+use core::{cmp, ops::Range};
 
 pub(crate) const APPROXIMATE_DEPTH: usize = 32;
 
 mod cursor;
+mod fields;
 mod visit;
 
-pub use self::cursor::*;
+pub use self::{cursor::*, fields::*};
 
 /**
 Buffering writer for protobuf, with state `T`.
@@ -49,6 +50,15 @@ struct LenStackFrame<T> {
     state: T,
 }
 
+/**
+A position in a [`ProtoBufMut`].
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Mark {
+    bytes: usize,
+    chunks: usize,
+}
+
 #[derive(Debug, Clone, Copy)]
 struct LenPrefixedChunk {
     // Written before the data in `range`
@@ -276,6 +286,98 @@ impl<T> ProtoBufMut<T> {
         self.bytes.reserve(num_bytes);
     }
 
+    /**
+    Get the current position in the buffer.
+    */
+    #[inline]
+    pub(crate) fn mark(&self) -> Mark {
+        Mark {
+            bytes: self.bytes.len(),
+            chunks: self.chunks.len(),
+        }
+    }
+
+    /**
+    Get the payload between two positions as a contiguous buffer.
+
+    Any length-prefixed values in the range must be complete.
+    */
+    pub(crate) fn to_vec_range(&self, range: Range<Mark>) -> Cow<'_, [u8]> {
+        let bytes = &self.bytes[range.start.bytes..range.end.bytes];
+        let chunks = &self.chunks[range.start.chunks..range.end.chunks];
+
+        if chunks.is_empty() {
+            return Cow::Borrowed(bytes);
+        }
+
+        let chunks = chunks
+            .iter()
+            .map(|chunk| LenPrefixedChunk {
+                varint: chunk.varint,
+                start: chunk.start - range.start.bytes,
+            })
+            .collect::<Vec<_>>();
+
+        Cow::Owned(visit::to_vec(bytes, &chunks).into_owned())
+    }
+
+    /**
+    Rearrange the payload from `start` to the current position.
+
+    The given ranges must cover the payload exactly once, in the order they should be written.
+    Any length-prefixed values in the payload must be complete.
+    */
+    pub(crate) fn reorder(&mut self, start: Mark, ranges: impl IntoIterator<Item = Range<Mark>>) {
+        let mut bytes = Vec::with_capacity(self.bytes.len() - start.bytes);
+        let mut chunks = Vec::with_capacity(self.chunks.len() - start.chunks);
+
+        for range in ranges {
+            let offset = start.bytes + bytes.len();
+
+            chunks.extend(
+                self.chunks[range.start.chunks..range.end.chunks]
+                    .iter()
+                    .map(|chunk| LenPrefixedChunk {
+                        varint: chunk.varint,
+                        start: chunk.start - range.start.bytes + offset,
+                    }),
+            );
+            bytes.extend_from_slice(&self.bytes[range.start.bytes..range.end.bytes]);
+        }
+
+        debug_assert_eq!(self.bytes.len() - start.bytes, bytes.len());
+        debug_assert_eq!(self.chunks.len() - start.chunks, chunks.len());
+
+        self.bytes.truncate(start.bytes);
+        self.bytes.extend_from_slice(&bytes);
+
+        self.chunks.truncate(start.chunks);
+        self.chunks.extend_from_slice(&chunks);
+    }
+
+    /**
+    Rewrite a length written at `start` if the payload after it doesn't match.
+
+    The length must have been written by [`ProtoBufMut::push_len_varint_uint64`] as `len`.
+    There can't be any length-prefixed values in the payload after it.
+    */
+    pub(crate) fn rewrite_len(&mut self, start: Mark, len: u64) {
+        debug_assert_eq!(start.chunks, self.chunks.len());
+
+        let prefix_end = start.bytes + VarInt::uint64(len).len();
+        let actual = (self.bytes.len() - prefix_end) as u64;
+
+        if actual != len {
+            self.bytes.splice(
+                start.bytes..prefix_end,
+                VarInt::uint64(actual)
+                    .fill_bytes(&mut [0; 10])
+                    .iter()
+                    .copied(),
+            );
+        }
+    }
+
     /**
     Begin a new length-prefixed value, where the length isn't known upfront.
 
@@ -514,6 +616,8 @@ impl<T> ProtoBufMutReusable<T> {
 mod tests {
     use super::*;
 
+    use alloc::vec;
+
     #[test]
     fn capacity_next() {
         let window = [
@@ -532,4 +636,60 @@ mod tests {
         assert_eq!(19, capacity.bytes_len);
         assert_eq!(19, capacity.chunks_len);
     }
+
+    #[test]
+    fn rewrite_len() {
+        for (len, payload, expected) in [
+            (2, vec![1u8, 2], vec![0x0au8, 2, 1, 2]),
+            (3, vec![1, 2], vec![0x0a, 2, 1, 2]),
+            (1, vec![0; 200], [vec![0x0a, 200, 1], vec![0; 200]].concat()),
+        ] {
+            let mut buf = ProtoBufMut::new(());
+
+            buf.push_field_len(1);
+
+            let start = buf.mark();
+            buf.push_len_varint_uint64(len);
+            buf.push(&payload);
+
+            buf.rewrite_len(start, len);
+
+            assert_eq!(&*expected, &*buf.freeze().to_vec());
+        }
+    }
+
+    #[test]
+    fn reorder() {
+        let mut buf = ProtoBufMut::new(());
+
+        buf.push_field_varint(1);
+        buf.push_varint_uint64(1);
+
+        let start = buf.mark();
+
+        buf.push_field_len(2);
+        buf.begin_len(());
+        buf.push(b"a");
+        buf.end_len();
+
+        let mid = buf.mark();
+
+        buf.push_field_len(2);
+        buf.begin_len(());
+        buf.begin_len(());
+        buf.push(b"bc");
+        buf.end_len();
+        buf.end_len();
+
+        let end = buf.mark();
+
+        assert_eq!(&[0x12, 3, 2, b'b', b'c'], &*buf.to_vec_range(mid..end));
+
+        buf.reorder(start, [mid..end, start..mid]);
+
+        assert_eq!(
+            &[0x08, 1, 0x12, 3, 2, b'b', b'c', 0x12, 1, b'a'],
+            &*buf.freeze().to_vec()
+        );
+    }
 }
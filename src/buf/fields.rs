@@ -0,0 +1,366 @@
+use super::{LenPrefixedChunk, ProtoBuf};
+use crate::{
+    raw::{Field, Payload, VarInt, WireType, I32, I64},
+    Error, ErrorKind,
+};
+
+use alloc::{borrow::Cow, vec::Vec};
+
+/**
+A borrowed view of an encoded protobuf message, or a length-prefixed payload within one.
+
+A `ProtoBufRef` may point into a [`ProtoBuf`] that hasn't been made contiguous yet. The
+lengths of any nested length-prefixed fields are then stored alongside the bytes instead
+of within them.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct ProtoBufRef<'a> {
+    bytes: &'a [u8],
+    chunks: &'a [LenPrefixedChunk],
+    start: usize,
+    end: usize,
+}
+
+impl<'a> ProtoBufRef<'a> {
+    /**
+    Get the length in bytes of the encoded payload.
+    */
+    pub fn len(&self) -> usize {
+        (self.end - self.start)
+            + self
+                .chunks
+                .iter()
+                .filter_map(|chunk| chunk.varint)
+                .map(|varint| VarInt::uint64(varint).len())
+                .sum::<usize>()
+    }
+
+    /**
+    Whether the encoded payload is empty.
+    */
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /**
+    Get the payload as a contiguous buffer, if it already is one.
+    */
+    pub fn as_slice(&self) -> Option<&'a [u8]> {
+        if self.chunks.iter().all(|chunk| chunk.varint.is_none()) {
+            Some(&self.bytes[self.start..self.end])
+        } else {
+            None
+        }
+    }
+
+    /**
+    Get the payload as a contiguous buffer.
+    */
+    pub fn to_vec(&self) -> Cow<'a, [u8]> {
+        if let Some(slice) = self.as_slice() {
+            return Cow::Borrowed(slice);
+        }
+
+        let mut buf = Vec::with_capacity(self.len());
+
+        let mut start = self.start;
+        for chunk in self.chunks {
+            buf.extend_from_slice(&self.bytes[start..chunk.start]);
+
+            if let Some(varint) = chunk.varint {
+                buf.extend_from_slice(VarInt::uint64(varint).fill_bytes(&mut [0; 10]));
+            }
+
+            start = chunk.start;
+        }
+
+        buf.extend_from_slice(&self.bytes[start..self.end]);
+
+        Cow::Owned(buf)
+    }
+
+    /**
+    Iterate over the fields in the payload.
+    */
+    pub fn fields(&self) -> Fields<'a> {
+        Fields {
+            buf: *self,
+            chunk: 0,
+        }
+    }
+}
+
+impl<'a> From<&'a [u8]> for ProtoBufRef<'a> {
+    fn from(bytes: &'a [u8]) -> Self {
+        ProtoBufRef {
+            bytes,
+            chunks: &[],
+            start: 0,
+            end: bytes.len(),
+        }
+    }
+}
+
+impl<'a> From<&'a ProtoBuf> for ProtoBufRef<'a> {
+    fn from(buf: &'a ProtoBuf) -> Self {
+        ProtoBufRef {
+            bytes: &buf.bytes,
+            chunks: &buf.chunks,
+            start: 0,
+            end: buf.bytes.len(),
+        }
+    }
+}
+
+impl ProtoBuf {
+    /**
+    Iterate over the fields in the encoded message.
+
+    Fields are read directly from the internal buffers, without first making them contiguous.
+    */
+    pub fn fields(&self) -> Fields<'_> {
+        ProtoBufRef::from(self).fields()
+    }
+}
+
+/**
+An iterator over the fields of a [`ProtoBuf`] or [`ProtoBufRef`].
+
+Length-prefixed payloads are yielded as [`ProtoBufRef`]s. If a field is malformed then
+an error is yielded and iteration stops.
+*/
+#[derive(Debug, Clone)]
+pub struct Fields<'a> {
+    buf: ProtoBufRef<'a>,
+    // The index of the next chunk in `buf`
+    chunk: usize,
+}
+
+impl<'a> Fields<'a> {
+    /**
+    The offset into the internal buffer where the next chunk begins.
+
+    If there are no more chunks then this will be the end of the buffer.
+    */
+    #[inline]
+    fn boundary(&self) -> usize {
+        self.buf
+            .chunks
+            .get(self.chunk)
+            .map(|chunk| chunk.start)
+            .unwrap_or(self.buf.end)
+    }
+
+    /**
+    Skip any remaining fields, so iteration stops.
+    */
+    pub(crate) fn stop(&mut self) {
+        self.buf.start = self.buf.end;
+        self.chunk = self.buf.chunks.len();
+    }
+
+    fn read_varint(&mut self) -> Result<VarInt, Error> {
+        loop {
+            match self.buf.chunks.get(self.chunk) {
+                // If a chunk starts here then it carries the varint
+                Some(chunk) if chunk.start == self.buf.start => {
+                    self.chunk += 1;
+
+                    if let Some(varint) = chunk.varint {
+                        return Ok(VarInt::uint64(varint));
+                    }
+                }
+                _ => {
+                    let (varint, len) =
+                        VarInt::decode(&self.buf.bytes[self.buf.start..self.boundary()])?;
+                    self.buf.start += len;
+
+                    return Ok(varint);
+                }
+            }
+        }
+    }
+
+    fn read_fixed(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let bytes = self
+            .buf
+            .bytes
+            .get(self.buf.start..self.buf.start + len)
+            .filter(|_| self.buf.start + len <= self.boundary())
+            .ok_or(Error::new(ErrorKind::Truncated))?;
+
+        self.buf.start += len;
+
+        Ok(bytes)
+    }
+
+    fn read_len(&mut self, mut len: usize) -> Result<ProtoBufRef<'a>, Error> {
+        let start = self.buf.start;
+        let chunk_start = self.chunk;
+
+        loop {
+            let boundary = self.boundary();
+            let available = boundary - self.buf.start;
+
+            if len <= available {
+                self.buf.start += len;
+                break;
+            }
+
+            len -= available;
+            self.buf.start = boundary;
+
+            // Any remaining length must be in the next chunk
+            let chunk = self
+                .buf
+                .chunks
+                .get(self.chunk)
+                .ok_or(Error::new(ErrorKind::Truncated))?;
+
+            let varint_len = chunk
+                .varint
+                .map(|varint| VarInt::uint64(varint).len())
+                .unwrap_or(0);
+
+            len = len
+                .checked_sub(varint_len)
+                .ok_or(Error::new(ErrorKind::Truncated))?;
+
+            self.chunk += 1;
+        }
+
+        Ok(ProtoBufRef {
+            bytes: self.buf.bytes,
+            chunks: &self.buf.chunks[chunk_start..self.chunk],
+            start,
+            end: self.buf.start,
+        })
+    }
+
+    fn read(&mut self) -> Result<Field<ProtoBufRef<'a>>, Error> {
+        let (number, wire_type) = self.read_varint()?.to_field()?;
+
+        let payload = match wire_type {
+            WireType::VarInt => Payload::VarInt(self.read_varint()?),
+            WireType::I64 => Payload::I64(I64::from_bytes(self.read_fixed(8)?.try_into().unwrap())),
+            WireType::I32 => Payload::I32(I32::from_bytes(self.read_fixed(4)?.try_into().unwrap())),
+            WireType::Len => {
+                let len = usize::try_from(self.read_varint()?.to_uint64()).unwrap_or(usize::MAX);
+
+                Payload::Len(self.read_len(len)?)
+            }
+        };
+
+        Ok(Field::new(number, payload))
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<Field<ProtoBufRef<'a>>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.start == self.buf.end
+            && self.buf.chunks[self.chunk..]
+                .iter()
+                .all(|chunk| chunk.varint.is_none())
+        {
+            return None;
+        }
+
+        match self.read() {
+            Ok(field) => Some(Ok(field)),
+            Err(err) => {
+                self.stop();
+
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{buf::ProtoBufMut, raw};
+
+    use super::*;
+
+    fn nested() -> ProtoBuf {
+        let mut buf = ProtoBufMut::new(());
+
+        buf.push_field_varint(1);
+        buf.push_varint_uint64(42);
+
+        buf.push_field_len(2);
+        buf.begin_len(());
+        buf.push_field_len(1);
+        buf.begin_len(());
+        buf.push(b"abc");
+        buf.end_len();
+        buf.push_field_i32(2);
+        buf.push_i32_fixed32(7);
+        buf.end_len();
+
+        buf.push_field_len(3);
+        buf.begin_len(());
+        buf.end_len();
+
+        buf.push_field_i64(4);
+        buf.push_i64_fixed64(9);
+
+        buf.freeze()
+    }
+
+    #[test]
+    fn read_fields_chunked_matches_contiguous() {
+        let buf = nested();
+        let contiguous = buf.to_vec().into_owned();
+
+        let chunked = buf.fields().collect::<Result<Vec<_>, _>>().unwrap();
+        let raw = raw::Fields::new(&contiguous)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(raw.len(), chunked.len());
+
+        for (chunked, raw) in chunked.iter().zip(raw.iter()) {
+            assert_eq!(raw.number(), chunked.number());
+            assert_eq!(raw.wire_type(), chunked.wire_type());
+
+            if let (Payload::Len(chunked), Payload::Len(raw)) = (chunked.payload(), raw.payload()) {
+                assert_eq!(raw.len(), chunked.len());
+                assert_eq!(*raw, &*chunked.to_vec());
+            }
+        }
+    }
+
+    #[test]
+    fn read_fields_chunked_nested() {
+        let buf = nested();
+
+        let Payload::Len(inner) = *buf.fields().nth(1).unwrap().unwrap().payload() else {
+            panic!("expected a length-prefixed field");
+        };
+
+        assert!(inner.as_slice().is_none());
+
+        let inner = inner.fields().collect::<Result<Vec<_>, _>>().unwrap();
+
+        assert_eq!(2, inner.len());
+        assert!(matches!(inner[0].payload(), Payload::Len(v) if v.as_slice() == Some(b"abc")));
+        assert!(matches!(inner[1].payload(), Payload::I32(v) if v.to_fixed32() == 7));
+    }
+
+    #[test]
+    fn read_fields_empty() {
+        let buf = ProtoBufMut::new(()).freeze();
+
+        assert!(buf.fields().next().is_none());
+    }
+
+    #[test]
+    fn read_fields_contiguous() {
+        let buf = ProtoBuf::pre_encoded(nested().to_vec().into_owned());
+
+        assert_eq!(4, buf.fields().count());
+    }
+}
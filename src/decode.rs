@@ -0,0 +1,282 @@
+/*!
+Decoding for the protobuf wire format.
+
+Without a schema, the protobuf wire format only describes the shape of a message, not
+its contents. The [`RawMessage`] type streams an encoded message as a generic structure:
+
+- Messages are streamed as records, using the field number as both the label and the index.
+- `VARINT` fields are streamed as `u64`s.
+- `I32` fields are streamed as `u32`s tagged with [`tags::PROTOBUF_I32`].
+- `I64` fields are streamed as `u64`s tagged with [`tags::PROTOBUF_I64`].
+- `LEN` fields are streamed as binary.
+
+Streaming a [`RawMessage`] back through a [`crate::ProtoBufStream`] will reproduce the
+original encoded message.
+
+With a schema, described by a [`MessageDescriptor`], the [`TypedMessage`] type streams an
+encoded message using field names and appropriate Rust types for their values.
+
+Rust types can be decoded directly from an encoded message through the [`FromProtobuf`] trait.
+Individual fields can be read lazily, without decoding the rest of the message, through
+a [`MessageView`].
+
+Messages that arrive in fragments, such as from a network socket, can be decoded
+incrementally with a [`PushDecoder`]. Messages that are already fully buffered can be
+walked field by field with a [`ProtoBufReader`].
+*/
+
+use crate::{
+    buf::ProtoBufCursor,
+    raw::{Fields, Payload},
+    tags,
+};
+use alloc::{borrow::Cow, vec::Vec};
+use sval::{Index, Label};
+
+mod from;
+mod push;
+mod reader;
+mod schema;
+mod view;
+
+pub use self::{from::*, push::*, reader::*, schema::*, view::*};
+
+#[cfg(feature = "derive")]
+pub use sval_protobuf_derive::FromProtobuf;
+
+/**
+Stream an encoded protobuf message as a generic structure.
+
+See [`RawMessage`] for details on how encoded messages are streamed.
+*/
+pub fn stream_from_protobuf<'sval>(
+    buf: &'sval [u8],
+    stream: &mut (impl sval::Stream<'sval> + ?Sized),
+) -> sval::Result {
+    RawMessage::new(buf).stream_fields(stream)
+}
+
+/**
+An encoded protobuf message that can be streamed without a schema.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct RawMessage<'a>(&'a [u8]);
+
+impl<'a> RawMessage<'a> {
+    /**
+    Treat a buffer as an encoded protobuf message.
+    */
+    pub fn new(buf: &'a [u8]) -> Self {
+        RawMessage(buf)
+    }
+
+    /**
+    Get the underlying encoded buffer.
+    */
+    pub fn as_slice(&self) -> &'a [u8] {
+        self.0
+    }
+
+    fn stream_fields<'sval>(&self, stream: &mut (impl sval::Stream<'sval> + ?Sized)) -> sval::Result
+    where
+        'a: 'sval,
+    {
+        stream.record_tuple_begin(None, None, None, None)?;
+
+        for field in Fields::new(self.0) {
+            let field = field?;
+
+            let mut label_buf = [0; 20];
+            let label = Label::new_computed(field_label(field.number(), &mut label_buf));
+            let index = Index::new_u64(field.number());
+
+            stream.record_tuple_value_begin(None, &label, &index)?;
+
+            match field.into_payload() {
+                Payload::VarInt(value) => stream.u64(value.to_uint64())?,
+                Payload::I32(value) => {
+                    stream.tagged_begin(Some(&tags::PROTOBUF_I32), None, None)?;
+                    stream.u32(value.to_fixed32())?;
+                    stream.tagged_end(Some(&tags::PROTOBUF_I32), None, None)?;
+                }
+                Payload::I64(value) => {
+                    stream.tagged_begin(Some(&tags::PROTOBUF_I64), None, None)?;
+                    stream.u64(value.to_fixed64())?;
+                    stream.tagged_end(Some(&tags::PROTOBUF_I64), None, None)?;
+                }
+                Payload::Len(value) => {
+                    stream.binary_begin(Some(value.len()))?;
+                    stream.binary_fragment(value)?;
+                    stream.binary_end()?;
+                }
+            }
+
+            stream.record_tuple_value_end(None, &label, &index)?;
+        }
+
+        stream.record_tuple_end(None, None, None)
+    }
+}
+
+impl<'a> sval::Value for RawMessage<'a> {
+    fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(&'sval self, stream: &mut S) -> sval::Result {
+        self.stream_fields(stream)
+    }
+}
+
+impl crate::buf::ProtoBuf {
+    /**
+    Stream the encoded message as a generic structure.
+
+    See [`RawMessage`] for details on how encoded messages are streamed.
+    */
+    pub fn stream_decoded<'sval, S: sval::Stream<'sval> + ?Sized>(
+        &'sval self,
+        stream: &mut S,
+    ) -> sval::Result {
+        match self.to_vec() {
+            Cow::Borrowed(buf) => RawMessage::new(buf).stream_fields(stream),
+            Cow::Owned(buf) => stream.value_computed(&RawMessage::new(&buf)),
+        }
+    }
+}
+
+impl ProtoBufCursor {
+    /**
+    Stream the remaining contents of the cursor as a generic structure.
+
+    See [`RawMessage`] for details on how encoded messages are streamed.
+    */
+    pub fn stream_decoded<'sval, S: sval::Stream<'sval> + ?Sized>(
+        &mut self,
+        stream: &mut S,
+    ) -> sval::Result {
+        let mut buf = Vec::new();
+        self.copy_to_vec(&mut buf);
+
+        stream.value_computed(&RawMessage::new(&buf))
+    }
+}
+
+fn field_label(mut number: u64, buf: &mut [u8; 20]) -> &str {
+    let mut i = buf.len();
+
+    loop {
+        i -= 1;
+        buf[i] = b'0' + (number % 10) as u8;
+        number /= 10;
+
+        if number == 0 {
+            break;
+        }
+    }
+
+    // The buffer only contains ASCII digits
+    core::str::from_utf8(&buf[i..]).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::buf::ProtoBufMut;
+
+    fn roundtrip(encoded: &[u8]) {
+        let decoded = crate::stream_to_protobuf(RawMessage::new(encoded));
+
+        assert_eq!(encoded, &*decoded.to_vec());
+    }
+
+    #[test]
+    fn decode_roundtrip_scalar() {
+        let mut buf = ProtoBufMut::new(());
+
+        buf.push_field_varint(1);
+        buf.push_varint_uint64(42);
+
+        buf.push_field_i32(2);
+        buf.push_i32_float(1.5);
+
+        buf.push_field_i64(3);
+        buf.push_i64_double(2.5);
+
+        buf.push_field_len(16);
+        buf.push_len_varint_uint64(3);
+        buf.push(b"abc");
+
+        roundtrip(&buf.freeze().to_vec());
+    }
+
+    #[test]
+    fn decode_roundtrip_nested() {
+        let mut buf = ProtoBufMut::new(());
+
+        buf.push_field_len(1);
+        buf.begin_len(());
+        buf.push_field_varint(1);
+        buf.push_varint_sint64(-1);
+        buf.push_field_len(2);
+        buf.begin_len(());
+        buf.push(b"abc");
+        buf.end_len();
+        buf.end_len();
+
+        buf.push_field_varint(2);
+        buf.push_varint_bool(true);
+
+        let buf = buf.freeze();
+
+        roundtrip(&buf.to_vec());
+
+        assert_eq!(
+            buf.to_vec(),
+            crate::stream_to_protobuf(StreamDecoded(&buf)).to_vec()
+        );
+    }
+
+    #[test]
+    fn decode_roundtrip_empty() {
+        roundtrip(&[]);
+    }
+
+    #[test]
+    fn decode_invalid() {
+        for case in [
+            // Truncated varint
+            &[8u8, 255] as &[u8],
+            // Truncated I32
+            &[13u8, 1, 2],
+            // Truncated I64
+            &[9u8, 1, 2, 3, 4],
+            // Truncated LEN
+            &[10u8, 5, 1, 2],
+            // Group
+            &[11u8],
+        ] {
+            assert!(
+                sval::Value::stream(&RawMessage::new(case), &mut crate::ProtoBufStream::new())
+                    .is_err(),
+                "{:?}",
+                case
+            );
+        }
+    }
+
+    #[test]
+    fn decode_field_label() {
+        for case in [0, 1, 10, 536_870_911, u64::MAX] {
+            assert_eq!(alloc::format!("{}", case), field_label(case, &mut [0; 20]),);
+        }
+    }
+
+    struct StreamDecoded<'a>(&'a crate::buf::ProtoBuf);
+
+    impl<'a> sval::Value for StreamDecoded<'a> {
+        fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(
+            &'sval self,
+            stream: &mut S,
+        ) -> sval::Result {
+            self.0.stream_decoded(stream)
+        }
+    }
+}
@@ -0,0 +1,712 @@
+use crate::{
+    raw::{Fields, Payload, WireType},
+    tags, Error, ErrorKind,
+};
+
+use alloc::{boxed::Box, collections::BTreeMap, string::String, vec::Vec};
+
+/**
+Decode a Rust value from the protobuf wire format.
+
+This trait is the inverse of streaming a value through a [`crate::ProtoBufStream`]. Values
+are built by first creating an empty one with [`FromProtobuf::empty`], then merging each
+occurrence of its field into it with [`FromProtobuf::merge_field`]. This follows the protobuf
+rules for fields that appear multiple times: scalars use the last value, repeated fields are
+appended to, and messages are merged.
+
+With the `derive` Cargo feature, this trait can be derived for structs and enums that also
+derive `sval::Value`, using the same `#[sval(index)]`, `#[sval(data_tag)]`, and
+`#[sval(flatten)]` attributes to match the encoding:
+
+```
+# #[cfg(feature = "derive")]
+# fn main() -> Result<(), sval_protobuf::Error> {
+use sval_derive::Value;
+use sval_protobuf::decode::FromProtobuf;
+
+#[derive(Value, FromProtobuf, Debug, PartialEq)]
+pub struct LogRecord {
+    #[sval(index = 1, data_tag = "sval_protobuf::tags::PROTOBUF_I64")]
+    time_unix_nano: u64,
+    #[sval(index = 3)]
+    severity_text: String,
+}
+
+let record = LogRecord {
+    time_unix_nano: 1696310935000000000,
+    severity_text: "Info".to_owned(),
+};
+
+let encoded = sval_protobuf::stream_to_protobuf(&record).to_vec().into_owned();
+
+assert_eq!(record, LogRecord::from_protobuf(&encoded)?);
+# Ok(())
+# }
+# #[cfg(not(feature = "derive"))]
+# fn main() {}
+```
+*/
+pub trait FromProtobuf: Sized {
+    /**
+    The value to use when its field isn't present in an encoded message.
+    */
+    fn empty() -> Self;
+
+    /**
+    Merge the payload of a field into this value.
+
+    The `tag` is the data tag the field is annotated with, such as [`tags::PROTOBUF_I32`],
+    which determines how numeric payloads are interpreted.
+    */
+    fn merge_field(
+        &mut self,
+        payload: Payload<&[u8]>,
+        tag: Option<&sval::Tag>,
+    ) -> Result<(), Error>;
+
+    /**
+    Merge a field of a message this value has been flattened into.
+
+    Implicitly numbered fields are shifted by `offset`, the position of this value in
+    the parent. This method returns `false` if the field doesn't belong to this value.
+    */
+    fn merge_flattened(
+        &mut self,
+        offset: u64,
+        number: u64,
+        payload: Payload<&[u8]>,
+    ) -> Result<bool, Error> {
+        let _ = (offset, number, payload);
+
+        Ok(false)
+    }
+
+    /**
+    Merge an encoded message into this value.
+
+    Standalone values are encoded as a message with a single field numbered `1`. Messages
+    override this method to merge each of their fields.
+    */
+    fn merge_message(&mut self, buf: &[u8]) -> Result<(), Error> {
+        for field in Fields::new(buf) {
+            let field = field?;
+
+            if field.number() == 1 {
+                self.merge_field(field.into_payload(), None)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+    The wire type of this value when it's an element of a packed repeated field.
+
+    Values that can't be packed return `None`.
+    */
+    fn packed_wire_type(tag: Option<&sval::Tag>) -> Option<WireType> {
+        let _ = tag;
+
+        None
+    }
+
+    /**
+    Decode a value from an encoded message.
+    */
+    fn from_protobuf(buf: &[u8]) -> Result<Self, Error> {
+        let mut value = Self::empty();
+        value.merge_message(buf)?;
+
+        Ok(value)
+    }
+}
+
+/**
+Decode a value from an encoded message.
+*/
+pub fn from_protobuf<T: FromProtobuf>(buf: &[u8]) -> Result<T, Error> {
+    T::from_protobuf(buf)
+}
+
+#[inline]
+fn invalid_wire_type<T>() -> Result<T, Error> {
+    Err(Error::new(ErrorKind::InvalidWireType))
+}
+
+#[inline]
+fn fixed_wire_type(tag: Option<&sval::Tag>) -> WireType {
+    match tag {
+        Some(&tags::PROTOBUF_I32) => WireType::I32,
+        Some(&tags::PROTOBUF_I64) => WireType::I64,
+        _ => WireType::VarInt,
+    }
+}
+
+impl FromProtobuf for bool {
+    fn empty() -> Self {
+        false
+    }
+
+    fn merge_field(&mut self, payload: Payload<&[u8]>, _: Option<&sval::Tag>) -> Result<(), Error> {
+        match payload {
+            Payload::VarInt(v) => {
+                *self = v.to_bool();
+
+                Ok(())
+            }
+            _ => invalid_wire_type(),
+        }
+    }
+
+    fn packed_wire_type(_: Option<&sval::Tag>) -> Option<WireType> {
+        Some(WireType::VarInt)
+    }
+}
+
+macro_rules! impl_unsigned {
+    ($($ty:ty),*) => {
+        $(
+            impl FromProtobuf for $ty {
+                fn empty() -> Self {
+                    0
+                }
+
+                fn merge_field(
+                    &mut self,
+                    payload: Payload<&[u8]>,
+                    _: Option<&sval::Tag>,
+                ) -> Result<(), Error> {
+                    let v = match payload {
+                        Payload::VarInt(v) => v.to_uint64(),
+                        Payload::I32(v) => v.to_fixed32() as u64,
+                        Payload::I64(v) => v.to_fixed64(),
+                        Payload::Len(_) => return invalid_wire_type(),
+                    };
+
+                    *self = v.try_into().map_err(|_| Error::new(ErrorKind::InvalidValue))?;
+
+                    Ok(())
+                }
+
+                fn packed_wire_type(tag: Option<&sval::Tag>) -> Option<WireType> {
+                    Some(fixed_wire_type(tag))
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_signed {
+    ($($ty:ty),*) => {
+        $(
+            impl FromProtobuf for $ty {
+                fn empty() -> Self {
+                    0
+                }
+
+                fn merge_field(
+                    &mut self,
+                    payload: Payload<&[u8]>,
+                    tag: Option<&sval::Tag>,
+                ) -> Result<(), Error> {
+                    let v = match payload {
+                        Payload::VarInt(v) if tag == Some(&tags::PROTOBUF_VARINT_SIGNED) => {
+                            v.to_sint64z()
+                        }
+                        Payload::VarInt(v) => v.to_sint64(),
+                        Payload::I32(v) => v.to_sfixed32() as i64,
+                        Payload::I64(v) => v.to_sfixed64(),
+                        Payload::Len(_) => return invalid_wire_type(),
+                    };
+
+                    *self = v.try_into().map_err(|_| Error::new(ErrorKind::InvalidValue))?;
+
+                    Ok(())
+                }
+
+                fn packed_wire_type(tag: Option<&sval::Tag>) -> Option<WireType> {
+                    Some(fixed_wire_type(tag))
+                }
+            }
+        )*
+    };
+}
+
+impl_unsigned!(u16, u32, u64);
+impl_signed!(i8, i16, i32, i64);
+
+impl FromProtobuf for u8 {
+    fn empty() -> Self {
+        0
+    }
+
+    fn merge_field(&mut self, payload: Payload<&[u8]>, _: Option<&sval::Tag>) -> Result<(), Error> {
+        let v = match payload {
+            Payload::VarInt(v) => v.to_uint64(),
+            Payload::I32(v) => v.to_fixed32() as u64,
+            Payload::I64(v) => v.to_fixed64(),
+            // A length-prefixed payload is most likely a `bytes` field, which should be decoded as `Bytes`
+            Payload::Len(_) => return Err(Error::new(ErrorKind::InvalidValue)),
+        };
+
+        *self = v
+            .try_into()
+            .map_err(|_| Error::new(ErrorKind::InvalidValue))?;
+
+        Ok(())
+    }
+
+    fn packed_wire_type(tag: Option<&sval::Tag>) -> Option<WireType> {
+        // Packed bytes can't be distinguished from a `bytes` field unless they're tagged
+        match tag {
+            Some(&tags::PROTOBUF_LEN_PACKED) => Some(WireType::VarInt),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_128bit {
+    ($($ty:ty),*) => {
+        $(
+            impl FromProtobuf for $ty {
+                fn empty() -> Self {
+                    0
+                }
+
+                fn merge_field(
+                    &mut self,
+                    payload: Payload<&[u8]>,
+                    _: Option<&sval::Tag>,
+                ) -> Result<(), Error> {
+                    match payload {
+                        // 128bit numbers are encoded as 16 little-endian bytes
+                        Payload::Len(v) => {
+                            let bytes = v
+                                .try_into()
+                                .map_err(|_| Error::new(ErrorKind::InvalidValue))?;
+
+                            *self = <$ty>::from_le_bytes(bytes);
+
+                            Ok(())
+                        }
+                        _ => invalid_wire_type(),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_128bit!(u128, i128);
+
+impl FromProtobuf for f32 {
+    fn empty() -> Self {
+        0.0
+    }
+
+    fn merge_field(&mut self, payload: Payload<&[u8]>, _: Option<&sval::Tag>) -> Result<(), Error> {
+        match payload {
+            Payload::I32(v) => {
+                *self = v.to_float();
+
+                Ok(())
+            }
+            _ => invalid_wire_type(),
+        }
+    }
+
+    fn packed_wire_type(_: Option<&sval::Tag>) -> Option<WireType> {
+        Some(WireType::I32)
+    }
+}
+
+impl FromProtobuf for f64 {
+    fn empty() -> Self {
+        0.0
+    }
+
+    fn merge_field(&mut self, payload: Payload<&[u8]>, _: Option<&sval::Tag>) -> Result<(), Error> {
+        match payload {
+            Payload::I64(v) => {
+                *self = v.to_double();
+
+                Ok(())
+            }
+            _ => invalid_wire_type(),
+        }
+    }
+
+    fn packed_wire_type(_: Option<&sval::Tag>) -> Option<WireType> {
+        Some(WireType::I64)
+    }
+}
+
+impl FromProtobuf for String {
+    fn empty() -> Self {
+        String::new()
+    }
+
+    fn merge_field(&mut self, payload: Payload<&[u8]>, _: Option<&sval::Tag>) -> Result<(), Error> {
+        match payload {
+            Payload::Len(v) => {
+                let v = core::str::from_utf8(v).map_err(|_| Error::new(ErrorKind::InvalidValue))?;
+
+                self.clear();
+                self.push_str(v);
+
+                Ok(())
+            }
+            _ => invalid_wire_type(),
+        }
+    }
+}
+
+/**
+An owned binary value, encoded as a protobuf `bytes` field.
+
+A `Vec<u8>` is a sequence of integers rather than binary, so `bytes` fields should be decoded
+into a `Bytes` instead.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Bytes(Vec<u8>);
+
+impl Bytes {
+    /**
+    Create a new binary value.
+    */
+    pub fn new(bytes: impl Into<Vec<u8>>) -> Self {
+        Bytes(bytes.into())
+    }
+
+    /**
+    Get the binary value as a slice.
+    */
+    pub fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+
+    /**
+    Get the binary value as a vector.
+    */
+    pub fn into_vec(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl From<Vec<u8>> for Bytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Bytes(bytes)
+    }
+}
+
+impl From<Bytes> for Vec<u8> {
+    fn from(bytes: Bytes) -> Self {
+        bytes.0
+    }
+}
+
+impl sval::Value for Bytes {
+    fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(&'sval self, stream: &mut S) -> sval::Result {
+        stream.binary_begin(Some(self.0.len()))?;
+        stream.binary_fragment(&self.0)?;
+        stream.binary_end()
+    }
+}
+
+impl FromProtobuf for Bytes {
+    fn empty() -> Self {
+        Bytes::default()
+    }
+
+    fn merge_field(&mut self, payload: Payload<&[u8]>, _: Option<&sval::Tag>) -> Result<(), Error> {
+        match payload {
+            Payload::Len(v) => {
+                self.0.clear();
+                self.0.extend_from_slice(v);
+
+                Ok(())
+            }
+            _ => invalid_wire_type(),
+        }
+    }
+}
+
+impl<T: FromProtobuf> FromProtobuf for Option<T> {
+    fn empty() -> Self {
+        None
+    }
+
+    fn merge_field(
+        &mut self,
+        payload: Payload<&[u8]>,
+        tag: Option<&sval::Tag>,
+    ) -> Result<(), Error> {
+        let value = self.get_or_insert_with(T::empty);
+
+        match (payload, tag) {
+            // Wrapped scalars are in field 1 of a message
+            (Payload::Len(buf), Some(&tags::PROTOBUF_WRAPPER)) => {
+                for field in Fields::new(buf) {
+                    let field = field?;
+
+                    if field.number() == 1 {
+                        value.merge_field(field.into_payload(), None)?;
+                    }
+                }
+
+                Ok(())
+            }
+            (payload, tag) => value.merge_field(payload, tag),
+        }
+    }
+
+    fn merge_flattened(
+        &mut self,
+        offset: u64,
+        number: u64,
+        payload: Payload<&[u8]>,
+    ) -> Result<bool, Error> {
+        match self {
+            Some(value) => value.merge_flattened(offset, number, payload),
+            None => {
+                let mut value = T::empty();
+
+                if value.merge_flattened(offset, number, payload)? {
+                    *self = Some(value);
+
+                    Ok(true)
+                } else {
+                    Ok(false)
+                }
+            }
+        }
+    }
+
+    fn merge_message(&mut self, buf: &[u8]) -> Result<(), Error> {
+        self.get_or_insert_with(T::empty).merge_message(buf)
+    }
+}
+
+impl<T: FromProtobuf> FromProtobuf for Box<T> {
+    fn empty() -> Self {
+        Box::new(T::empty())
+    }
+
+    fn merge_field(
+        &mut self,
+        payload: Payload<&[u8]>,
+        tag: Option<&sval::Tag>,
+    ) -> Result<(), Error> {
+        (**self).merge_field(payload, tag)
+    }
+
+    fn merge_flattened(
+        &mut self,
+        offset: u64,
+        number: u64,
+        payload: Payload<&[u8]>,
+    ) -> Result<bool, Error> {
+        (**self).merge_flattened(offset, number, payload)
+    }
+
+    fn merge_message(&mut self, buf: &[u8]) -> Result<(), Error> {
+        (**self).merge_message(buf)
+    }
+
+    fn packed_wire_type(tag: Option<&sval::Tag>) -> Option<WireType> {
+        T::packed_wire_type(tag)
+    }
+}
+
+impl<T: FromProtobuf> FromProtobuf for Vec<T> {
+    fn empty() -> Self {
+        Vec::new()
+    }
+
+    fn merge_field(
+        &mut self,
+        payload: Payload<&[u8]>,
+        tag: Option<&sval::Tag>,
+    ) -> Result<(), Error> {
+        // Numeric elements may be packed into a single length-prefixed field,
+        // regardless of whether they're tagged with `PROTOBUF_LEN_PACKED`
+        if let (Payload::Len(mut buf), Some(wire_type)) = (payload, T::packed_wire_type(tag)) {
+            while !buf.is_empty() {
+                let (payload, len) = Payload::decode_packed(buf, wire_type)?;
+                buf = &buf[len..];
+
+                let mut value = T::empty();
+                value.merge_field(payload, tag)?;
+
+                self.push(value);
+            }
+
+            return Ok(());
+        }
+
+        let mut value = T::empty();
+        value.merge_field(payload, tag)?;
+
+        self.push(value);
+
+        Ok(())
+    }
+}
+
+impl<K: FromProtobuf + Ord, V: FromProtobuf> FromProtobuf for BTreeMap<K, V> {
+    fn empty() -> Self {
+        BTreeMap::new()
+    }
+
+    fn merge_field(
+        &mut self,
+        payload: Payload<&[u8]>,
+        tag: Option<&sval::Tag>,
+    ) -> Result<(), Error> {
+        // Map entries are messages with the key in field 1 and the value in field 2
+        // Any tag on the map applies to its values
+        let Payload::Len(entry) = payload else {
+            return invalid_wire_type();
+        };
+
+        let mut key = K::empty();
+        let mut value = V::empty();
+
+        for field in Fields::new(entry) {
+            let field = field?;
+
+            match field.number() {
+                1 => key.merge_field(field.into_payload(), None)?,
+                2 => value.merge_field(field.into_payload(), tag)?,
+                _ => (),
+            }
+        }
+
+        self.insert(key, value);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::stream_to_protobuf;
+
+    use alloc::{borrow::ToOwned, vec};
+
+    fn roundtrip<T: FromProtobuf>(v: impl sval::Value) -> T {
+        T::from_protobuf(&stream_to_protobuf(v).to_vec()).unwrap()
+    }
+
+    #[test]
+    fn decode_scalar() {
+        assert!(roundtrip::<bool>(true));
+        assert_eq!(u8::MAX, roundtrip::<u8>(u8::MAX));
+        assert_eq!(u32::MAX, roundtrip::<u32>(u32::MAX));
+        assert_eq!(u64::MAX, roundtrip::<u64>(u64::MAX));
+        assert_eq!(i32::MIN, roundtrip::<i32>(i32::MIN));
+        assert_eq!(i64::MIN, roundtrip::<i64>(i64::MIN));
+        assert_eq!(u128::MAX, roundtrip::<u128>(u128::MAX));
+        assert_eq!(i128::MIN, roundtrip::<i128>(i128::MIN));
+        assert_eq!(1.5, roundtrip::<f32>(1.5f32));
+        assert_eq!(-2.5, roundtrip::<f64>(-2.5f64));
+        assert_eq!("abc", roundtrip::<String>("abc"));
+        assert_eq!(
+            Bytes::new(vec![0x80, 0x01]),
+            roundtrip::<Bytes>(sval::BinarySlice::new(&[0x80, 0x01]))
+        );
+    }
+
+    #[test]
+    fn decode_bytes_not_packed() {
+        let encoded = stream_to_protobuf(sval::BinarySlice::new(&[0x80, 0x01]))
+            .to_vec()
+            .into_owned();
+
+        assert_eq!(
+            ErrorKind::InvalidValue,
+            Vec::<u8>::from_protobuf(&encoded).unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn decode_scalar_out_of_range() {
+        let encoded = stream_to_protobuf(300u32).to_vec().into_owned();
+
+        assert_eq!(
+            ErrorKind::InvalidValue,
+            u8::from_protobuf(&encoded).unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn decode_tagged() {
+        struct Tagged<T>(sval::Tag, T);
+
+        impl<T: sval::Value> sval::Value for Tagged<T> {
+            fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(
+                &'sval self,
+                stream: &mut S,
+            ) -> sval::Result {
+                stream.tagged_begin(Some(&self.0), None, None)?;
+                stream.value(&self.1)?;
+                stream.tagged_end(Some(&self.0), None, None)
+            }
+        }
+
+        fn merge<T: FromProtobuf>(tag: sval::Tag, v: impl sval::Value) -> T {
+            let encoded = stream_to_protobuf(Tagged(tag, v)).to_vec().into_owned();
+            let field = Fields::new(&encoded).next().unwrap().unwrap();
+
+            let mut value = T::empty();
+            value.merge_field(field.into_payload(), Some(&tag)).unwrap();
+
+            value
+        }
+
+        assert_eq!(-3, merge::<i64>(tags::PROTOBUF_VARINT_SIGNED, -3i64));
+        assert_eq!(-3, merge::<i32>(tags::PROTOBUF_I32, -3i32));
+        assert_eq!(7, merge::<u64>(tags::PROTOBUF_I64, 7u64));
+        assert_eq!(
+            vec![1, -2, 3],
+            merge::<Vec<i32>>(tags::PROTOBUF_LEN_PACKED, [1, -2, 3])
+        );
+    }
+
+    #[test]
+    fn decode_repeated() {
+        assert_eq!(
+            vec!["a".to_owned(), "b".to_owned()],
+            roundtrip::<Vec<String>>(["a", "b"])
+        );
+        assert_eq!(vec![1u64, 2, 3], roundtrip::<Vec<u64>>([1u64, 2, 3]));
+    }
+
+    #[test]
+    fn decode_map() {
+        let map = roundtrip::<BTreeMap<String, i32>>(sval::MapSlice::new(&[("a", 1), ("b", 2)]));
+
+        assert_eq!(Some(&1), map.get("a"));
+        assert_eq!(Some(&2), map.get("b"));
+    }
+
+    #[test]
+    fn decode_last_one_wins() {
+        let encoded = [8, 1, 8, 2];
+
+        assert_eq!(2, u32::from_protobuf(&encoded).unwrap());
+        assert_eq!(vec![1, 2], Vec::<u32>::from_protobuf(&encoded).unwrap());
+    }
+
+    #[test]
+    fn decode_invalid_wire_type() {
+        let encoded = stream_to_protobuf("abc").to_vec().into_owned();
+
+        assert_eq!(
+            ErrorKind::InvalidWireType,
+            u32::from_protobuf(&encoded).unwrap_err().kind()
+        );
+    }
+}
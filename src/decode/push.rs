@@ -0,0 +1,591 @@
+use crate::{
+    raw::{VarInt, WireType, I32, I64},
+    Error, ErrorKind,
+};
+
+use alloc::vec::Vec;
+
+/**
+The default maximum nesting depth of a [`PushDecoder`].
+*/
+pub const DEFAULT_MAX_DEPTH: usize = 100;
+
+/**
+A resumable decoder that accepts an encoded message in arbitrary fragments.
+
+Fragments are given to the decoder through [`PushDecoder::push`] as they arrive, and events
+are emitted to a [`PushVisitor`] as fields complete. Variable-length integers and length-prefixes
+may be split across fragments.
+
+Length-prefixed fields are either decoded as nested messages, or streamed as opaque payloads,
+depending on what the [`PushVisitor`] chooses when they begin.
+*/
+#[derive(Debug)]
+pub struct PushDecoder {
+    state: State,
+    partial: Partial,
+    stack: Vec<Frame>,
+    consumed: u64,
+    max_depth: usize,
+    max_len: u64,
+}
+
+/**
+A receiver of events from a [`PushDecoder`].
+*/
+pub trait PushVisitor {
+    /**
+    A field with a variable-length encoded value.
+    */
+    fn varint(&mut self, number: u64, value: VarInt);
+
+    /**
+    A field with a 32bit fixed-length encoded value.
+    */
+    fn i32(&mut self, number: u64, value: I32);
+
+    /**
+    A field with a 64bit fixed-length encoded value.
+    */
+    fn i64(&mut self, number: u64, value: I64);
+
+    /**
+    The start of a length-prefixed field with a payload of `len` bytes.
+
+    If this method returns `true` then the payload will be decoded as a nested message.
+    If this method returns `false` then the payload will be passed to [`PushVisitor::len_fragment`].
+    */
+    fn len_begin(&mut self, number: u64, len: u64) -> bool {
+        let _ = (number, len);
+
+        false
+    }
+
+    /**
+    A fragment of a length-prefixed payload that isn't being decoded as a nested message.
+    */
+    fn len_fragment(&mut self, fragment: &[u8]) {
+        let _ = fragment;
+    }
+
+    /**
+    The end of a length-prefixed field.
+    */
+    fn len_end(&mut self) {}
+}
+
+impl<V: PushVisitor + ?Sized> PushVisitor for &mut V {
+    fn varint(&mut self, number: u64, value: VarInt) {
+        (**self).varint(number, value)
+    }
+
+    fn i32(&mut self, number: u64, value: I32) {
+        (**self).i32(number, value)
+    }
+
+    fn i64(&mut self, number: u64, value: I64) {
+        (**self).i64(number, value)
+    }
+
+    fn len_begin(&mut self, number: u64, len: u64) -> bool {
+        (**self).len_begin(number, len)
+    }
+
+    fn len_fragment(&mut self, fragment: &[u8]) {
+        (**self).len_fragment(fragment)
+    }
+
+    fn len_end(&mut self) {
+        (**self).len_end()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Key,
+    VarInt { number: u64 },
+    I32 { number: u64 },
+    I64 { number: u64 },
+    Len { number: u64 },
+    Payload { remaining: u64 },
+}
+
+#[derive(Debug, Default)]
+struct Partial {
+    buf: [u8; 10],
+    len: usize,
+}
+
+#[derive(Debug)]
+struct Frame {
+    // The value of `consumed` when this message ends
+    end: u64,
+}
+
+impl Default for PushDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PushDecoder {
+    /**
+    Create a new decoder with default limits.
+    */
+    pub fn new() -> Self {
+        PushDecoder {
+            state: State::Key,
+            partial: Partial::default(),
+            stack: Vec::new(),
+            consumed: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_len: u64::MAX,
+        }
+    }
+
+    /**
+    Set the maximum number of nested messages that can be open at once.
+    */
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /**
+    Set the maximum total number of bytes that can be decoded.
+    */
+    pub fn with_max_len(mut self, max_len: u64) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /**
+    The total number of bytes decoded so far.
+    */
+    pub fn consumed(&self) -> u64 {
+        self.consumed
+    }
+
+    /**
+    The number of nested messages currently open.
+    */
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /**
+    Decode the next fragment of the message.
+
+    If an error is returned then the decoder can't be used any further.
+    */
+    pub fn push(
+        &mut self,
+        mut fragment: &[u8],
+        mut visitor: impl PushVisitor,
+    ) -> Result<(), Error> {
+        if self.max_len - self.consumed < fragment.len() as u64 {
+            return Err(ErrorKind::LimitExceeded.into());
+        }
+
+        loop {
+            self.end_complete(&mut visitor);
+
+            if fragment.is_empty() {
+                return Ok(());
+            }
+
+            match self.state {
+                State::Payload { remaining } => {
+                    let len = remaining.min(fragment.len() as u64) as usize;
+
+                    let (payload, rest) = fragment.split_at(len);
+                    fragment = rest;
+
+                    self.consumed += len as u64;
+                    visitor.len_fragment(payload);
+
+                    if remaining == len as u64 {
+                        self.state = State::Key;
+                        visitor.len_end();
+                    } else {
+                        self.state = State::Payload {
+                            remaining: remaining - len as u64,
+                        };
+                    }
+                }
+                State::I32 { number } => {
+                    if let Some(buf) = self.take_fixed::<4>(&mut fragment)? {
+                        self.state = State::Key;
+                        visitor.i32(number, I32::from_bytes(buf));
+                    }
+                }
+                State::I64 { number } => {
+                    if let Some(buf) = self.take_fixed::<8>(&mut fragment)? {
+                        self.state = State::Key;
+                        visitor.i64(number, I64::from_bytes(buf));
+                    }
+                }
+                State::Key => {
+                    if let Some(key) = self.take_varint(&mut fragment)? {
+                        let (number, wire_type) = key.to_field()?;
+
+                        self.state = match wire_type {
+                            WireType::VarInt => State::VarInt { number },
+                            WireType::I32 => State::I32 { number },
+                            WireType::I64 => State::I64 { number },
+                            WireType::Len => State::Len { number },
+                        };
+                    }
+                }
+                State::VarInt { number } => {
+                    if let Some(value) = self.take_varint(&mut fragment)? {
+                        self.state = State::Key;
+                        visitor.varint(number, value);
+                    }
+                }
+                State::Len { number } => {
+                    if let Some(len) = self.take_varint(&mut fragment)? {
+                        let len = len.to_uint64();
+
+                        // The payload must fit within its parent message
+                        if self.remaining() < len {
+                            return Err(ErrorKind::Truncated.into());
+                        }
+
+                        if self.max_len - self.consumed < len {
+                            return Err(ErrorKind::LimitExceeded.into());
+                        }
+
+                        if visitor.len_begin(number, len) {
+                            if self.stack.len() >= self.max_depth {
+                                return Err(ErrorKind::LimitExceeded.into());
+                            }
+
+                            self.state = State::Key;
+                            self.stack.push(Frame {
+                                end: self.consumed + len,
+                            });
+                        } else if len == 0 {
+                            self.state = State::Key;
+                            visitor.len_end();
+                        } else {
+                            self.state = State::Payload { remaining: len };
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /**
+    Complete the message, checking that no fields were left incomplete.
+    */
+    pub fn finish(mut self, mut visitor: impl PushVisitor) -> Result<(), Error> {
+        self.end_complete(&mut visitor);
+
+        match self.state {
+            State::Key if self.partial.len == 0 && self.stack.is_empty() => Ok(()),
+            _ => Err(ErrorKind::Truncated.into()),
+        }
+    }
+
+    fn end_complete(&mut self, mut visitor: impl PushVisitor) {
+        if let State::Key = self.state {
+            if self.partial.len != 0 {
+                return;
+            }
+
+            while let Some(frame) = self.stack.last() {
+                if frame.end != self.consumed {
+                    break;
+                }
+
+                self.stack.pop();
+                visitor.len_end();
+            }
+        }
+    }
+
+    fn remaining(&self) -> u64 {
+        self.stack
+            .last()
+            .map(|frame| frame.end - self.consumed)
+            .unwrap_or(u64::MAX)
+    }
+
+    fn take_varint(&mut self, fragment: &mut &[u8]) -> Result<Option<VarInt>, Error> {
+        while let Some((b, rest)) = fragment.split_first() {
+            if self.remaining() == 0 {
+                return Err(ErrorKind::Truncated.into());
+            }
+
+            if self.partial.len == self.partial.buf.len() {
+                return Err(ErrorKind::Overlong.into());
+            }
+
+            *fragment = rest;
+            self.consumed += 1;
+
+            self.partial.buf[self.partial.len] = *b;
+            self.partial.len += 1;
+
+            if b & 0b1000_0000 == 0 {
+                let (varint, _) = VarInt::decode(&self.partial.buf[..self.partial.len])?;
+                self.partial.len = 0;
+
+                return Ok(Some(varint));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn take_fixed<const N: usize>(
+        &mut self,
+        fragment: &mut &[u8],
+    ) -> Result<Option<[u8; N]>, Error> {
+        let len = (N - self.partial.len).min(fragment.len());
+
+        if self.remaining() < len as u64 {
+            return Err(ErrorKind::Truncated.into());
+        }
+
+        let (bytes, rest) = fragment.split_at(len);
+        *fragment = rest;
+        self.consumed += len as u64;
+
+        self.partial.buf[self.partial.len..self.partial.len + len].copy_from_slice(bytes);
+        self.partial.len += len;
+
+        if self.partial.len == N {
+            self.partial.len = 0;
+
+            Ok(Some(self.partial.buf[..N].try_into().unwrap()))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::buf::ProtoBufMut;
+    use alloc::{format, string::String, vec::Vec};
+
+    #[derive(Default)]
+    struct Events {
+        nested: &'static [u64],
+        events: Vec<String>,
+    }
+
+    impl PushVisitor for Events {
+        fn varint(&mut self, number: u64, value: VarInt) {
+            self.events
+                .push(format!("{}: {}", number, value.to_uint64()));
+        }
+
+        fn i32(&mut self, number: u64, value: I32) {
+            self.events
+                .push(format!("{}: {}i32", number, value.to_fixed32()));
+        }
+
+        fn i64(&mut self, number: u64, value: I64) {
+            self.events
+                .push(format!("{}: {}i64", number, value.to_fixed64()));
+        }
+
+        fn len_begin(&mut self, number: u64, len: u64) -> bool {
+            self.events.push(format!("{}: {{{}", number, len));
+
+            self.nested.contains(&number)
+        }
+
+        fn len_fragment(&mut self, fragment: &[u8]) {
+            // Merge fragments so events don't depend on how the input was split
+            if let Some(last) = self.events.last_mut() {
+                if last.starts_with('"') {
+                    last.push_str(core::str::from_utf8(fragment).unwrap());
+                    return;
+                }
+            }
+
+            self.events
+                .push(format!("\"{}", core::str::from_utf8(fragment).unwrap()));
+        }
+
+        fn len_end(&mut self) {
+            self.events.push("}".into());
+        }
+    }
+
+    fn message() -> Vec<u8> {
+        let mut buf = ProtoBufMut::new(());
+
+        buf.push_field_varint(1);
+        buf.push_varint_uint64(u64::MAX);
+
+        buf.push_field_len(2);
+        buf.begin_len(());
+        buf.push_field_len(3);
+        buf.begin_len(());
+        buf.push(b"some text");
+        buf.end_len();
+        buf.push_field_i32(4);
+        buf.push_i32_fixed32(42);
+        buf.push_field_len(3);
+        buf.begin_len(());
+        buf.end_len();
+        buf.end_len();
+
+        buf.push_field_i64(5);
+        buf.push_i64_fixed64(7);
+
+        buf.push_field_len(2);
+        buf.begin_len(());
+        buf.end_len();
+
+        buf.freeze().to_vec().into_owned()
+    }
+
+    fn decode_split(buf: &[u8], at: &[usize]) -> Result<Vec<String>, Error> {
+        let mut decoder = PushDecoder::new();
+        let mut events = Events {
+            nested: &[2],
+            ..Default::default()
+        };
+
+        let mut from = 0;
+        for to in at.iter().copied().chain(Some(buf.len())) {
+            decoder.push(&buf[from..to], &mut events)?;
+            from = to;
+        }
+
+        decoder.finish(&mut events)?;
+
+        Ok(events.events)
+    }
+
+    #[test]
+    fn push_whole() {
+        let events = decode_split(&message(), &[]).unwrap();
+
+        assert_eq!(
+            &[
+                "1: 18446744073709551615",
+                "2: {18",
+                "3: {9",
+                "\"some text",
+                "}",
+                "4: 42i32",
+                "3: {0",
+                "}",
+                "}",
+                "5: 7i64",
+                "2: {0",
+                "}",
+            ] as &[&str],
+            &*events
+        );
+    }
+
+    #[test]
+    fn push_split_anywhere() {
+        let buf = message();
+        let expected = decode_split(&buf, &[]).unwrap();
+
+        for at in 0..buf.len() {
+            assert_eq!(expected, decode_split(&buf, &[at]).unwrap(), "{}", at);
+        }
+
+        let every_byte = (0..buf.len()).collect::<Vec<_>>();
+        assert_eq!(expected, decode_split(&buf, &every_byte).unwrap());
+    }
+
+    #[test]
+    fn push_truncated() {
+        let buf = message();
+
+        // Cutting the message between top-level fields is still valid
+        let mut boundaries = Vec::new();
+        let mut fields = crate::raw::Fields::new(&buf);
+        while let Some(Ok(_)) = fields.next() {
+            boundaries.push(buf.len() - fields.remaining().len());
+        }
+
+        for len in (1..buf.len()).filter(|len| !boundaries.contains(len)) {
+            let mut decoder = PushDecoder::new();
+            let mut events = Events {
+                nested: &[2],
+                ..Default::default()
+            };
+
+            decoder.push(&buf[..len], &mut events).unwrap();
+
+            assert_eq!(
+                ErrorKind::Truncated,
+                decoder.finish(&mut events).unwrap_err().kind(),
+                "{}",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn push_nested_overflows_parent() {
+        // 1: {2: {...5 bytes}} where the outer message is only 3 bytes long
+        let buf = [10u8, 3, 18, 5, 1, 2, 3, 4, 5];
+
+        let mut decoder = PushDecoder::new();
+        let mut events = Events {
+            nested: &[1],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            ErrorKind::Truncated,
+            decoder.push(&buf, &mut events).unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn push_max_depth() {
+        // 1: {1: {1: {}}}
+        let buf = [10u8, 4, 10, 2, 10, 0];
+
+        let mut events = Events {
+            nested: &[1],
+            ..Default::default()
+        };
+
+        assert!(PushDecoder::new()
+            .with_max_depth(3)
+            .push(&buf, &mut events)
+            .is_ok());
+
+        assert_eq!(
+            ErrorKind::LimitExceeded,
+            PushDecoder::new()
+                .with_max_depth(2)
+                .push(&buf, &mut events)
+                .unwrap_err()
+                .kind()
+        );
+    }
+
+    #[test]
+    fn push_max_len() {
+        let buf = message();
+
+        let mut decoder = PushDecoder::new().with_max_len(buf.len() as u64 - 1);
+        let mut events = Events::default();
+
+        decoder.push(&buf[..8], &mut events).unwrap();
+
+        assert_eq!(
+            ErrorKind::LimitExceeded,
+            decoder.push(&buf[8..], &mut events).unwrap_err().kind()
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use crate::{
+    buf::{Fields, ProtoBufRef},
+    raw::{Field, Payload},
+    Error, ErrorKind,
+};
+
+use alloc::vec::Vec;
+
+/**
+A pull-style reader over an encoded message that can descend into nested messages.
+
+The reader uses a stack to track the bounds of nested messages, in the same way
+[`crate::buf::ProtoBufMut`] does when encoding them. After reading a length-prefixed field,
+call [`ProtoBufReader::begin_len`] to read its payload as a message, and
+[`ProtoBufReader::end_len`] to return to the parent.
+*/
+#[derive(Debug, Clone)]
+pub struct ProtoBufReader<'a> {
+    fields: Fields<'a>,
+    current: Option<ProtoBufRef<'a>>,
+    len_stack: Vec<Fields<'a>>,
+}
+
+impl<'a> ProtoBufReader<'a> {
+    /**
+    Create a reader over an encoded message.
+
+    The message may be a contiguous buffer or a [`crate::buf::ProtoBuf`].
+    */
+    pub fn new(buf: impl Into<ProtoBufRef<'a>>) -> Self {
+        ProtoBufReader {
+            fields: buf.into().fields(),
+            current: None,
+            len_stack: Vec::new(),
+        }
+    }
+
+    /**
+    The current depth of nested messages.
+    */
+    pub fn depth(&self) -> usize {
+        self.len_stack.len()
+    }
+
+    /**
+    Read the next field in the current message.
+
+    This method returns `None` when there are no more fields in the current message.
+    Fields in parent messages won't be read until [`ProtoBufReader::end_len`] is called.
+    */
+    pub fn next_field(&mut self) -> Result<Option<Field<ProtoBufRef<'a>>>, Error> {
+        self.current = None;
+
+        match self.fields.next() {
+            Some(Ok(field)) => {
+                if let Payload::Len(payload) = field.payload() {
+                    self.current = Some(*payload);
+                }
+
+                Ok(Some(field))
+            }
+            Some(Err(err)) => Err(err),
+            None => Ok(None),
+        }
+    }
+
+    /**
+    Begin reading the payload of the last field read as a nested message.
+
+    The last field read by [`ProtoBufReader::next_field`] must be length-prefixed.
+    */
+    pub fn begin_len(&mut self) -> Result<(), Error> {
+        let payload = self
+            .current
+            .take()
+            .ok_or(Error::new(ErrorKind::InvalidWireType))?;
+
+        let parent = core::mem::replace(&mut self.fields, payload.fields());
+        self.len_stack.push(parent);
+
+        Ok(())
+    }
+
+    /**
+    Complete reading a nested message, returning to its parent.
+
+    Any remaining fields in the nested message are skipped. This method fails if there's
+    no nested message to complete.
+    */
+    pub fn end_len(&mut self) -> Result<(), Error> {
+        let parent = self
+            .len_stack
+            .pop()
+            .ok_or(Error::new(ErrorKind::Unbalanced))?;
+
+        self.current = None;
+        self.fields = parent;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::buf::ProtoBufMut;
+
+    fn nested() -> crate::buf::ProtoBuf {
+        let mut buf = ProtoBufMut::new(());
+
+        buf.push_field_len(1);
+        buf.begin_len(());
+        buf.push_field_len(1);
+        buf.begin_len(());
+        buf.push_field_varint(1);
+        buf.push_varint_uint64(7);
+        buf.end_len();
+        buf.push_field_varint(2);
+        buf.push_varint_uint64(3);
+        buf.end_len();
+
+        buf.push_field_varint(2);
+        buf.push_varint_uint64(5);
+
+        buf.freeze()
+    }
+
+    fn varint(field: Option<Field<ProtoBufRef>>) -> (u64, u64) {
+        let field = field.unwrap();
+
+        match field.payload() {
+            Payload::VarInt(v) => (field.number(), v.to_uint64()),
+            _ => panic!("expected a varint"),
+        }
+    }
+
+    #[test]
+    fn read_nested() {
+        for buf in [
+            nested(),
+            crate::buf::ProtoBuf::pre_encoded(nested().to_vec().into_owned()),
+        ] {
+            let mut reader = ProtoBufReader::new(&buf);
+
+            assert_eq!(1, reader.next_field().unwrap().unwrap().number());
+            reader.begin_len().unwrap();
+            assert_eq!(1, reader.depth());
+
+            assert_eq!(1, reader.next_field().unwrap().unwrap().number());
+            reader.begin_len().unwrap();
+            assert_eq!(2, reader.depth());
+
+            assert_eq!((1, 7), varint(reader.next_field().unwrap()));
+            assert!(reader.next_field().unwrap().is_none());
+            reader.end_len().unwrap();
+
+            assert_eq!((2, 3), varint(reader.next_field().unwrap()));
+            assert!(reader.next_field().unwrap().is_none());
+            reader.end_len().unwrap();
+
+            assert_eq!((2, 5), varint(reader.next_field().unwrap()));
+            assert!(reader.next_field().unwrap().is_none());
+            assert_eq!(0, reader.depth());
+        }
+    }
+
+    #[test]
+    fn read_nested_skip_remaining() {
+        let buf = nested();
+        let mut reader = ProtoBufReader::new(&buf);
+
+        reader.next_field().unwrap();
+        reader.begin_len().unwrap();
+        reader.end_len().unwrap();
+
+        assert_eq!((2, 5), varint(reader.next_field().unwrap()));
+    }
+
+    #[test]
+    fn read_begin_len_not_len() {
+        let buf = nested();
+        let mut reader = ProtoBufReader::new(&buf);
+
+        assert_eq!(
+            ErrorKind::InvalidWireType,
+            reader.begin_len().unwrap_err().kind()
+        );
+
+        reader.next_field().unwrap();
+        reader.next_field().unwrap();
+
+        assert_eq!(
+            ErrorKind::InvalidWireType,
+            reader.begin_len().unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn read_end_len_unbalanced() {
+        let buf = nested();
+        let mut reader = ProtoBufReader::new(&buf);
+
+        assert_eq!(ErrorKind::Unbalanced, reader.end_len().unwrap_err().kind());
+
+        reader.next_field().unwrap();
+        reader.begin_len().unwrap();
+        reader.end_len().unwrap();
+
+        assert_eq!(ErrorKind::Unbalanced, reader.end_len().unwrap_err().kind());
+        assert_eq!((2, 5), varint(reader.next_field().unwrap()));
+    }
+}
@@ -0,0 +1,753 @@
+use crate::{
+    raw::{Fields, Payload, VarInt, WireType, I32, I64},
+    tags, Error, ErrorKind,
+};
+
+use alloc::vec::Vec;
+use sval::{Index, Label};
+
+/**
+A description of a protobuf message.
+
+Descriptors can be declared as constants. Messages that are recursive need to be declared as
+statics instead:
+
+```
+use sval_protobuf::decode::{FieldDescriptor, FieldKind, MessageDescriptor};
+
+const KEY_VALUE: MessageDescriptor = MessageDescriptor::new(
+    "KeyValue",
+    &[
+        FieldDescriptor::new(1, "key", FieldKind::String),
+        FieldDescriptor::new(2, "value", FieldKind::Int64),
+    ],
+);
+
+const RECORD: MessageDescriptor = MessageDescriptor::new(
+    "Record",
+    &[
+        FieldDescriptor::new(1, "id", FieldKind::UInt64),
+        FieldDescriptor::new(2, "attributes", FieldKind::Message(&KEY_VALUE)).repeated(),
+    ],
+);
+```
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct MessageDescriptor {
+    name: &'static str,
+    fields: &'static [FieldDescriptor],
+}
+
+/**
+A description of a field within a protobuf message.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct FieldDescriptor {
+    number: u64,
+    name: &'static str,
+    kind: FieldKind,
+    repeated: bool,
+    one_of: Option<&'static str>,
+}
+
+/**
+The type of a field within a protobuf message.
+*/
+#[derive(Debug, Clone, Copy)]
+pub enum FieldKind {
+    /**
+    A `double`.
+    */
+    Double,
+    /**
+    A `float`.
+    */
+    Float,
+    /**
+    An `int32`.
+    */
+    Int32,
+    /**
+    An `int64`.
+    */
+    Int64,
+    /**
+    A `uint32`.
+    */
+    UInt32,
+    /**
+    A `uint64`.
+    */
+    UInt64,
+    /**
+    A zigzag encoded `sint32`.
+    */
+    SInt32,
+    /**
+    A zigzag encoded `sint64`.
+    */
+    SInt64,
+    /**
+    A `fixed32`.
+    */
+    Fixed32,
+    /**
+    A `fixed64`.
+    */
+    Fixed64,
+    /**
+    An `sfixed32`.
+    */
+    SFixed32,
+    /**
+    An `sfixed64`.
+    */
+    SFixed64,
+    /**
+    A `bool`.
+    */
+    Bool,
+    /**
+    A UTF-8 `string`.
+    */
+    String,
+    /**
+    Arbitrary `bytes`.
+    */
+    Bytes,
+    /**
+    An `enum`, represented by its `int32` value.
+    */
+    Enum,
+    /**
+    A nested message.
+    */
+    Message(&'static MessageDescriptor),
+    /**
+    A `map`, with the given key and value types.
+    */
+    Map(&'static FieldKind, &'static FieldKind),
+}
+
+impl MessageDescriptor {
+    /**
+    Describe a message with the given fields.
+    */
+    pub const fn new(name: &'static str, fields: &'static [FieldDescriptor]) -> Self {
+        MessageDescriptor { name, fields }
+    }
+
+    /**
+    The name of the message.
+    */
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /**
+    The fields of the message.
+    */
+    pub const fn fields(&self) -> &'static [FieldDescriptor] {
+        self.fields
+    }
+
+    /**
+    Get the field with the given number.
+    */
+    pub fn field(&self, number: u64) -> Option<&'static FieldDescriptor> {
+        self.fields.iter().find(|field| field.number == number)
+    }
+}
+
+impl FieldDescriptor {
+    /**
+    Describe a singular field.
+    */
+    pub const fn new(number: u64, name: &'static str, kind: FieldKind) -> Self {
+        FieldDescriptor {
+            number,
+            name,
+            kind,
+            repeated: false,
+            one_of: None,
+        }
+    }
+
+    /**
+    Mark the field as repeated.
+
+    Repeated numeric fields may be encoded either packed or expanded.
+    */
+    pub const fn repeated(mut self) -> Self {
+        self.repeated = true;
+        self
+    }
+
+    /**
+    Mark the field as a member of the given oneof.
+
+    When multiple members of the same oneof are present, only the last one is used.
+    */
+    pub const fn one_of(mut self, one_of: &'static str) -> Self {
+        self.one_of = Some(one_of);
+        self
+    }
+
+    /**
+    The field number.
+    */
+    pub const fn number(&self) -> u64 {
+        self.number
+    }
+
+    /**
+    The name of the field.
+    */
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /**
+    The type of the field.
+    */
+    pub const fn kind(&self) -> FieldKind {
+        self.kind
+    }
+
+    /**
+    Whether the field is repeated.
+
+    Maps are always considered repeated.
+    */
+    pub const fn is_repeated(&self) -> bool {
+        self.repeated || matches!(self.kind, FieldKind::Map(_, _))
+    }
+
+    /**
+    The oneof this field belongs to, if any.
+    */
+    pub const fn get_one_of(&self) -> Option<&'static str> {
+        self.one_of
+    }
+}
+
+impl FieldKind {
+    /**
+    The wire type values of this kind are encoded with.
+
+    Repeated numeric fields may also use length-prefixed packed encoding.
+    */
+    pub const fn wire_type(&self) -> WireType {
+        match self {
+            FieldKind::Int32
+            | FieldKind::Int64
+            | FieldKind::UInt32
+            | FieldKind::UInt64
+            | FieldKind::SInt32
+            | FieldKind::SInt64
+            | FieldKind::Bool
+            | FieldKind::Enum => WireType::VarInt,
+            FieldKind::Fixed32 | FieldKind::SFixed32 | FieldKind::Float => WireType::I32,
+            FieldKind::Fixed64 | FieldKind::SFixed64 | FieldKind::Double => WireType::I64,
+            FieldKind::String | FieldKind::Bytes | FieldKind::Message(_) | FieldKind::Map(_, _) => {
+                WireType::Len
+            }
+        }
+    }
+}
+
+/**
+An encoded protobuf message that can be streamed using a schema.
+
+Messages are streamed as records, using the name of each field as its label and its number as
+its index. Fields that aren't present, or aren't described by the schema, aren't streamed.
+Scalar fields that appear multiple times use the last value. Repeated fields are streamed
+as sequences and maps as maps.
+
+Numbers are streamed using their Rust equivalents, and tagged with [`crate::tags`] as needed
+so that streaming a `TypedMessage` through a [`crate::ProtoBufStream`] will reproduce an
+equivalent encoded message.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct TypedMessage<'a> {
+    buf: &'a [u8],
+    descriptor: &'a MessageDescriptor,
+}
+
+impl<'a> TypedMessage<'a> {
+    /**
+    Treat a buffer as an encoded protobuf message described by `descriptor`.
+    */
+    pub fn new(buf: &'a [u8], descriptor: &'a MessageDescriptor) -> Self {
+        TypedMessage { buf, descriptor }
+    }
+}
+
+impl<'a> sval::Value for TypedMessage<'a> {
+    fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(&'sval self, stream: &mut S) -> sval::Result {
+        stream_message(self.buf, self.descriptor, stream)
+    }
+}
+
+fn stream_message<'sval>(
+    buf: &'sval [u8],
+    descriptor: &MessageDescriptor,
+    stream: &mut (impl sval::Stream<'sval> + ?Sized),
+) -> sval::Result {
+    let message_label = Label::new(descriptor.name);
+
+    let fields = FieldIndex::new(buf)?;
+
+    stream.record_tuple_begin(None, Some(&message_label), None, None)?;
+
+    for field in descriptor.fields {
+        if let Some(one_of) = field.one_of {
+            // Only the last member of a oneof that's present is used
+            if fields.last_one_of(descriptor, one_of) != Some(field.number) {
+                continue;
+            }
+        }
+
+        let label = Label::new(field.name);
+        let index = Index::new_u64(field.number);
+
+        match field.kind {
+            FieldKind::Map(key, value) => {
+                let entries = fields.occurrences(field.number);
+
+                if entries.is_empty() {
+                    continue;
+                }
+
+                stream.record_tuple_value_begin(None, &label, &index)?;
+                stream.map_begin(None)?;
+
+                for entry in entries {
+                    let Payload::Len(entry) = entry.payload else {
+                        return Err(Error::new(ErrorKind::InvalidWireType).into());
+                    };
+
+                    stream.map_key_begin()?;
+                    stream_value(last(entry, 1)?.unwrap_or(default(key)), *key, stream)?;
+                    stream.map_key_end()?;
+
+                    stream.map_value_begin()?;
+                    stream_value(last(entry, 2)?.unwrap_or(default(value)), *value, stream)?;
+                    stream.map_value_end()?;
+                }
+
+                stream.map_end()?;
+                stream.record_tuple_value_end(None, &label, &index)?;
+            }
+            kind if field.repeated => {
+                let values = fields.occurrences(field.number);
+
+                if values.is_empty() {
+                    continue;
+                }
+
+                // Retain packed encoding if the field used it
+                let packed = kind.wire_type() != WireType::Len
+                    && values
+                        .iter()
+                        .any(|value| matches!(value.payload, Payload::Len(_)));
+
+                stream.record_tuple_value_begin(None, &label, &index)?;
+
+                if packed {
+                    stream.tagged_begin(Some(&tags::PROTOBUF_LEN_PACKED), None, None)?;
+                }
+
+                stream.seq_begin(None)?;
+
+                for value in values {
+                    match value.payload {
+                        Payload::Len(packed) if kind.wire_type() != WireType::Len => {
+                            stream_packed(packed, kind, stream)?;
+                        }
+                        payload => {
+                            stream.seq_value_begin()?;
+                            stream_value(payload, kind, stream)?;
+                            stream.seq_value_end()?;
+                        }
+                    }
+                }
+
+                stream.seq_end()?;
+
+                if packed {
+                    stream.tagged_end(Some(&tags::PROTOBUF_LEN_PACKED), None, None)?;
+                }
+
+                stream.record_tuple_value_end(None, &label, &index)?;
+            }
+            kind => {
+                let Some(value) = fields.occurrences(field.number).last() else {
+                    continue;
+                };
+
+                stream.record_tuple_value_begin(None, &label, &index)?;
+                stream_value(value.payload, kind, stream)?;
+                stream.record_tuple_value_end(None, &label, &index)?;
+            }
+        }
+    }
+
+    stream.record_tuple_end(None, Some(&message_label), None)
+}
+
+fn stream_value<'sval>(
+    payload: Payload<&'sval [u8]>,
+    kind: FieldKind,
+    stream: &mut (impl sval::Stream<'sval> + ?Sized),
+) -> sval::Result {
+    fn tagged<'sval, S: sval::Stream<'sval> + ?Sized>(
+        stream: &mut S,
+        tag: &sval::Tag,
+        f: impl FnOnce(&mut S) -> sval::Result,
+    ) -> sval::Result {
+        stream.tagged_begin(Some(tag), None, None)?;
+        f(stream)?;
+        stream.tagged_end(Some(tag), None, None)
+    }
+
+    match (kind, payload) {
+        (FieldKind::Int32 | FieldKind::Enum, Payload::VarInt(v)) => {
+            stream.i32(v.to_sint64() as i32)
+        }
+        (FieldKind::Int64, Payload::VarInt(v)) => stream.i64(v.to_sint64()),
+        (FieldKind::UInt32, Payload::VarInt(v)) => stream.u32(v.to_uint64() as u32),
+        (FieldKind::UInt64, Payload::VarInt(v)) => stream.u64(v.to_uint64()),
+        (FieldKind::SInt32, Payload::VarInt(v)) => {
+            tagged(stream, &tags::PROTOBUF_VARINT_SIGNED, |stream| {
+                stream.i32(v.to_sint64z() as i32)
+            })
+        }
+        (FieldKind::SInt64, Payload::VarInt(v)) => {
+            tagged(stream, &tags::PROTOBUF_VARINT_SIGNED, |stream| {
+                stream.i64(v.to_sint64z())
+            })
+        }
+        (FieldKind::Bool, Payload::VarInt(v)) => stream.bool(v.to_bool()),
+        (FieldKind::Float, Payload::I32(v)) => stream.f32(v.to_float()),
+        (FieldKind::Fixed32, Payload::I32(v)) => tagged(stream, &tags::PROTOBUF_I32, |stream| {
+            stream.u32(v.to_fixed32())
+        }),
+        (FieldKind::SFixed32, Payload::I32(v)) => tagged(stream, &tags::PROTOBUF_I32, |stream| {
+            stream.i32(v.to_sfixed32())
+        }),
+        (FieldKind::Double, Payload::I64(v)) => stream.f64(v.to_double()),
+        (FieldKind::Fixed64, Payload::I64(v)) => tagged(stream, &tags::PROTOBUF_I64, |stream| {
+            stream.u64(v.to_fixed64())
+        }),
+        (FieldKind::SFixed64, Payload::I64(v)) => tagged(stream, &tags::PROTOBUF_I64, |stream| {
+            stream.i64(v.to_sfixed64())
+        }),
+        (FieldKind::String, Payload::Len(v)) => {
+            let v = core::str::from_utf8(v).map_err(|_| Error::new(ErrorKind::InvalidValue))?;
+
+            stream.text_begin(Some(v.len()))?;
+            stream.text_fragment(v)?;
+            stream.text_end()
+        }
+        (FieldKind::Bytes, Payload::Len(v)) => {
+            stream.binary_begin(Some(v.len()))?;
+            stream.binary_fragment(v)?;
+            stream.binary_end()
+        }
+        (FieldKind::Message(descriptor), Payload::Len(v)) => stream_message(v, descriptor, stream),
+        _ => Err(Error::new(ErrorKind::InvalidWireType).into()),
+    }
+}
+
+fn stream_packed<'sval>(
+    mut buf: &'sval [u8],
+    kind: FieldKind,
+    stream: &mut (impl sval::Stream<'sval> + ?Sized),
+) -> sval::Result {
+    while !buf.is_empty() {
+        let (payload, len) = Payload::decode_packed(buf, kind.wire_type())?;
+
+        buf = &buf[len..];
+
+        stream.seq_value_begin()?;
+        stream_value(payload, kind, stream)?;
+        stream.seq_value_end()?;
+    }
+
+    Ok(())
+}
+
+fn occurrences(buf: &[u8], number: u64) -> impl Iterator<Item = Result<Payload<&[u8]>, Error>> {
+    Fields::new(buf).filter_map(move |field| match field {
+        Ok(field) if field.number() == number => Some(Ok(field.into_payload())),
+        Ok(_) => None,
+        Err(err) => Some(Err(err)),
+    })
+}
+
+fn last(buf: &[u8], number: u64) -> Result<Option<Payload<&[u8]>>, Error> {
+    let mut last = None;
+
+    for payload in occurrences(buf, number) {
+        last = Some(payload?);
+    }
+
+    Ok(last)
+}
+
+/**
+The fields of an encoded message, indexed by their number.
+*/
+struct FieldIndex<'a> {
+    // Sorted by number, with fields that have the same number in the order they're encoded
+    fields: Vec<IndexedField<'a>>,
+}
+
+struct IndexedField<'a> {
+    number: u64,
+    // The position of the field in the encoded message
+    position: usize,
+    payload: Payload<&'a [u8]>,
+}
+
+impl<'a> FieldIndex<'a> {
+    fn new(buf: &'a [u8]) -> Result<Self, Error> {
+        let mut fields = Fields::new(buf)
+            .enumerate()
+            .map(|(position, field)| {
+                let field = field?;
+
+                Ok(IndexedField {
+                    number: field.number(),
+                    position,
+                    payload: field.into_payload(),
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        // The sort is stable, so repeated fields keep their order
+        fields.sort_by_key(|field| field.number);
+
+        Ok(FieldIndex { fields })
+    }
+
+    fn occurrences(&self, number: u64) -> &[IndexedField<'a>] {
+        let start = self.fields.partition_point(|field| field.number < number);
+        let end = self.fields.partition_point(|field| field.number <= number);
+
+        &self.fields[start..end]
+    }
+
+    fn last_one_of(&self, descriptor: &MessageDescriptor, one_of: &str) -> Option<u64> {
+        descriptor
+            .fields
+            .iter()
+            .filter(|field| field.one_of == Some(one_of))
+            .filter_map(|field| self.occurrences(field.number).last())
+            .max_by_key(|field| field.position)
+            .map(|field| field.number)
+    }
+}
+
+fn default(kind: &FieldKind) -> Payload<&'static [u8]> {
+    match kind.wire_type() {
+        WireType::VarInt => Payload::VarInt(VarInt::uint64(0)),
+        WireType::I32 => Payload::I32(I32::fixed32(0)),
+        WireType::I64 => Payload::I64(I64::fixed64(0)),
+        WireType::Len => Payload::Len(&[]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::buf::ProtoBufMut;
+
+    const INNER: MessageDescriptor = MessageDescriptor::new(
+        "Inner",
+        &[
+            FieldDescriptor::new(1, "a", FieldKind::SInt64),
+            FieldDescriptor::new(2, "b", FieldKind::Fixed32),
+        ],
+    );
+
+    const OUTER: MessageDescriptor = MessageDescriptor::new(
+        "Outer",
+        &[
+            FieldDescriptor::new(1, "id", FieldKind::Int32),
+            FieldDescriptor::new(2, "name", FieldKind::String),
+            FieldDescriptor::new(3, "inner", FieldKind::Message(&INNER)),
+            FieldDescriptor::new(4, "packed", FieldKind::UInt32).repeated(),
+            FieldDescriptor::new(5, "expanded", FieldKind::Double).repeated(),
+            FieldDescriptor::new(
+                6,
+                "map",
+                FieldKind::Map(&FieldKind::String, &FieldKind::Bool),
+            ),
+            FieldDescriptor::new(7, "number", FieldKind::Int64).one_of("value"),
+            FieldDescriptor::new(8, "text", FieldKind::String).one_of("value"),
+        ],
+    );
+
+    fn outer() -> ProtoBufMut<()> {
+        let mut buf = ProtoBufMut::new(());
+
+        buf.push_field_varint(1);
+        buf.push_varint_sint64(-1);
+
+        buf.push_field_len(2);
+        buf.push_len_varint_uint64(3);
+        buf.push(b"abc");
+
+        buf.push_field_len(3);
+        buf.begin_len(());
+        buf.push_field_varint(1);
+        buf.push_varint_sint64z(-2);
+        buf.push_field_i32(2);
+        buf.push_i32_fixed32(7);
+        buf.end_len();
+
+        buf.push_field_len(4);
+        buf.begin_len(());
+        buf.push_varint_uint64(1);
+        buf.push_varint_uint64(300);
+        buf.end_len();
+
+        buf.push_field_i64(5);
+        buf.push_i64_double(1.5);
+        buf.push_field_i64(5);
+        buf.push_i64_double(2.5);
+
+        buf.push_field_len(6);
+        buf.begin_len(());
+        buf.push_field_len(1);
+        buf.push_len_varint_uint64(1);
+        buf.push(b"k");
+        buf.push_field_varint(2);
+        buf.push_varint_bool(true);
+        buf.end_len();
+
+        buf.push_field_varint(7);
+        buf.push_varint_sint64(42);
+
+        buf
+    }
+
+    #[test]
+    fn decode_roundtrip() {
+        let encoded = outer().freeze().to_vec().into_owned();
+
+        let decoded = crate::stream_to_protobuf(TypedMessage::new(&encoded, &OUTER));
+
+        assert_eq!(encoded, &*decoded.to_vec());
+    }
+
+    #[test]
+    fn decode_last_one_wins() {
+        let mut buf = outer();
+
+        buf.push_field_varint(1);
+        buf.push_varint_sint64(5);
+
+        buf.push_field_len(8);
+        buf.push_len_varint_uint64(1);
+        buf.push(b"x");
+
+        let encoded = buf.freeze().to_vec().into_owned();
+        let decoded = crate::stream_to_protobuf(TypedMessage::new(&encoded, &OUTER))
+            .to_vec()
+            .into_owned();
+
+        let fields = Fields::new(&decoded)
+            .map(|field| field.map(|field| field.number()))
+            .collect::<Result<alloc::vec::Vec<_>, _>>()
+            .unwrap();
+
+        // Field 1 only appears once, and field 8 replaces field 7 in the oneof
+        assert_eq!(&[1, 2, 3, 4, 5, 5, 6, 8], &*fields);
+        assert!(matches!(
+            Fields::new(&decoded).next().unwrap().unwrap().payload(),
+            Payload::VarInt(v) if v.to_sint64() == 5
+        ));
+    }
+
+    #[test]
+    fn decode_map_entry_defaults() {
+        let mut buf = ProtoBufMut::new(());
+
+        buf.push_field_len(6);
+        buf.push_len_varint_uint64(0);
+
+        let encoded = buf.freeze().to_vec().into_owned();
+        let decoded = crate::stream_to_protobuf(TypedMessage::new(&encoded, &OUTER));
+
+        let mut expected = ProtoBufMut::new(());
+
+        expected.push_field_len(6);
+        expected.begin_len(());
+        expected.push_field_len(1);
+        expected.push_len_varint_uint64(0);
+        expected.push_field_varint(2);
+        expected.push_varint_bool(false);
+        expected.end_len();
+
+        assert_eq!(expected.freeze().to_vec(), decoded.to_vec());
+    }
+
+    #[test]
+    fn decode_interleaved_fields() {
+        let mut buf = ProtoBufMut::new(());
+
+        buf.push_field_i64(5);
+        buf.push_i64_double(1.5);
+        buf.push_field_len(8);
+        buf.push_len_varint_uint64(1);
+        buf.push(b"x");
+        buf.push_field_varint(1);
+        buf.push_varint_sint64(-1);
+        buf.push_field_i64(5);
+        buf.push_i64_double(2.5);
+        buf.push_field_varint(7);
+        buf.push_varint_sint64(42);
+
+        let encoded = buf.freeze().to_vec().into_owned();
+        let decoded = crate::stream_to_protobuf(TypedMessage::new(&encoded, &OUTER));
+
+        // Fields are written in the order of the descriptor, repeated fields keep their order,
+        // and field 7 replaces field 8 in the oneof
+        let mut expected = ProtoBufMut::new(());
+
+        expected.push_field_varint(1);
+        expected.push_varint_sint64(-1);
+        expected.push_field_i64(5);
+        expected.push_i64_double(1.5);
+        expected.push_field_i64(5);
+        expected.push_i64_double(2.5);
+        expected.push_field_varint(7);
+        expected.push_varint_sint64(42);
+
+        assert_eq!(expected.freeze().to_vec(), decoded.to_vec());
+    }
+
+    #[test]
+    fn decode_invalid() {
+        for (field, encoded) in [
+            // Invalid UTF-8
+            (2, &[18u8, 1, 255] as &[u8]),
+            // Wrong wire type
+            (1, &[13u8, 1, 0, 0, 0]),
+        ] {
+            assert!(
+                sval::Value::stream(
+                    &TypedMessage::new(encoded, &OUTER),
+                    &mut crate::ProtoBufStream::new()
+                )
+                .is_err(),
+                "{}",
+                field
+            );
+        }
+    }
+}
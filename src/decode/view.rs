@@ -0,0 +1,521 @@
+use crate::{
+    buf::{Fields, ProtoBuf, ProtoBufRef},
+    decode::FromProtobuf,
+    raw::{Payload, WireType},
+    Error, ErrorKind,
+};
+
+use core::marker::PhantomData;
+
+/**
+A borrowed view of an encoded protobuf message that reads its fields on demand.
+
+Nothing is decoded until a field is requested, and then only that field is read. Text and
+binary fields borrow from the encoded message instead of being copied. Fields that appear
+multiple times use the last value, and repeated fields are read through an iterator.
+
+Views for specific messages can be defined by wrapping a `MessageView` and implementing
+[`FromView`] for them:
+
+```
+use sval_protobuf::{
+    decode::{FromView, MessageView},
+    raw::Payload,
+    buf::ProtoBufRef,
+    Error,
+};
+
+#[derive(Clone, Copy)]
+pub struct KeyValueView<'a>(MessageView<'a>);
+
+impl<'a> KeyValueView<'a> {
+    pub fn key(&self) -> Result<&'a str, Error> {
+        self.0.get(1)
+    }
+}
+
+impl<'a> FromView<'a> for KeyValueView<'a> {
+    fn empty() -> Self {
+        KeyValueView(MessageView::empty())
+    }
+
+    fn from_payload(
+        payload: Payload<ProtoBufRef<'a>>,
+        tag: Option<&sval::Tag>,
+    ) -> Result<Self, Error> {
+        MessageView::from_payload(payload, tag).map(KeyValueView)
+    }
+}
+```
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct MessageView<'a> {
+    buf: ProtoBufRef<'a>,
+}
+
+impl<'a> MessageView<'a> {
+    /**
+    Create a view over an encoded message.
+
+    The message may be a contiguous buffer or a [`crate::buf::ProtoBuf`].
+    */
+    pub fn new(buf: impl Into<ProtoBufRef<'a>>) -> Self {
+        MessageView { buf: buf.into() }
+    }
+
+    /**
+    Create a view over an empty message.
+    */
+    pub fn empty() -> Self {
+        MessageView::new(&[] as &[u8])
+    }
+
+    /**
+    Get the encoded message this view reads from.
+    */
+    pub fn as_buf(&self) -> ProtoBufRef<'a> {
+        self.buf
+    }
+
+    /**
+    Iterate over all fields in the message.
+    */
+    pub fn fields(&self) -> Fields<'a> {
+        self.buf.fields()
+    }
+
+    /**
+    Get the value of a field, or its empty value if it isn't present.
+
+    If the field appears multiple times then the last value is used.
+    */
+    pub fn get<T: FromView<'a>>(&self, number: u64) -> Result<T, Error> {
+        self.get_tagged(number, None)
+    }
+
+    /**
+    Get the value of a field, interpreting it using the given data tag.
+
+    See [`MessageView::get`] for details.
+    */
+    pub fn get_tagged<T: FromView<'a>>(
+        &self,
+        number: u64,
+        tag: Option<&sval::Tag>,
+    ) -> Result<T, Error> {
+        Ok(self
+            .get_optional_tagged(number, tag)?
+            .unwrap_or_else(T::empty))
+    }
+
+    /**
+    Get the value of a field, or `None` if it isn't present.
+
+    If the field appears multiple times then the last value is used.
+    */
+    pub fn get_optional<T: FromView<'a>>(&self, number: u64) -> Result<Option<T>, Error> {
+        self.get_optional_tagged(number, None)
+    }
+
+    /**
+    Get the value of a field, interpreting it using the given data tag.
+
+    See [`MessageView::get_optional`] for details.
+    */
+    pub fn get_optional_tagged<T: FromView<'a>>(
+        &self,
+        number: u64,
+        tag: Option<&sval::Tag>,
+    ) -> Result<Option<T>, Error> {
+        let mut last = None;
+
+        for field in self.fields() {
+            let field = field?;
+
+            if field.number() == number {
+                last = Some(field.into_payload());
+            }
+        }
+
+        last.map(|payload| T::from_payload(payload, tag))
+            .transpose()
+    }
+
+    /**
+    Iterate over the values of a repeated field.
+
+    Both packed and expanded encodings of the field are supported.
+    */
+    pub fn get_repeated<T: FromView<'a>>(&self, number: u64) -> RepeatedView<'a, T> {
+        self.get_repeated_tagged(number, None)
+    }
+
+    /**
+    Iterate over the values of a repeated field, interpreting them using the given data tag.
+
+    See [`MessageView::get_repeated`] for details.
+    */
+    pub fn get_repeated_tagged<T: FromView<'a>>(
+        &self,
+        number: u64,
+        tag: Option<&'a sval::Tag>,
+    ) -> RepeatedView<'a, T> {
+        RepeatedView {
+            fields: self.fields(),
+            number,
+            tag,
+            packed: &[],
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl ProtoBuf {
+    /**
+    Get a view over the encoded message that reads its fields on demand.
+    */
+    pub fn view(&self) -> MessageView<'_> {
+        MessageView::new(self)
+    }
+}
+
+/**
+An iterator over the values of a repeated field in a [`MessageView`].
+*/
+#[derive(Debug, Clone)]
+pub struct RepeatedView<'a, T> {
+    fields: Fields<'a>,
+    number: u64,
+    tag: Option<&'a sval::Tag>,
+    // The remaining elements of the current packed field
+    packed: &'a [u8],
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, T: FromView<'a>> Iterator for RepeatedView<'a, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let packed_wire_type = T::packed_wire_type(self.tag);
+
+        loop {
+            if let Some(wire_type) = packed_wire_type {
+                if !self.packed.is_empty() {
+                    return Some(match Payload::decode_packed(self.packed, wire_type) {
+                        Ok((payload, len)) => {
+                            self.packed = &self.packed[len..];
+
+                            T::from_payload(with_buf(payload), self.tag)
+                        }
+                        Err(err) => {
+                            self.stop();
+
+                            Err(err)
+                        }
+                    });
+                }
+            }
+
+            let field = match self.fields.next()? {
+                Ok(field) => field,
+                Err(err) => return Some(Err(err)),
+            };
+
+            if field.number() != self.number {
+                continue;
+            }
+
+            match (field.into_payload(), packed_wire_type) {
+                // Read the elements of the packed field on the next iteration
+                (Payload::Len(packed), Some(_)) => match packed.as_slice() {
+                    Some(packed) => self.packed = packed,
+                    None => {
+                        self.stop();
+
+                        return Some(Err(Error::new(ErrorKind::InvalidValue)));
+                    }
+                },
+                (payload, _) => return Some(T::from_payload(payload, self.tag)),
+            }
+        }
+    }
+}
+
+impl<'a, T> RepeatedView<'a, T> {
+    /**
+    Skip any remaining values after an error, so iteration stops.
+    */
+    fn stop(&mut self) {
+        self.packed = &[];
+        self.fields.stop();
+    }
+}
+
+/**
+A value that can be read from a field in a [`MessageView`].
+*/
+pub trait FromView<'a>: Sized {
+    /**
+    The value to use when a field isn't present.
+    */
+    fn empty() -> Self;
+
+    /**
+    Read a value from the payload of a field.
+
+    The `tag` is the data tag the field is annotated with, such as [`crate::tags::PROTOBUF_I32`].
+    */
+    fn from_payload(
+        payload: Payload<ProtoBufRef<'a>>,
+        tag: Option<&sval::Tag>,
+    ) -> Result<Self, Error>;
+
+    /**
+    The wire type of this value when it's an element of a packed repeated field.
+
+    Values that can't be packed return `None`.
+    */
+    fn packed_wire_type(tag: Option<&sval::Tag>) -> Option<WireType> {
+        let _ = tag;
+
+        None
+    }
+}
+
+fn with_buf<'a>(payload: Payload<&'a [u8]>) -> Payload<ProtoBufRef<'a>> {
+    match payload {
+        Payload::VarInt(v) => Payload::VarInt(v),
+        Payload::I64(v) => Payload::I64(v),
+        Payload::Len(v) => Payload::Len(v.into()),
+        Payload::I32(v) => Payload::I32(v),
+    }
+}
+
+fn contiguous(buf: ProtoBufRef<'_>) -> Result<&[u8], Error> {
+    // Only the payloads of fields containing nested messages may be split
+    buf.as_slice().ok_or(Error::new(ErrorKind::InvalidValue))
+}
+
+macro_rules! impl_from_view_owned {
+    ($($ty:ty),*) => {
+        $(
+            impl<'a> FromView<'a> for $ty {
+                fn empty() -> Self {
+                    <$ty as FromProtobuf>::empty()
+                }
+
+                fn from_payload(
+                    payload: Payload<ProtoBufRef<'a>>,
+                    tag: Option<&sval::Tag>,
+                ) -> Result<Self, Error> {
+                    let payload = match payload {
+                        Payload::VarInt(v) => Payload::VarInt(v),
+                        Payload::I64(v) => Payload::I64(v),
+                        Payload::Len(v) => Payload::Len(contiguous(v)?),
+                        Payload::I32(v) => Payload::I32(v),
+                    };
+
+                    let mut value = <$ty as FromProtobuf>::empty();
+                    value.merge_field(payload, tag)?;
+
+                    Ok(value)
+                }
+
+                fn packed_wire_type(tag: Option<&sval::Tag>) -> Option<WireType> {
+                    <$ty as FromProtobuf>::packed_wire_type(tag)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_view_owned!(bool, u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+impl<'a> FromView<'a> for &'a [u8] {
+    fn empty() -> Self {
+        &[]
+    }
+
+    fn from_payload(
+        payload: Payload<ProtoBufRef<'a>>,
+        _: Option<&sval::Tag>,
+    ) -> Result<Self, Error> {
+        match payload {
+            Payload::Len(v) => contiguous(v),
+            _ => Err(Error::new(ErrorKind::InvalidWireType)),
+        }
+    }
+}
+
+impl<'a> FromView<'a> for &'a str {
+    fn empty() -> Self {
+        ""
+    }
+
+    fn from_payload(
+        payload: Payload<ProtoBufRef<'a>>,
+        tag: Option<&sval::Tag>,
+    ) -> Result<Self, Error> {
+        core::str::from_utf8(<&'a [u8]>::from_payload(payload, tag)?)
+            .map_err(|_| Error::new(ErrorKind::InvalidValue))
+    }
+}
+
+impl<'a> FromView<'a> for ProtoBufRef<'a> {
+    fn empty() -> Self {
+        ProtoBufRef::from(&[] as &[u8])
+    }
+
+    fn from_payload(
+        payload: Payload<ProtoBufRef<'a>>,
+        _: Option<&sval::Tag>,
+    ) -> Result<Self, Error> {
+        match payload {
+            Payload::Len(v) => Ok(v),
+            _ => Err(Error::new(ErrorKind::InvalidWireType)),
+        }
+    }
+}
+
+impl<'a> FromView<'a> for MessageView<'a> {
+    fn empty() -> Self {
+        MessageView::empty()
+    }
+
+    fn from_payload(
+        payload: Payload<ProtoBufRef<'a>>,
+        tag: Option<&sval::Tag>,
+    ) -> Result<Self, Error> {
+        ProtoBufRef::from_payload(payload, tag).map(MessageView::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{buf::ProtoBufMut, tags};
+
+    use alloc::vec::Vec;
+
+    fn message() -> crate::buf::ProtoBuf {
+        let mut buf = ProtoBufMut::new(());
+
+        buf.push_field_varint(1);
+        buf.push_varint_uint64(1);
+
+        buf.push_field_len(2);
+        buf.begin_len(());
+        buf.push(b"abc");
+        buf.end_len();
+
+        buf.push_field_len(3);
+        buf.begin_len(());
+        buf.push_field_len(1);
+        buf.begin_len(());
+        buf.push(b"nested");
+        buf.end_len();
+        buf.end_len();
+
+        buf.push_field_len(4);
+        buf.begin_len(());
+        buf.push_varint_uint64(1);
+        buf.push_varint_uint64(2);
+        buf.end_len();
+
+        buf.push_field_varint(4);
+        buf.push_varint_uint64(3);
+
+        buf.push_field_varint(5);
+        buf.push_varint_sint64z(-7);
+
+        buf.push_field_varint(1);
+        buf.push_varint_uint64(2);
+
+        buf.freeze()
+    }
+
+    #[test]
+    fn view_get() {
+        let chunked = message();
+        let contiguous = chunked.to_vec().into_owned();
+
+        for view in [MessageView::new(&chunked), MessageView::new(&*contiguous)] {
+            // The last value is used
+            assert_eq!(2, view.get::<u32>(1).unwrap());
+            assert_eq!("abc", view.get::<&str>(2).unwrap());
+            assert_eq!(
+                "nested",
+                view.get::<MessageView>(3).unwrap().get::<&str>(1).unwrap()
+            );
+            assert_eq!(
+                -7,
+                view.get_tagged::<i64>(5, Some(&tags::PROTOBUF_VARINT_SIGNED))
+                    .unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn view_get_missing() {
+        let buf = message();
+        let view = MessageView::new(&buf);
+
+        assert_eq!(0, view.get::<u32>(9).unwrap());
+        assert_eq!("", view.get::<&str>(9).unwrap());
+        assert!(view.get_optional::<u32>(9).unwrap().is_none());
+        assert_eq!(0, view.get_repeated::<u32>(9).count());
+    }
+
+    #[test]
+    fn view_get_repeated() {
+        let buf = message();
+        let view = MessageView::new(&buf);
+
+        let repeated = view
+            .get_repeated::<u32>(4)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(&[1, 2, 3], &*repeated);
+
+        let repeated = view
+            .get_repeated::<&str>(2)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(&["abc"], &*repeated);
+    }
+
+    #[test]
+    fn view_get_invalid() {
+        let buf = message();
+        let view = MessageView::new(&buf);
+
+        assert_eq!(
+            ErrorKind::InvalidWireType,
+            view.get::<&str>(1).unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn view_get_repeated_empty_packed() {
+        // Many consecutive empty packed fields are skipped without recursing
+        let buf = [0x22, 0x00].repeat(1_000_000);
+        let view = MessageView::new(&*buf);
+
+        assert_eq!(0, view.get_repeated::<u32>(4).count());
+    }
+
+    #[test]
+    fn view_get_repeated_invalid_packed() {
+        let buf = [0x22, 0x01, 0x80, 0x20, 0x01];
+        let view = MessageView::new(&buf[..]);
+
+        // Iteration stops after the first error
+        let repeated = view.get_repeated::<u32>(4).collect::<Vec<_>>();
+
+        assert_eq!(1, repeated.len());
+        assert!(repeated[0].is_err());
+    }
+}
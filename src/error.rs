@@ -0,0 +1,104 @@
+use core::fmt;
+
+/**
+An error encountered while working with the protobuf wire format.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Error(ErrorKind);
+
+/**
+The kind of failure an [`Error`] represents.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /**
+    The input ended before a complete value could be read.
+    */
+    Truncated,
+    /**
+    A variable-length integer used more than the maximum of 10 bytes, or overflowed 64bits.
+    */
+    Overlong,
+    /**
+    A field used a wire type that isn't supported.
+    */
+    InvalidWireType,
+    /**
+    A field number was outside the valid range of `1` to [`crate::raw::MAX_FIELD_NUMBER`].
+    */
+    InvalidFieldNumber,
+    /**
+    A field number was used more than once in the same message.
+    */
+    DuplicateField,
+    /**
+    A configured limit, such as the maximum nesting depth or total length, was exceeded.
+    */
+    LimitExceeded,
+    /**
+    A value didn't match the type it was expected to have, such as text that isn't valid UTF-8.
+    */
+    InvalidValue,
+    /**
+    The value being encoded failed to stream itself.
+    */
+    Stream,
+    /**
+    A value began more nested messages or length-prefixed fields than it ended.
+    */
+    Unbalanced,
+    /**
+    A value had a shape that can't be represented in the protobuf wire format.
+    */
+    Unsupported,
+}
+
+impl Error {
+    #[inline]
+    pub(crate) fn new(kind: ErrorKind) -> Self {
+        Error(kind)
+    }
+
+    /**
+    Get the kind of failure this error represents.
+    */
+    #[inline]
+    pub fn kind(&self) -> ErrorKind {
+        self.0
+    }
+}
+
+impl From<ErrorKind> for Error {
+    #[inline]
+    fn from(kind: ErrorKind) -> Self {
+        Error::new(kind)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            ErrorKind::Truncated => f.write_str("the input ended unexpectedly"),
+            ErrorKind::Overlong => f.write_str("a variable-length integer was too long"),
+            ErrorKind::InvalidWireType => f.write_str("a field used an unsupported wire type"),
+            ErrorKind::InvalidFieldNumber => f.write_str("a field number was out of range"),
+            ErrorKind::DuplicateField => f.write_str("a field number was used more than once"),
+            ErrorKind::LimitExceeded => f.write_str("a decoding limit was exceeded"),
+            ErrorKind::InvalidValue => f.write_str("a value was invalid for its type"),
+            ErrorKind::Stream => f.write_str("the value failed to stream"),
+            ErrorKind::Unbalanced => f.write_str("the value's nesting was unbalanced"),
+            ErrorKind::Unsupported => f.write_str("the value's shape isn't supported by protobuf"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+impl From<Error> for sval::Error {
+    #[inline]
+    fn from(_: Error) -> Self {
+        sval::Error::new()
+    }
+}
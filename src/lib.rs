@@ -9,7 +9,8 @@ This library doesn't depend on `protoc` or any code generation tools; any implem
 impractical or produces undesirable results. It supports some more niche use-cases like embedding
 already encoded messages into others without needing to parse them first.
 
-This library only supports encoding.
+Encoded messages can be streamed back into any `sval::Stream` without a schema through
+the [`decode`] module.
 
 ## Specifics
 
@@ -91,7 +92,8 @@ Anonymous tuples are also messages:
 3: {"Some amazing content"}
 ```
 
-128bit numbers are always encoded as a 16 byte buffer with the little-endian bytes of the value.
+128bit numbers are encoded as a 16 byte buffer with the little-endian bytes of the value by default.
+Other strategies can be configured through [`ProtoBufStream::with_int128_encoding`].
 */
 
 #![deny(missing_docs)]
@@ -99,10 +101,21 @@ Anonymous tuples are also messages:
 
 extern crate alloc;
 
+#[cfg(feature = "std")]
+extern crate std;
+
+mod error;
 mod stream;
-pub use self::stream::*;
+pub use self::{error::*, stream::*};
 
 pub mod buf;
+pub mod decode;
 pub mod tags;
+pub mod well_known;
 
 pub mod raw;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use sval;
+}
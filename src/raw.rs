@@ -7,7 +7,18 @@ It's a length-prefixed format that makes extensive use of variable-length intege
 
 #![allow(missing_docs)]
 
-use core::mem;
+use crate::{Error, ErrorKind};
+use core::{mem, ops::RangeInclusive};
+
+/**
+The largest field number that can be encoded.
+*/
+pub const MAX_FIELD_NUMBER: u64 = (1 << 29) - 1;
+
+/**
+The range of field numbers reserved for the protobuf implementation.
+*/
+pub const RESERVED_FIELD_NUMBERS: RangeInclusive<u64> = 19000..=19999;
 
 #[derive(Debug, Clone, Copy)]
 #[repr(transparent)]
@@ -65,6 +76,71 @@ impl VarInt {
         &buf[..i + 1]
     }
 
+    /**
+    Read a variable-length integer from the start of `buf`.
+
+    This method returns the value along with the number of bytes it was encoded in.
+    */
+    #[inline]
+    pub fn decode(buf: &[u8]) -> Result<(Self, usize), Error> {
+        let mut v = 0;
+
+        for (i, b) in buf.iter().enumerate() {
+            // The 10th byte only has room for the top bit of a 64bit value
+            if i == 9 && *b > 1 {
+                return Err(ErrorKind::Overlong.into());
+            }
+
+            v |= ((b & 0b0111_1111) as u64) << (i * 7);
+
+            if b & 0b1000_0000 == 0 {
+                return Ok((VarInt(v), i + 1));
+            }
+        }
+
+        Err(ErrorKind::Truncated.into())
+    }
+
+    #[inline(always)]
+    pub fn to_uint64(&self) -> u64 {
+        self.0
+    }
+
+    #[inline(always)]
+    pub fn to_sint64(&self) -> i64 {
+        self.0 as i64
+    }
+
+    #[inline(always)]
+    pub fn to_sint64z(&self) -> i64 {
+        (self.0 >> 1) as i64 ^ -((self.0 & 1) as i64)
+    }
+
+    #[inline(always)]
+    pub fn to_bool(&self) -> bool {
+        self.0 != 0
+    }
+
+    #[inline(always)]
+    pub fn to_enum32(&self) -> i32 {
+        self.0 as i32
+    }
+
+    /**
+    Split a field key into its field number and wire type.
+    */
+    #[inline]
+    pub fn to_field(&self) -> Result<(u64, WireType), Error> {
+        let wire_type = WireType::try_from((self.0 & 0b111) as u8)?;
+        let field_number = self.0 >> 3;
+
+        if field_number == 0 || field_number > MAX_FIELD_NUMBER {
+            return Err(ErrorKind::InvalidFieldNumber.into());
+        }
+
+        Ok((field_number, wire_type))
+    }
+
     #[inline(always)]
     pub fn len(&self) -> usize {
         // From:
@@ -96,6 +172,37 @@ impl I32 {
         I32(v as u32)
     }
 
+    #[inline(always)]
+    pub fn from_bytes(bytes: [u8; 4]) -> Self {
+        I32(u32::from_le_bytes(bytes))
+    }
+
+    /**
+    Read a 32bit value from the start of `buf`.
+    */
+    #[inline]
+    pub fn decode(buf: &[u8]) -> Result<(Self, usize), Error> {
+        match buf.get(..4) {
+            Some(bytes) => Ok((I32::from_bytes(bytes.try_into().unwrap()), 4)),
+            None => Err(ErrorKind::Truncated.into()),
+        }
+    }
+
+    #[inline(always)]
+    pub fn to_float(&self) -> f32 {
+        f32::from_bits(self.0)
+    }
+
+    #[inline(always)]
+    pub fn to_fixed32(&self) -> u32 {
+        self.0
+    }
+
+    #[inline(always)]
+    pub fn to_sfixed32(&self) -> i32 {
+        self.0 as i32
+    }
+
     #[inline(always)]
     pub fn to_bytes(&self) -> [u8; 4] {
         self.0.to_le_bytes()
@@ -129,6 +236,37 @@ impl I64 {
         I64(v as u64)
     }
 
+    #[inline(always)]
+    pub fn from_bytes(bytes: [u8; 8]) -> Self {
+        I64(u64::from_le_bytes(bytes))
+    }
+
+    /**
+    Read a 64bit value from the start of `buf`.
+    */
+    #[inline]
+    pub fn decode(buf: &[u8]) -> Result<(Self, usize), Error> {
+        match buf.get(..8) {
+            Some(bytes) => Ok((I64::from_bytes(bytes.try_into().unwrap()), 8)),
+            None => Err(ErrorKind::Truncated.into()),
+        }
+    }
+
+    #[inline(always)]
+    pub fn to_double(&self) -> f64 {
+        f64::from_bits(self.0)
+    }
+
+    #[inline(always)]
+    pub fn to_fixed64(&self) -> u64 {
+        self.0
+    }
+
+    #[inline(always)]
+    pub fn to_sfixed64(&self) -> i64 {
+        self.0 as i64
+    }
+
     #[inline(always)]
     pub fn to_bytes(&self) -> [u8; 8] {
         self.0.to_le_bytes()
@@ -149,6 +287,191 @@ pub enum WireType {
     I32 = 5,
 }
 
+impl TryFrom<u8> for WireType {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        match v {
+            0 => Ok(WireType::VarInt),
+            1 => Ok(WireType::I64),
+            2 => Ok(WireType::Len),
+            5 => Ok(WireType::I32),
+            // Groups (3 and 4) are deprecated and aren't supported
+            _ => Err(ErrorKind::InvalidWireType.into()),
+        }
+    }
+}
+
+/**
+A field read from an encoded message.
+
+The payload of length-prefixed fields is represented by `B`.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct Field<B> {
+    number: u64,
+    payload: Payload<B>,
+}
+
+/**
+The payload of a field read from an encoded message.
+*/
+#[derive(Debug, Clone, Copy)]
+pub enum Payload<B> {
+    VarInt(VarInt),
+    I64(I64),
+    Len(B),
+    I32(I32),
+}
+
+impl<B> Field<B> {
+    #[inline(always)]
+    pub fn new(number: u64, payload: Payload<B>) -> Self {
+        Field { number, payload }
+    }
+
+    #[inline(always)]
+    pub fn number(&self) -> u64 {
+        self.number
+    }
+
+    #[inline(always)]
+    pub fn wire_type(&self) -> WireType {
+        self.payload.wire_type()
+    }
+
+    #[inline(always)]
+    pub fn payload(&self) -> &Payload<B> {
+        &self.payload
+    }
+
+    #[inline(always)]
+    pub fn into_payload(self) -> Payload<B> {
+        self.payload
+    }
+}
+
+impl<B> Payload<B> {
+    #[inline(always)]
+    pub fn wire_type(&self) -> WireType {
+        match self {
+            Payload::VarInt(_) => WireType::VarInt,
+            Payload::I64(_) => WireType::I64,
+            Payload::Len(_) => WireType::Len,
+            Payload::I32(_) => WireType::I32,
+        }
+    }
+}
+
+impl<'a> Payload<&'a [u8]> {
+    /**
+    Read a single element of a packed repeated field from the start of `buf`.
+
+    This method returns the element along with the number of bytes it was encoded in.
+    Only `VARINT`, `I32`, and `I64` values can be packed.
+    */
+    #[inline]
+    pub fn decode_packed(buf: &'a [u8], wire_type: WireType) -> Result<(Self, usize), Error> {
+        match wire_type {
+            WireType::VarInt => VarInt::decode(buf).map(|(v, len)| (Payload::VarInt(v), len)),
+            WireType::I32 => I32::decode(buf).map(|(v, len)| (Payload::I32(v), len)),
+            WireType::I64 => I64::decode(buf).map(|(v, len)| (Payload::I64(v), len)),
+            WireType::Len => Err(ErrorKind::InvalidWireType.into()),
+        }
+    }
+}
+
+/**
+An iterator over the fields of an encoded message.
+
+Fields are yielded in the order they're encoded, borrowing length-prefixed payloads from the input.
+No allocations are made. If a field is malformed then an error is yielded and iteration stops.
+*/
+#[derive(Debug, Clone)]
+pub struct Fields<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Fields<'a> {
+    #[inline]
+    pub fn new(buf: &'a [u8]) -> Self {
+        Fields { buf }
+    }
+
+    /**
+    Get the portion of the message that hasn't been read yet.
+    */
+    #[inline]
+    pub fn remaining(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    fn read(&mut self) -> Result<Field<&'a [u8]>, Error> {
+        let (key, len) = VarInt::decode(self.buf)?;
+        self.buf = &self.buf[len..];
+
+        let (number, wire_type) = key.to_field()?;
+
+        let payload = match wire_type {
+            WireType::VarInt => {
+                let (v, len) = VarInt::decode(self.buf)?;
+                self.buf = &self.buf[len..];
+
+                Payload::VarInt(v)
+            }
+            WireType::I64 => {
+                let (v, len) = I64::decode(self.buf)?;
+                self.buf = &self.buf[len..];
+
+                Payload::I64(v)
+            }
+            WireType::I32 => {
+                let (v, len) = I32::decode(self.buf)?;
+                self.buf = &self.buf[len..];
+
+                Payload::I32(v)
+            }
+            WireType::Len => {
+                let (v, len) = VarInt::decode(self.buf)?;
+                self.buf = &self.buf[len..];
+
+                let len = usize::try_from(v.to_uint64()).unwrap_or(usize::MAX);
+                if self.buf.len() < len {
+                    return Err(ErrorKind::Truncated.into());
+                }
+
+                let (v, rest) = self.buf.split_at(len);
+                self.buf = rest;
+
+                Payload::Len(v)
+            }
+        };
+
+        Ok(Field::new(number, payload))
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<Field<&'a [u8]>, Error>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() {
+            return None;
+        }
+
+        match self.read() {
+            Ok(field) => Some(Ok(field)),
+            Err(err) => {
+                self.buf = &[];
+
+                Some(Err(err))
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -256,4 +579,168 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn decode_varint_roundtrip() {
+        for n in [
+            0,
+            1,
+            127,
+            128,
+            255,
+            300,
+            u32::MAX as u64,
+            u64::MAX - 1,
+            u64::MAX,
+        ] {
+            let mut buf = [0; 10];
+            let encoded = VarInt::uint64(n).fill_bytes(&mut buf);
+
+            let (decoded, len) = VarInt::decode(encoded).unwrap();
+
+            assert_eq!(n, decoded.to_uint64());
+            assert_eq!(encoded.len(), len);
+        }
+
+        for n in [0, 1, -1, i64::MIN, i64::MAX] {
+            let mut buf = [0; 10];
+
+            let (decoded, _) = VarInt::decode(VarInt::sint64(n).fill_bytes(&mut buf)).unwrap();
+            assert_eq!(n, decoded.to_sint64());
+
+            let (decoded, _) = VarInt::decode(VarInt::sint64z(n).fill_bytes(&mut buf)).unwrap();
+            assert_eq!(n, decoded.to_sint64z());
+        }
+    }
+
+    #[test]
+    fn decode_varint_trailing() {
+        let (decoded, len) = VarInt::decode(&[172, 2, 1, 2, 3]).unwrap();
+
+        assert_eq!(300, decoded.to_uint64());
+        assert_eq!(2, len);
+    }
+
+    #[test]
+    fn decode_varint_invalid() {
+        for (case, expected) in [
+            (&[] as &[u8], ErrorKind::Truncated),
+            (&[128u8, 128u8], ErrorKind::Truncated),
+            (
+                &[255u8, 255, 255, 255, 255, 255, 255, 255, 255, 2],
+                ErrorKind::Overlong,
+            ),
+            (
+                &[128u8, 128, 128, 128, 128, 128, 128, 128, 128, 128, 1],
+                ErrorKind::Overlong,
+            ),
+        ] {
+            assert_eq!(
+                expected,
+                VarInt::decode(case).unwrap_err().kind(),
+                "{:?}",
+                case
+            );
+        }
+    }
+
+    #[test]
+    fn decode_fixed() {
+        let (decoded, len) = I32::decode(&I32::float(1.5).to_bytes()).unwrap();
+        assert_eq!(1.5, decoded.to_float());
+        assert_eq!(4, len);
+
+        let (decoded, len) = I64::decode(&I64::sfixed64(-2).to_bytes()).unwrap();
+        assert_eq!(-2, decoded.to_sfixed64());
+        assert_eq!(8, len);
+
+        assert_eq!(
+            ErrorKind::Truncated,
+            I32::decode(&[1, 2, 3]).unwrap_err().kind()
+        );
+        assert_eq!(
+            ErrorKind::Truncated,
+            I64::decode(&[1, 2, 3, 4, 5, 6, 7]).unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn decode_field() {
+        for (number, wire_type) in [
+            (1, WireType::VarInt),
+            (2, WireType::I64),
+            (15, WireType::Len),
+            (MAX_FIELD_NUMBER, WireType::I32),
+        ] {
+            assert_eq!(
+                (number, wire_type),
+                VarInt::field(number, wire_type).to_field().unwrap()
+            );
+        }
+
+        assert_eq!(
+            ErrorKind::InvalidFieldNumber,
+            VarInt::field(0, WireType::VarInt)
+                .to_field()
+                .unwrap_err()
+                .kind()
+        );
+        assert_eq!(
+            ErrorKind::InvalidFieldNumber,
+            VarInt::field(MAX_FIELD_NUMBER + 1, WireType::VarInt)
+                .to_field()
+                .unwrap_err()
+                .kind()
+        );
+        assert_eq!(
+            ErrorKind::InvalidWireType,
+            VarInt::uint64((1 << 3) | 3).to_field().unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn read_fields() {
+        let buf = [
+            // 1: 150
+            8u8, 150, 1, //
+            // 2: {"ab"}
+            18, 2, b'a', b'b', //
+            // 3: 1i32
+            29, 1, 0, 0, 0, //
+            // 4: 1i64
+            33, 1, 0, 0, 0, 0, 0, 0, 0,
+        ];
+
+        let fields = Fields::new(&buf)
+            .collect::<Result<alloc::vec::Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(4, fields.len());
+
+        assert_eq!(1, fields[0].number());
+        assert!(matches!(fields[0].payload(), Payload::VarInt(v) if v.to_uint64() == 150));
+
+        assert_eq!(2, fields[1].number());
+        assert!(matches!(fields[1].payload(), Payload::Len(b"ab")));
+
+        assert_eq!(3, fields[2].number());
+        assert_eq!(WireType::I32, fields[2].wire_type());
+        assert!(matches!(fields[2].payload(), Payload::I32(v) if v.to_fixed32() == 1));
+
+        assert_eq!(4, fields[3].number());
+        assert_eq!(WireType::I64, fields[3].wire_type());
+        assert!(matches!(fields[3].payload(), Payload::I64(v) if v.to_fixed64() == 1));
+    }
+
+    #[test]
+    fn read_fields_invalid() {
+        let mut fields = Fields::new(&[8, 1, 18, 5, b'a']);
+
+        assert!(fields.next().unwrap().is_ok());
+        assert_eq!(
+            ErrorKind::Truncated,
+            fields.next().unwrap().unwrap_err().kind()
+        );
+        assert!(fields.next().is_none());
+    }
 }
@@ -1,7 +1,11 @@
-use crate::buf::{ProtoBuf, ProtoBufMut, ProtoBufMutReusable};
-use crate::raw::WireType;
+use crate::buf::{Mark, ProtoBuf, ProtoBufMut, ProtoBufMutReusable};
+use crate::raw::{VarInt, WireType, MAX_FIELD_NUMBER, RESERVED_FIELD_NUMBERS};
 use crate::tags;
-use sval::{Index, Label, Tag};
+use crate::well_known::{FieldMask, FieldSelection, Timestamp};
+use crate::{Error, ErrorKind};
+use alloc::{string::String, vec, vec::Vec};
+use core::{mem, ops::Range};
+use sval::{Index, Label, Stream as _, Tag};
 
 pub use crate::buf::Capacity;
 
@@ -9,93 +13,986 @@ pub use crate::buf::Capacity;
 Encode a value to the protobuf wire format.
 
 Standalone scalar values will be wrapped in a message with a field number `1`.
+Use a [`ProtoBufStream`] with [`RootOptions`] to change how standalone values are encoded.
+
+If the value fails to stream then the returned message may be incomplete.
+Use [`try_stream_to_protobuf`] to detect failures.
 */
 pub fn stream_to_protobuf(v: impl sval::Value) -> ProtoBuf {
     let mut stream = ProtoBufStream::new();
 
-    let _ = v.stream(&mut stream);
+    let _ = v.stream(&mut stream);
+
+    stream.buf.freeze()
+}
+
+/**
+Encode a value to the protobuf wire format, returning an error if it can't be encoded.
+
+Standalone scalar values will be wrapped in a message with a field number `1`.
+*/
+pub fn try_stream_to_protobuf(v: impl sval::Value) -> Result<ProtoBuf, Error> {
+    let mut stream = ProtoBufStream::new();
+
+    match v.stream(&mut stream) {
+        Ok(()) => stream.try_freeze(),
+        Err(_) => Err(stream
+            .error
+            .unwrap_or_else(|| Error::new(ErrorKind::Stream))),
+    }
+}
+
+/**
+An [`sval::Stream`] that encodes into the protobuf wire format.
+*/
+#[derive(Debug)]
+pub struct ProtoBufStream {
+    buf: ProtoBufMut<LenFrame>,
+    field: FieldState,
+    len: LenState,
+    one_of: OneOfState,
+    strict: StrictState,
+    integers: IntegerOptions,
+    int128: Int128Encoding,
+    number: NumberState,
+    collection: CollectionState,
+    map: MapState,
+    time: TimeState,
+    mask: MaskState,
+    error: Option<Error>,
+}
+
+/**
+Options for encoding values that aren't messages at the root of a [`ProtoBufStream`].
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RootOptions {
+    field_number: u64,
+    is_raw: bool,
+}
+
+impl Default for RootOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RootOptions {
+    /**
+    Create a new set of root options.
+
+    Standalone scalars, sequences, and maps will be wrapped in a message with a field number `1`.
+    */
+    pub fn new() -> Self {
+        RootOptions {
+            field_number: 1,
+            is_raw: false,
+        }
+    }
+
+    /**
+    Set the field number to wrap standalone scalars, sequences, and maps in.
+    */
+    pub fn with_field_number(mut self, field_number: u64) -> Self {
+        self.field_number = field_number;
+        self
+    }
+
+    /**
+    Whether to encode standalone scalars without wrapping them in a message.
+
+    Raw varints and fixed-width numbers are written without a field key.
+    Raw text and binary values are written without a field key or length prefix.
+    The result can be embedded in a larger message using [`ProtoBuf::pre_encoded`].
+
+    Sequences and maps are always wrapped in a message.
+    */
+    pub fn with_raw_scalars(mut self, raw: bool) -> Self {
+        self.is_raw = raw;
+        self
+    }
+
+    /**
+    Get the field number to wrap standalone scalars, sequences, and maps in.
+    */
+    pub fn field_number(&self) -> u64 {
+        self.field_number
+    }
+
+    /**
+    Whether standalone scalars are encoded without wrapping them in a message.
+    */
+    pub fn raw_scalars(&self) -> bool {
+        self.is_raw
+    }
+}
+
+/**
+The default encodings for integers that aren't tagged.
+
+Integers can be tagged with [`tags::PROTOBUF_VARINT`], [`tags::PROTOBUF_VARINT_SIGNED`],
+[`tags::PROTOBUF_I32`], or [`tags::PROTOBUF_I64`] to override these defaults.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntegerOptions {
+    i32: IntegerEncoding,
+    i64: IntegerEncoding,
+    u32: IntegerEncoding,
+    u64: IntegerEncoding,
+}
+
+impl Default for IntegerOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IntegerOptions {
+    /**
+    Create a new set of integer options.
+
+    All integers will be encoded as plain varints.
+    */
+    pub fn new() -> Self {
+        IntegerOptions {
+            i32: IntegerEncoding::VarInt,
+            i64: IntegerEncoding::VarInt,
+            u32: IntegerEncoding::VarInt,
+            u64: IntegerEncoding::VarInt,
+        }
+    }
+
+    /**
+    Set the default encoding for both 32 and 64bit signed integers.
+    */
+    pub fn with_signed(self, encoding: IntegerEncoding) -> Self {
+        self.with_i32(encoding).with_i64(encoding)
+    }
+
+    /**
+    Set the default encoding for both 32 and 64bit unsigned integers.
+    */
+    pub fn with_unsigned(self, encoding: IntegerEncoding) -> Self {
+        self.with_u32(encoding).with_u64(encoding)
+    }
+
+    /**
+    Set the default encoding for 32bit signed integers.
+    */
+    pub fn with_i32(mut self, encoding: IntegerEncoding) -> Self {
+        self.i32 = encoding;
+        self
+    }
+
+    /**
+    Set the default encoding for 64bit signed integers.
+    */
+    pub fn with_i64(mut self, encoding: IntegerEncoding) -> Self {
+        self.i64 = encoding;
+        self
+    }
+
+    /**
+    Set the default encoding for 32bit unsigned integers.
+    */
+    pub fn with_u32(mut self, encoding: IntegerEncoding) -> Self {
+        self.u32 = encoding;
+        self
+    }
+
+    /**
+    Set the default encoding for 64bit unsigned integers.
+    */
+    pub fn with_u64(mut self, encoding: IntegerEncoding) -> Self {
+        self.u64 = encoding;
+        self
+    }
+}
+
+/**
+The encoding to use for an integer.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum IntegerEncoding {
+    /**
+    Encode as a plain varint, like `int32`, `int64`, `uint32`, and `uint64`.
+    */
+    #[default]
+    VarInt,
+    /**
+    Encode as a zigzag varint, like `sint32` and `sint64`.
+
+    Unsigned integers are encoded as plain varints.
+    */
+    Signed,
+    /**
+    Encode as a fixed-width number, like `fixed32`, `fixed64`, `sfixed32`, and `sfixed64`.
+    */
+    Fixed,
+}
+
+/**
+The strategy to use when encoding 128bit integers.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Int128Encoding {
+    /**
+    Encode as a 16 byte `bytes` field with the little-endian bytes of the value.
+    */
+    #[default]
+    Bytes,
+    /**
+    Encode as a message with the upper 64bits in a `fixed64` field `1` and the lower 64bits in a `fixed64` field `2`.
+
+    Signed values use their two's complement representation.
+    */
+    HiLo,
+    /**
+    Encode as a `string` field containing the value in decimal.
+    */
+    Decimal,
+}
+
+/**
+The strategy to use when encoding arbitrary-precision numbers tagged with [`sval::tags::NUMBER`].
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum NumberEncoding {
+    /**
+    Encode as a `string` field containing the text of the number.
+    */
+    #[default]
+    Text,
+    /**
+    Encode as a `double` field.
+
+    Numbers that can't be represented exactly will lose precision.
+    */
+    Double,
+    /**
+    Encode as an `int64` field if the number is negative, or a `uint64` field if it's positive.
+
+    Numbers that aren't integers or don't fit in 64bits are encoded as a `string` field.
+    */
+    Integer,
+}
+
+impl ProtoBufStream {
+    /**
+    Create a new protobuf stream.
+    */
+    pub fn new() -> Self {
+        Self::from_buf(ProtoBufMut::new(LenFrame::new()))
+    }
+
+    /**
+    Create a new protobuf stream from reusable internals.
+    */
+    pub fn new_reuse(reuse: ProtoBufStreamReusable) -> Self {
+        Self::from_buf(ProtoBufMut::new_reuse(reuse.0, LenFrame::new()))
+    }
+
+    fn from_buf(buf: ProtoBufMut<LenFrame>) -> Self {
+        ProtoBufStream {
+            buf,
+            field: FieldState {
+                number: 1,
+                ty: FieldType::Root,
+                is_raw: false,
+                is_implicit: false,
+                is_wrapper: false,
+                omit_defaults: false,
+            },
+            len: LenState {
+                is_packed: false,
+                in_packed: false,
+                is_prefixed: false,
+                is_omitted: false,
+                is_expanded: false,
+                is_map: false,
+                is_pending_packed: false,
+                is_pending_prefix: false,
+                is_pending_entry: false,
+                num_entries: None,
+                prefixed_len: None,
+                auto_packed: false,
+            },
+            one_of: OneOfState {
+                is_internally_tagged: false,
+            },
+            strict: StrictState {
+                is_strict: false,
+                fields: Vec::new(),
+                in_map_key: false,
+                map_value_depth: None,
+            },
+            integers: IntegerOptions::new(),
+            int128: Int128Encoding::Bytes,
+            number: NumberState {
+                encoding: NumberEncoding::Text,
+                is_number: false,
+                is_buffering: false,
+                text: String::new(),
+            },
+            collection: CollectionState {
+                is_wrapping: false,
+                value_depth: None,
+            },
+            map: MapState {
+                is_deterministic: false,
+                maps: Vec::new(),
+            },
+            time: TimeState {
+                is_timestamp: false,
+                is_message: false,
+            },
+            mask: MaskState {
+                mask: None,
+                nodes: Vec::new(),
+                fields: Vec::new(),
+                skip_depth: 0,
+            },
+            error: None,
+        }
+    }
+
+    /**
+    Whether to validate field numbers and the shape of messages while encoding.
+
+    In strict mode, the stream will fail if:
+
+    - a field number is outside the valid range of `1` to [`crate::raw::MAX_FIELD_NUMBER`],
+      or within [`crate::raw::RESERVED_FIELD_NUMBERS`]. Outside of strict mode, indexes that
+      can't be converted into a field number, like negative ones, fall back to `1`.
+    - a field number is used more than once in the same message.
+    - a map key is a message, sequence, or map.
+    - a map value is itself a map.
+
+    Failures can be detected through [`ProtoBufStream::try_freeze`].
+    Strict mode is disabled by default.
+    */
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict.is_strict = strict;
+        self
+    }
+
+    /**
+    Whether to omit fields that hold their default value, like proto3 fields with implicit presence.
+
+    When enabled, message fields holding `0`, `false`, an empty string, or empty bytes won't
+    be written. Fields that are explicitly present, like `Option<T>`s that are `Some`, and
+    members of a oneof are always written. Values in sequences and maps are always written.
+
+    Members of a oneof that's flattened into its parent message can't be distinguished from
+    regular fields, so they'll be omitted when they hold their default value.
+
+    Omitting defaults is disabled by default.
+    */
+    pub fn with_omit_defaults(mut self, omit_defaults: bool) -> Self {
+        self.field.omit_defaults = omit_defaults;
+        self
+    }
+
+    /**
+    Whether to pack sequences of numeric values automatically.
+
+    When enabled, any sequence whose first value is a varint, i32, or i64 will be packed,
+    as if it was tagged with [`tags::PROTOBUF_LEN_PACKED`]. Sequences can opt-out by being
+    tagged with [`tags::PROTOBUF_LEN_EXPANDED`].
+
+    Automatic packing is disabled by default.
+    */
+    pub fn with_auto_packed(mut self, auto_packed: bool) -> Self {
+        self.len.auto_packed = auto_packed;
+        self
+    }
+
+    /**
+    Whether to wrap sequences and maps that are nested directly in other sequences or maps in a message.
+
+    Protobuf doesn't support repeated fields of repeated fields or maps of maps.
+    When enabled, each nested sequence or map is encoded as a message with its values
+    in field `1`, like:
+
+    ```text
+    message Nested {
+        repeated T values = 1;
+    }
+    ```
+
+    Wrapping nested collections is disabled by default, so their values are flattened into the
+    field of the outer collection.
+    */
+    pub fn with_wrap_nested(mut self, wrap: bool) -> Self {
+        self.collection.is_wrapping = wrap;
+        self
+    }
+
+    /**
+    Whether to write the entries of maps in a deterministic order.
+
+    When enabled, the entries of each map, including sequences tagged with [`tags::PROTOBUF_MAP`],
+    are buffered and written sorted by the bytes of their encoded keys. Identical maps will
+    always be encoded to identical bytes, regardless of the order their entries were streamed in.
+
+    Deterministic maps are disabled by default, so entries are written in the order they're streamed.
+    */
+    pub fn with_deterministic_maps(mut self, deterministic: bool) -> Self {
+        self.map.is_deterministic = deterministic;
+        self
+    }
+
+    /**
+    Only encode the fields selected by a [`FieldMask`].
+
+    Fields are matched by their number and label. Any fields that aren't selected are skipped
+    along with all of their nested values, without buffering them. The mask applies to fields
+    of records and tuples. Sequences and enums are matched through to the fields of their values,
+    and maps are always encoded in full.
+
+    All fields are encoded by default.
+    */
+    pub fn with_field_mask(mut self, mask: FieldMask) -> Self {
+        self.mask.nodes = vec![mask.root()];
+        self.mask.mask = Some(mask);
+        self
+    }
+
+    /**
+    Set the default encodings for integers that aren't tagged.
+
+    By default, all integers are encoded as plain varints.
+    */
+    pub fn with_integers(mut self, integers: IntegerOptions) -> Self {
+        self.integers = integers;
+        self
+    }
+
+    /**
+    Set the strategy to use when encoding 128bit integers.
+
+    The default strategy is [`Int128Encoding::Bytes`].
+    */
+    pub fn with_int128_encoding(mut self, encoding: Int128Encoding) -> Self {
+        self.int128 = encoding;
+        self
+    }
+
+    /**
+    Set the strategy to use when encoding numbers tagged with [`sval::tags::NUMBER`].
+
+    The default strategy is [`NumberEncoding::Text`].
+    */
+    pub fn with_number_encoding(mut self, encoding: NumberEncoding) -> Self {
+        self.number.encoding = encoding;
+        self
+    }
+
+    /**
+    Set the options for encoding a value that isn't a message.
+
+    These options only apply to the root value, and need to be set before anything is streamed.
+    */
+    pub fn with_root(mut self, root: RootOptions) -> Self {
+        self.field.number = root.field_number;
+        self.field.is_raw = root.is_raw;
+
+        self.buf.state_mut().number = root.field_number;
+
+        self
+    }
+
+    /**
+    Complete the stream, returning the encoded protobuf message.
+    */
+    #[inline]
+    pub fn freeze(self) -> ProtoBuf {
+        self.buf.freeze()
+    }
+
+    /**
+    Complete the stream, returning the encoded protobuf message or an error if it couldn't be encoded.
+
+    The stream fails if any value had a shape that can't be encoded, or, in strict mode,
+    was given an invalid field number. Every nested message or length-prefixed field that was started must also
+    have been completed.
+    */
+    pub fn try_freeze(self) -> Result<ProtoBuf, Error> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+
+        if self.buf.depth() != 0 {
+            return Err(Error::new(ErrorKind::Unbalanced));
+        }
+
+        Ok(self.buf.freeze())
+    }
+
+    /**
+    Complete the stream, returning the encoded protobuf message.
+
+    This method also returns some temporary allocations and metadata about the encoded payload
+    that can be used to encode a similar payload more efficiently later.
+    */
+    #[inline]
+    pub fn freeze_reuse(self) -> (ProtoBuf, ProtoBufStreamReusable) {
+        let (buf, reuse) = self.buf.freeze_reuse();
+
+        (buf, ProtoBufStreamReusable(reuse))
+    }
+
+    #[cold]
+    fn fail(&mut self, kind: ErrorKind) -> sval::Error {
+        let err = Error::new(kind);
+
+        if self.error.is_none() {
+            self.error = Some(err);
+        }
+
+        err.into()
+    }
+
+    /**
+    Begin a field, returning whether it's selected by the field mask.
+
+    If the field isn't selected then everything streamed until its end is skipped.
+    */
+    fn mask_field_begin(&mut self, label: Option<&Label>, index: &Index) -> bool {
+        if self.mask.is_skipping() {
+            self.mask.skip_depth += 1;
+
+            return false;
+        }
+
+        let mask = match self.mask.mask {
+            Some(ref mask) => mask,
+            None => return true,
+        };
+
+        let parents = match self.mask.fields.last() {
+            Some(Some(start)) => *start..self.mask.nodes.len(),
+            // The parent field is selected in full
+            Some(None) => {
+                self.mask.fields.push(None);
+
+                return true;
+            }
+            None => 0..self.mask.nodes.len(),
+        };
+
+        let start = self.mask.nodes.len();
+        match mask.push_field(
+            &mut self.mask.nodes,
+            parents,
+            field_number(index),
+            label.map(|label| label.as_str()),
+        ) {
+            FieldSelection::Full => self.mask.fields.push(None),
+            FieldSelection::Partial => self.mask.fields.push(Some(start)),
+            FieldSelection::Excluded => {
+                self.mask.skip_depth = 1;
+
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /**
+    End a field, returning whether it was selected by the field mask.
+    */
+    fn mask_field_end(&mut self) -> bool {
+        if self.mask.is_skipping() {
+            self.mask.skip_depth -= 1;
+
+            return false;
+        }
+
+        if let Some(Some(start)) = self.mask.fields.pop() {
+            self.mask.nodes.truncate(start);
+        }
+
+        true
+    }
+
+    fn field_value_begin(&mut self, label: Option<&Label>, index: &Index) -> sval::Result {
+        if !self.mask_field_begin(label, index) {
+            return Ok(());
+        }
+
+        self.field_begin();
+
+        if let Some(entry_values) = self.buf.state_mut().entry_values.as_mut() {
+            let value = *entry_values;
+            *entry_values += 1;
+
+            // The first value is the key and the second is the value
+            return match value {
+                0 => {
+                    self.map_entry_key_begin();
+
+                    Ok(())
+                }
+                1 => self.map_value_begin(),
+                _ => Err(self.fail(ErrorKind::Unsupported)),
+            };
+        }
+
+        self.field_set(index)?;
+
+        self.field.is_implicit = self.field.omit_defaults || self.buf.state_mut().omit_defaults;
+
+        Ok(())
+    }
+
+    fn field_value_end(&mut self) -> sval::Result {
+        if !self.mask_field_end() {
+            return Ok(());
+        }
+
+        if self.buf.state_mut().entry_values == Some(1) {
+            self.map_key_end()?;
+        }
+
+        Ok(())
+    }
+
+    /**
+    Begin a map, selecting all of its keys and values.
+    */
+    fn mask_map_begin(&mut self) {
+        if self.mask.mask.is_some() {
+            self.mask.fields.push(None);
+        }
+    }
+
+    fn mask_map_end(&mut self) {
+        if self.mask.mask.is_some() {
+            self.mask.fields.pop();
+        }
+    }
+
+    #[inline(always)]
+    fn field_set(&mut self, index: &Index) -> sval::Result {
+        let number = field_number(index);
+
+        self.field.set(number);
+
+        if self.strict.is_strict {
+            self.strict_field(number)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn strict_field(&mut self, number: Option<u64>) -> sval::Result {
+        // The index must be a valid field number, rather than falling back to `1`
+        let number = match number {
+            Some(number) => number,
+            None => return Err(self.fail(ErrorKind::InvalidFieldNumber)),
+        };
+
+        if number == 0 || number > MAX_FIELD_NUMBER || RESERVED_FIELD_NUMBERS.contains(&number) {
+            return Err(self.fail(ErrorKind::InvalidFieldNumber));
+        }
+
+        // Fields are tracked along with the depth of the message they belong to
+        // Any fields from more deeply nested messages have been completed
+        let depth = self.buf.depth();
+        while let Some((field_depth, _)) = self.strict.fields.last() {
+            if *field_depth > depth {
+                self.strict.fields.pop();
+            } else {
+                break;
+            }
+        }
+
+        if self
+            .strict
+            .fields
+            .iter()
+            .rev()
+            .take_while(|(field_depth, _)| *field_depth == depth)
+            .any(|(_, field_number)| *field_number == number)
+        {
+            return Err(self.fail(ErrorKind::DuplicateField));
+        }
+
+        self.strict.fields.push((depth, number));
+
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn integer_ty(&self, default: IntegerEncoding, fixed: FieldType) -> FieldType {
+        match self.field.ty {
+            // Untagged integers use the configured default
+            FieldType::Any | FieldType::Root => match default {
+                IntegerEncoding::VarInt => FieldType::VarInt,
+                IntegerEncoding::Signed => FieldType::Signed,
+                IntegerEncoding::Fixed => fixed,
+            },
+            ty => ty,
+        }
+    }
+
+    #[inline(always)]
+    fn is_omitted(&mut self, is_default: bool) -> bool {
+        // Values in fields that aren't selected by the field mask are always omitted
+        if self.mask.is_skipping() {
+            return true;
+        }
 
-    stream.buf.freeze()
-}
+        if is_default && self.field.is_implicit && self.field.is_set() {
+            self.field.number = 0;
 
-/**
-An [`sval::Stream`] that encodes into the protobuf wire format.
-*/
-#[derive(Debug)]
-pub struct ProtoBufStream {
-    buf: ProtoBufMut<u64>,
-    field: FieldState,
-    len: LenState,
-    one_of: OneOfState,
-}
+            true
+        } else {
+            false
+        }
+    }
 
-impl ProtoBufStream {
-    /**
-    Create a new protobuf stream.
-    */
-    pub fn new() -> Self {
-        Self::from_buf(ProtoBufMut::new(1))
+    #[inline(always)]
+    fn message_begin(&mut self) -> sval::Result {
+        self.len.is_pending_packed = false;
+
+        // Messages can't be used as map keys
+        if self.strict.is_strict && self.strict.in_map_key {
+            return Err(self.fail(ErrorKind::Unsupported));
+        }
+
+        self.field.push(WireType::Len, &mut self.buf);
+        self.buf.begin_len(LenFrame::new());
+
+        if self.strict.is_strict {
+            // Forget any fields from a previous message at the same depth
+            let depth = self.buf.depth();
+            while let Some((field_depth, _)) = self.strict.fields.last() {
+                if *field_depth >= depth {
+                    self.strict.fields.pop();
+                } else {
+                    break;
+                }
+            }
+        }
+
+        Ok(())
     }
 
-    /**
-    Create a new protobuf stream from reusable internals.
-    */
-    pub fn new_reuse(reuse: ProtoBufStreamReusable) -> Self {
-        Self::from_buf(ProtoBufMut::new_reuse(reuse.0, 1))
+    #[inline(always)]
+    fn collection_begin(&mut self, is_map: bool) -> sval::Result {
+        self.packed_begin()?;
+
+        // Only scalars can be wrapped, and sequences and maps aren't map entries
+        if self.field.is_wrapper || self.len.is_pending_entry {
+            return Err(self.fail(ErrorKind::Unsupported));
+        }
+
+        if self.strict.is_strict {
+            // Sequences and maps can't be used as map keys
+            if self.strict.in_map_key {
+                return Err(self.fail(ErrorKind::Unsupported));
+            }
+
+            // Maps can't be used as map values, unless they're wrapped
+            if is_map
+                && !self.collection.is_wrapping
+                && self.strict.map_value_depth == Some(self.buf.depth())
+            {
+                return Err(self.fail(ErrorKind::Unsupported));
+            }
+        }
+
+        // Sequences and maps that are values in other sequences or maps get wrapped in a message
+        if self.collection.is_wrapping
+            && self.field.is_set()
+            && self.collection.value_depth == Some(self.buf.depth())
+        {
+            self.collection.value_depth = None;
+
+            self.message_begin()?;
+            self.buf.state_mut().is_wrapped = true;
+
+            self.field.number = 1;
+        }
+
+        Ok(())
     }
 
-    fn from_buf(buf: ProtoBufMut<u64>) -> Self {
-        ProtoBufStream {
-            buf,
-            field: FieldState {
-                number: 1,
-                ty: FieldType::Root,
-            },
-            len: LenState {
-                is_packed: false,
-                is_prefixed: false,
-            },
-            one_of: OneOfState {
-                is_internally_tagged: false,
-            },
+    fn map_entry_begin(&mut self, frame: LenFrame) {
+        let start = self.buf.mark();
+
+        self.field.number = self.buf.state_mut().number;
+        self.field.push(WireType::Len, &mut self.buf);
+
+        let value_ty = self.buf.state_mut().value_ty;
+        self.buf.begin_len(LenFrame { value_ty, ..frame });
+
+        if self.map.is_deterministic {
+            let key = self.buf.mark();
+
+            if let Some(entries) = self.map.maps.last_mut() {
+                entries.push(MapEntry {
+                    start,
+                    key: key..key,
+                });
+            }
         }
     }
 
-    /**
-    Complete the stream, returning the encoded protobuf message.
-    */
-    #[inline]
-    pub fn freeze(self) -> ProtoBuf {
-        self.buf.freeze()
+    fn map_entry_key_begin(&mut self) {
+        self.field.number = 1;
+        self.field.is_implicit = false;
+
+        self.strict.in_map_key = true;
     }
 
-    /**
-    Complete the stream, returning the encoded protobuf message.
+    fn map_entries_begin(&mut self) {
+        if self.map.is_deterministic {
+            self.map.maps.push(Vec::new());
+        }
+    }
 
-    This method also returns some temporary allocations and metadata about the encoded payload
-    that can be used to encode a similar payload more efficiently later.
-    */
-    #[inline]
-    pub fn freeze_reuse(self) -> (ProtoBuf, ProtoBufStreamReusable) {
-        let (buf, reuse) = self.buf.freeze_reuse();
+    fn map_entries_end(&mut self) {
+        if !self.map.is_deterministic {
+            return;
+        }
 
-        (buf, ProtoBufStreamReusable(reuse))
+        let entries = match self.map.maps.pop() {
+            Some(entries) => entries,
+            None => return,
+        };
+
+        let start = match entries.first() {
+            Some(entry) => entry.start,
+            None => return,
+        };
+
+        let end = self.buf.mark();
+
+        // Each entry runs until the start of the next one
+        let mut ranges = entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let entry_end = entries.get(i + 1).map(|next| next.start).unwrap_or(end);
+
+                (entry.key.clone(), entry.start..entry_end)
+            })
+            .collect::<Vec<_>>();
+
+        let buf = &self.buf;
+        ranges.sort_by(|(a, _), (b, _)| {
+            buf.to_vec_range(a.clone())
+                .cmp(&buf.to_vec_range(b.clone()))
+        });
+
+        self.buf
+            .reorder(start, ranges.into_iter().map(|(_, entry)| entry));
+    }
+
+    #[inline(always)]
+    fn wrapper_begin(&mut self) {
+        // Wrapped scalars are written in field `1` of a message that's always present
+        if mem::take(&mut self.field.is_wrapper) && self.field.is_set() {
+            self.len.is_pending_packed = false;
+
+            self.field.push(WireType::Len, &mut self.buf);
+            self.buf.begin_len(LenFrame {
+                is_wrapper: true,
+                ..LenFrame::new()
+            });
+
+            self.field.number = 1;
+            self.field.is_implicit = false;
+        }
+    }
+
+    #[inline(always)]
+    fn collection_end(&mut self) {
+        if self.buf.state_mut().is_wrapped {
+            self.buf.end_len();
+        }
+    }
+
+    #[inline(always)]
+    fn scalar_begin(&mut self, width: Option<usize>) {
+        if self.mask.is_skipping() {
+            return;
+        }
+
+        self.wrapper_begin();
+
+        // If the first value in a sequence is a scalar then pack it
+        if self.len.is_pending_packed {
+            self.len.is_pending_packed = false;
+            self.len.is_packed = true;
+            self.len.in_packed = true;
+            self.len.is_pending_prefix = true;
+        }
+
+        if self.len.is_pending_prefix {
+            self.len.is_pending_prefix = false;
+
+            self.packed_prefix(width);
+        }
+    }
+
+    fn packed_prefix(&mut self, width: Option<usize>) {
+        self.field.number = self.buf.state_mut().number;
+
+        match (self.len.num_entries, width) {
+            // If the values are fixed-width and their count is known then so is the length
+            (Some(num_entries), Some(width)) => {
+                let len = num_entries * width;
+
+                self.buf.reserve_bytes(len);
+
+                self.field.push_if_set(WireType::Len, &mut self.buf);
+
+                self.len.prefixed_len = Some((self.buf.mark(), len));
+                self.buf.push_len_varint_uint64(len as u64);
+            }
+            _ => {
+                self.field.push_if_set(WireType::Len, &mut self.buf);
+                self.buf.begin_len(LenFrame::new());
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn packed_begin(&mut self) -> sval::Result {
+        // If the first value in a sequence isn't a scalar then don't pack it
+        self.len.is_pending_packed = false;
+
+        // Packed fields can only contain varint, i32, or i64 values
+        if self.len.in_packed {
+            Err(self.fail(ErrorKind::Unsupported))
+        } else {
+            Ok(())
+        }
     }
 
     #[inline(always)]
-    fn internally_tagged_begin(&mut self, index: Option<&Index>) {
+    fn internally_tagged_begin(&mut self, index: Option<&Index>) -> sval::Result {
         if self.one_of.is_internally_tagged {
             self.one_of.is_internally_tagged = false;
 
             if self.field.is_set() {
-                self.field.push(WireType::Len, &mut self.buf);
-                self.buf.begin_len(1);
+                self.message_begin()?;
             }
 
             if let Some(index) = index {
-                self.field.set(index);
+                self.field_set(index)?;
             }
         }
+
+        Ok(())
     }
 
     #[inline(always)]
@@ -107,11 +1004,11 @@ impl ProtoBufStream {
 
     #[inline(always)]
     fn root_begin(&mut self) {
+        self.field.is_raw = false;
+
         if let FieldType::Root = self.field.ty {
-            self.field = FieldState {
-                ty: FieldType::Any,
-                number: 0,
-            }
+            self.field.ty = FieldType::Any;
+            self.field.number = 0;
         }
     }
 
@@ -120,8 +1017,163 @@ impl ProtoBufStream {
         self.one_of.is_internally_tagged = false;
     }
 
+    fn number_end(&mut self) -> sval::Result {
+        let text = mem::take(&mut self.number.text);
+
+        let r = match self.number.encoding {
+            NumberEncoding::Double => match text.parse::<f64>() {
+                Ok(value) => self.f64(value),
+                Err(_) => Err(self.fail(ErrorKind::InvalidValue)),
+            },
+            NumberEncoding::Integer => {
+                if let Ok(value) = text.parse::<u64>() {
+                    self.u64(value)
+                } else if let Ok(value) = text.parse::<i64>() {
+                    self.i64(value)
+                } else {
+                    self.number_text(&text)
+                }
+            }
+            NumberEncoding::Text => self.number_text(&text),
+        };
+
+        // Keep the allocation around for the next number
+        self.number.text = text;
+        self.number.text.clear();
+
+        r
+    }
+
+    fn number_text(&mut self, text: &str) -> sval::Result {
+        self.non_root_binary_begin(Some(text.len()))?;
+        self.buf.push(text.as_bytes());
+        self.non_root_binary_end()
+    }
+
+    fn timestamp_end(&mut self) -> sval::Result {
+        let text = mem::take(&mut self.number.text);
+
+        let r = match Timestamp::parse_rfc3339(&text) {
+            Ok(timestamp) => self.seconds_nanos(timestamp.seconds(), timestamp.nanos()),
+            Err(err) => Err(self.fail(err.kind())),
+        };
+
+        // Keep the allocation around for the next number or timestamp
+        self.number.text = text;
+        self.number.text.clear();
+
+        r
+    }
+
+    fn seconds_nanos(&mut self, seconds: i64, nanos: i32) -> sval::Result {
+        // The fields of timestamps and durations are omitted when they're zero
+        let field_len = |value: i64| {
+            if value != 0 {
+                1 + VarInt::sint64(value).len()
+            } else {
+                0
+            }
+        };
+
+        // At the root, the fields are written directly
+        let is_root = self.field.ty == FieldType::Root;
+
+        if is_root {
+            self.root_begin();
+        } else {
+            // Messages are always explicitly present
+            self.field.is_implicit = false;
+
+            self.non_root_binary_begin(Some(field_len(seconds) + field_len(nanos as i64)))?;
+        }
+
+        if seconds != 0 {
+            self.buf.push_field_varint(1);
+            self.buf.push_varint_sint64(seconds);
+        }
+
+        if nanos != 0 {
+            self.buf.push_field_varint(2);
+            self.buf.push_varint_sint64(nanos as i64);
+        }
+
+        if is_root {
+            Ok(())
+        } else {
+            self.non_root_binary_end()
+        }
+    }
+
+    fn int128_hi_lo(&mut self, value: u128) -> sval::Result {
+        // The message is two fixed64 fields, each with a single byte key
+        self.non_root_binary_begin(Some(18))?;
+
+        self.buf.push_field_i64(1);
+        self.buf.push_i64_fixed64((value >> 64) as u64);
+        self.buf.push_field_i64(2);
+        self.buf.push_i64_fixed64(value as u64);
+
+        self.non_root_binary_end()
+    }
+
+    fn int128_decimal(&mut self, is_negative: bool, mut value: u128) -> sval::Result {
+        // The largest value has 39 digits, plus a sign
+        let mut buf = [0; 40];
+        let mut start = buf.len();
+
+        loop {
+            start -= 1;
+            buf[start] = b'0' + (value % 10) as u8;
+            value /= 10;
+
+            if value == 0 {
+                break;
+            }
+        }
+
+        if is_negative {
+            start -= 1;
+            buf[start] = b'-';
+        }
+
+        let digits = &buf[start..];
+
+        self.non_root_binary_begin(Some(digits.len()))?;
+        self.buf.push(digits);
+        self.non_root_binary_end()
+    }
+
     #[inline(always)]
     fn non_root_binary_begin(&mut self, num_bytes: Option<usize>) -> sval::Result {
+        // Fragments of text and binary in fields that aren't selected are dropped
+        if self.mask.is_skipping() {
+            self.len.is_omitted = true;
+
+            return Ok(());
+        }
+
+        self.wrapper_begin();
+        self.packed_begin()?;
+
+        // Raw root values are written without a key or length
+        if self.field.is_raw {
+            self.field.is_raw = false;
+            self.field.number = 0;
+            self.len.is_prefixed = true;
+
+            if let Some(num_bytes) = num_bytes {
+                self.buf.reserve_bytes(num_bytes);
+            }
+
+            return Ok(());
+        }
+
+        if num_bytes == Some(0) && self.is_omitted(true) {
+            self.len.is_omitted = true;
+
+            return Ok(());
+        }
+
         if let Some(num_bytes) = num_bytes {
             self.len.is_prefixed = true;
 
@@ -133,7 +1185,7 @@ impl ProtoBufStream {
             Ok(())
         } else {
             self.field.push_if_set(WireType::Len, &mut self.buf);
-            self.buf.begin_len(1);
+            self.buf.begin_len(LenFrame::new());
 
             Ok(())
         }
@@ -141,7 +1193,11 @@ impl ProtoBufStream {
 
     #[inline(always)]
     fn non_root_binary_end(&mut self) -> sval::Result {
-        if self.len.is_prefixed {
+        if self.len.is_omitted {
+            self.len.is_omitted = false;
+
+            Ok(())
+        } else if self.len.is_prefixed {
             self.len.is_prefixed = false;
 
             Ok(())
@@ -157,13 +1213,18 @@ impl ProtoBufStream {
 struct FieldState {
     number: u64,
     ty: FieldType,
+    is_raw: bool,
+    is_implicit: bool,
+    is_wrapper: bool,
+    omit_defaults: bool,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum FieldType {
     Any,
     Root,
     PreEncoded,
+    VarInt,
     Signed,
     I32,
     I64,
@@ -175,41 +1236,161 @@ impl FieldState {
         self.number != 0
     }
 
-    #[inline]
-    fn set(&mut self, index: &Index) {
-        self.ty = FieldType::Any;
-        self.number = match index.tag() {
-            // Field indexes are 1-based in protobuf, but 0-based in sval
-            // If the index came from a Rust field offset then increment it
-            Some(&sval::tags::VALUE_OFFSET) => index.to_u64().unwrap_or_default() + 1,
-            // If the index was specified then use it directly
-            _ => index.to_u64().unwrap_or(1),
-        }
-    }
+    #[inline]
+    fn set(&mut self, number: Option<u64>) {
+        self.ty = FieldType::Any;
+        self.is_implicit = false;
+
+        // Indexes that aren't valid field numbers fall back to `1` outside of strict mode
+        self.number = number.unwrap_or(1);
+    }
+
+    #[inline(always)]
+    fn push_if_set<T>(&mut self, wire_type: WireType, buf: &mut ProtoBufMut<T>) {
+        if self.is_raw {
+            self.is_raw = false;
+            self.number = 0;
+        } else if self.is_set() {
+            self.push(wire_type, buf)
+        }
+    }
+
+    #[inline(always)]
+    fn push<T>(&mut self, wire_type: WireType, buf: &mut ProtoBufMut<T>) {
+        buf.push_field(self.number, wire_type);
+        self.number = 0;
+    }
+}
+
+#[inline]
+fn field_number(index: &Index) -> Option<u64> {
+    match index.tag() {
+        // Field indexes are 1-based in protobuf, but 0-based in sval
+        // If the index came from a Rust field offset then increment it
+        Some(&sval::tags::VALUE_OFFSET) => index.to_u64().and_then(|index| index.checked_add(1)),
+        // If the index was specified then use it directly
+        // The number must fit in a field key alongside its wire type
+        _ => index.to_u64().filter(|number| *number <= u64::MAX >> 3),
+    }
+}
+
+#[derive(Debug)]
+struct LenState {
+    is_packed: bool,
+    in_packed: bool,
+    is_prefixed: bool,
+    is_omitted: bool,
+    is_expanded: bool,
+    is_map: bool,
+    is_pending_packed: bool,
+    is_pending_prefix: bool,
+    // Whether a value in a sequence tagged as a map hasn't started a map entry yet
+    is_pending_entry: bool,
+    num_entries: Option<usize>,
+    // The position and value of a length prefix written before the values in a packed sequence
+    prefixed_len: Option<(Mark, usize)>,
+    auto_packed: bool,
+}
+
+#[derive(Debug)]
+struct OneOfState {
+    is_internally_tagged: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LenFrame {
+    // The field number of values in a sequence or map
+    number: u64,
+    // Whether the frame is a synthetic message wrapping a nested sequence or map
+    is_wrapped: bool,
+    // Whether values in a sequence are map entries
+    is_map: bool,
+    // The number of values seen in a map entry streamed as a tuple or record
+    entry_values: Option<usize>,
+    // Whether fields in the message always omit their default values, like in well-known types
+    omit_defaults: bool,
+    // Whether the frame is a well-known wrapper message around a scalar
+    is_wrapper: bool,
+    // The encoding of values in a map
+    value_ty: FieldType,
+}
+
+impl LenFrame {
+    #[inline(always)]
+    fn new() -> Self {
+        LenFrame {
+            number: 1,
+            is_wrapped: false,
+            is_map: false,
+            entry_values: None,
+            omit_defaults: false,
+            is_wrapper: false,
+            value_ty: FieldType::Any,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CollectionState {
+    is_wrapping: bool,
+    value_depth: Option<usize>,
+}
+
+#[derive(Debug)]
+struct MapState {
+    is_deterministic: bool,
+    // The entries of any maps currently being sorted
+    maps: Vec<Vec<MapEntry>>,
+}
+
+#[derive(Debug)]
+struct MapEntry {
+    start: Mark,
+    key: Range<Mark>,
+}
+
+#[derive(Debug)]
+struct TimeState {
+    // Whether text should be parsed as an RFC 3339 timestamp
+    is_timestamp: bool,
+    // Whether the next message is a timestamp or duration
+    is_message: bool,
+}
 
-    #[inline(always)]
-    fn push_if_set<T>(&mut self, wire_type: WireType, buf: &mut ProtoBufMut<T>) {
-        if self.is_set() {
-            self.push(wire_type, buf)
-        }
-    }
+#[derive(Debug)]
+struct MaskState {
+    mask: Option<FieldMask>,
+    // The nodes in the mask that match the fields being encoded
+    // A field can be matched by more than one path, so each has a range of nodes
+    nodes: Vec<usize>,
+    // The start of the nodes of each field being encoded
+    // If a field is selected in full then it doesn't have any nodes
+    fields: Vec<Option<usize>>,
+    // The depth of fields within a field that isn't selected by the mask
+    skip_depth: usize,
+}
 
+impl MaskState {
     #[inline(always)]
-    fn push<T>(&mut self, wire_type: WireType, buf: &mut ProtoBufMut<T>) {
-        buf.push_field(self.number, wire_type);
-        self.number = 0;
+    fn is_skipping(&self) -> bool {
+        self.skip_depth != 0
     }
 }
 
 #[derive(Debug)]
-struct LenState {
-    is_packed: bool,
-    is_prefixed: bool,
+struct NumberState {
+    encoding: NumberEncoding,
+    is_number: bool,
+    is_buffering: bool,
+    text: String,
 }
 
 #[derive(Debug)]
-struct OneOfState {
-    is_internally_tagged: bool,
+struct StrictState {
+    is_strict: bool,
+    fields: Vec<(usize, u64)>,
+    in_map_key: bool,
+    map_value_depth: Option<usize>,
 }
 
 /**
@@ -218,7 +1399,7 @@ The re-usable internals of a [`ProtoBufStream`] that can optimize a later encodi
 This type can be produced through [`ProtoBufStream::freeze_reuse`].
 */
 #[derive(Clone, Default)]
-pub struct ProtoBufStreamReusable(ProtoBufMutReusable<u64>);
+pub struct ProtoBufStreamReusable(ProtoBufMutReusable<LenFrame>);
 
 impl ProtoBufStreamReusable {
     /**
@@ -253,6 +1434,12 @@ impl<'sval> sval::Stream<'sval> for ProtoBufStream {
     }
 
     fn bool(&mut self, value: bool) -> sval::Result {
+        self.scalar_begin(None);
+
+        if self.is_omitted(!value) {
+            return Ok(());
+        }
+
         self.field.push_if_set(WireType::VarInt, &mut self.buf);
         self.buf.push_varint_bool(value);
 
@@ -260,14 +1447,41 @@ impl<'sval> sval::Stream<'sval> for ProtoBufStream {
     }
 
     fn text_begin(&mut self, num_bytes: Option<usize>) -> sval::Result {
+        // Numbers and timestamps are buffered so they can be parsed
+        if self.number.is_number || self.time.is_timestamp {
+            self.number.is_buffering = true;
+
+            return Ok(());
+        }
+
         self.non_root_binary_begin(num_bytes)
     }
 
     fn text_fragment_computed(&mut self, fragment: &str) -> sval::Result {
+        if self.number.is_buffering {
+            self.number.text.push_str(fragment);
+
+            return Ok(());
+        }
+
         self.binary_fragment_computed(fragment.as_bytes())
     }
 
     fn text_end(&mut self) -> sval::Result {
+        if self.number.is_buffering {
+            self.number.is_buffering = false;
+
+            if mem::take(&mut self.time.is_timestamp) {
+                self.time.is_message = false;
+
+                return self.timestamp_end();
+            }
+
+            self.number.is_number = false;
+
+            return self.number_end();
+        }
+
         self.non_root_binary_end()
     }
 
@@ -284,6 +1498,10 @@ impl<'sval> sval::Stream<'sval> for ProtoBufStream {
     }
 
     fn binary_fragment_computed(&mut self, fragment: &[u8]) -> sval::Result {
+        if self.len.is_omitted {
+            return Ok(());
+        }
+
         self.buf.push(fragment);
 
         Ok(())
@@ -298,7 +1516,15 @@ impl<'sval> sval::Stream<'sval> for ProtoBufStream {
     }
 
     fn u32(&mut self, value: u32) -> sval::Result {
-        match self.field.ty {
+        let ty = self.integer_ty(self.integers.u32, FieldType::I32);
+
+        self.scalar_begin((ty == FieldType::I32).then_some(4));
+
+        if self.is_omitted(value == 0) {
+            return Ok(());
+        }
+
+        match ty {
             FieldType::I32 => {
                 self.field.push_if_set(WireType::I32, &mut self.buf);
                 self.buf.push_i32_fixed32(value);
@@ -315,7 +1541,15 @@ impl<'sval> sval::Stream<'sval> for ProtoBufStream {
     }
 
     fn u64(&mut self, value: u64) -> sval::Result {
-        match self.field.ty {
+        let ty = self.integer_ty(self.integers.u64, FieldType::I64);
+
+        self.scalar_begin((ty == FieldType::I64).then_some(8));
+
+        if self.is_omitted(value == 0) {
+            return Ok(());
+        }
+
+        match ty {
             FieldType::I64 => {
                 self.field.push_if_set(WireType::I64, &mut self.buf);
                 self.buf.push_i64_fixed64(value);
@@ -332,15 +1566,33 @@ impl<'sval> sval::Stream<'sval> for ProtoBufStream {
     }
 
     fn u128(&mut self, value: u128) -> sval::Result {
-        let bytes = value.to_le_bytes();
+        if self.is_omitted(value == 0) {
+            return Ok(());
+        }
+
+        match self.int128 {
+            Int128Encoding::Bytes => {
+                let bytes = value.to_le_bytes();
 
-        self.binary_begin(Some(bytes.len()))?;
-        self.binary_fragment_computed(&bytes)?;
-        self.binary_end()
+                self.binary_begin(Some(bytes.len()))?;
+                self.binary_fragment_computed(&bytes)?;
+                self.binary_end()
+            }
+            Int128Encoding::HiLo => self.int128_hi_lo(value),
+            Int128Encoding::Decimal => self.int128_decimal(false, value),
+        }
     }
 
     fn i32(&mut self, value: i32) -> sval::Result {
-        match self.field.ty {
+        let ty = self.integer_ty(self.integers.i32, FieldType::I32);
+
+        self.scalar_begin((ty == FieldType::I32).then_some(4));
+
+        if self.is_omitted(value == 0) {
+            return Ok(());
+        }
+
+        match ty {
             FieldType::I32 => {
                 self.field.push_if_set(WireType::I32, &mut self.buf);
                 self.buf.push_i32_sfixed32(value);
@@ -363,7 +1615,15 @@ impl<'sval> sval::Stream<'sval> for ProtoBufStream {
     }
 
     fn i64(&mut self, value: i64) -> sval::Result {
-        match self.field.ty {
+        let ty = self.integer_ty(self.integers.i64, FieldType::I64);
+
+        self.scalar_begin((ty == FieldType::I64).then_some(8));
+
+        if self.is_omitted(value == 0) {
+            return Ok(());
+        }
+
+        match ty {
             FieldType::I64 => {
                 self.field.push_if_set(WireType::I64, &mut self.buf);
                 self.buf.push_i64_sfixed64(value);
@@ -386,14 +1646,31 @@ impl<'sval> sval::Stream<'sval> for ProtoBufStream {
     }
 
     fn i128(&mut self, value: i128) -> sval::Result {
-        let bytes = value.to_le_bytes();
+        if self.is_omitted(value == 0) {
+            return Ok(());
+        }
 
-        self.binary_begin(Some(bytes.len()))?;
-        self.binary_fragment_computed(&bytes)?;
-        self.binary_end()
+        match self.int128 {
+            Int128Encoding::Bytes => {
+                let bytes = value.to_le_bytes();
+
+                self.binary_begin(Some(bytes.len()))?;
+                self.binary_fragment_computed(&bytes)?;
+                self.binary_end()
+            }
+            Int128Encoding::HiLo => self.int128_hi_lo(value as u128),
+            Int128Encoding::Decimal => self.int128_decimal(value < 0, value.unsigned_abs()),
+        }
     }
 
     fn f32(&mut self, value: f32) -> sval::Result {
+        self.scalar_begin(Some(4));
+
+        // Only positive zero is the default
+        if self.is_omitted(value.to_bits() == 0) {
+            return Ok(());
+        }
+
         self.field.push_if_set(WireType::I32, &mut self.buf);
         self.buf.push_i32_float(value);
 
@@ -401,6 +1678,13 @@ impl<'sval> sval::Stream<'sval> for ProtoBufStream {
     }
 
     fn f64(&mut self, value: f64) -> sval::Result {
+        self.scalar_begin(Some(8));
+
+        // Only positive zero is the default
+        if self.is_omitted(value.to_bits() == 0) {
+            return Ok(());
+        }
+
         self.field.push_if_set(WireType::I64, &mut self.buf);
         self.buf.push_i64_double(value);
 
@@ -408,99 +1692,235 @@ impl<'sval> sval::Stream<'sval> for ProtoBufStream {
     }
 
     fn map_begin(&mut self, num_entries: Option<usize>) -> sval::Result {
+        if self.mask.is_skipping() {
+            return Ok(());
+        }
+
+        self.collection_begin(true)?;
+
+        self.field.is_raw = false;
+
         if let Some(num_entries) = num_entries {
             self.buf.reserve(num_entries * 2);
         }
 
-        *self.buf.state_mut() = self.field.number;
+        self.buf.state_mut().number = self.field.number;
+
+        // Numeric encodings from tags on the map apply to its values
+        self.buf.state_mut().value_ty = match self.field.ty {
+            ty @ (FieldType::Signed | FieldType::I32 | FieldType::I64) => ty,
+            _ => FieldType::Any,
+        };
 
         self.field.number = 0;
         self.field.ty = FieldType::Any;
 
+        self.map_entries_begin();
+        self.mask_map_begin();
+
         Ok(())
     }
 
     fn map_key_begin(&mut self) -> sval::Result {
-        self.field_begin();
+        if self.mask.is_skipping() {
+            return Ok(());
+        }
 
-        self.field.number = *self.buf.state_mut();
-        self.field.push(WireType::Len, &mut self.buf);
-        self.field.number = 1;
+        self.field_begin();
 
-        self.buf.begin_len(1);
+        self.map_entry_begin(LenFrame::new());
+        self.map_entry_key_begin();
 
         Ok(())
     }
 
     fn map_key_end(&mut self) -> sval::Result {
+        if self.mask.is_skipping() {
+            return Ok(());
+        }
+
+        self.strict.in_map_key = false;
+
+        if self.map.is_deterministic {
+            let end = self.buf.mark();
+
+            if let Some(entry) = self
+                .map
+                .maps
+                .last_mut()
+                .and_then(|entries| entries.last_mut())
+            {
+                entry.key.end = end;
+            }
+        }
+
         Ok(())
     }
 
     fn map_value_begin(&mut self) -> sval::Result {
+        if self.mask.is_skipping() {
+            return Ok(());
+        }
+
         self.field.number = 2;
+        self.field.ty = self.buf.state_mut().value_ty;
+        self.field.is_implicit = false;
+
+        self.strict.map_value_depth = Some(self.buf.depth());
+        self.collection.value_depth = Some(self.buf.depth());
 
         Ok(())
     }
 
     fn map_value_end(&mut self) -> sval::Result {
+        if self.mask.is_skipping() {
+            return Ok(());
+        }
+
+        self.strict.map_value_depth = None;
+        self.collection.value_depth = None;
+
         self.buf.end_len();
 
         Ok(())
     }
 
     fn map_end(&mut self) -> sval::Result {
+        if self.mask.is_skipping() {
+            return Ok(());
+        }
+
         self.len.is_prefixed = false;
 
+        self.map_entries_end();
+        self.mask_map_end();
+
+        self.collection_end();
+
         Ok(())
     }
 
     fn seq_begin(&mut self, num_entries: Option<usize>) -> sval::Result {
+        if self.mask.is_skipping() {
+            return Ok(());
+        }
+
+        let is_map = mem::replace(&mut self.len.is_map, false);
+
+        self.collection_begin(is_map)?;
+
+        self.field.is_raw = false;
+
         if let Some(num_entries) = num_entries {
             self.buf.reserve(num_entries);
         }
 
-        if self.len.is_packed {
-            self.field.push_if_set(WireType::Len, &mut self.buf);
-            self.buf.begin_len(1);
+        let is_expanded = mem::replace(&mut self.len.is_expanded, false);
+
+        self.buf.state_mut().number = self.field.number;
+        self.field.number = 0;
+
+        self.len.num_entries = num_entries;
+
+        if is_map {
+            // Values in the sequence are map entries
+            self.buf.state_mut().is_map = true;
+            self.map_entries_begin();
+            self.mask_map_begin();
+
+            self.len.is_packed = false;
+            self.field.ty = FieldType::Any;
 
             Ok(())
-        } else {
-            *self.buf.state_mut() = self.field.number;
+        } else if self.len.is_packed {
+            self.len.in_packed = true;
 
-            self.field.number = 0;
+            // The length prefix is written when the first value is seen
+            self.len.is_pending_prefix = true;
+
+            Ok(())
+        } else {
             self.field.ty = FieldType::Any;
 
+            // Whether to pack the sequence is decided by its first value
+            self.len.is_pending_packed = self.len.auto_packed && !is_expanded;
+
             Ok(())
         }
     }
 
     fn seq_value_begin(&mut self) -> sval::Result {
+        if self.mask.is_skipping() {
+            return Ok(());
+        }
+
+        self.field_begin();
+
         if self.len.is_packed {
             Ok(())
         } else {
-            self.field_begin();
-            self.field.number = *self.buf.state_mut();
+            self.field.number = self.buf.state_mut().number;
+            self.field.is_implicit = false;
+
+            self.collection.value_depth = Some(self.buf.depth());
+            self.len.is_pending_entry = self.buf.state_mut().is_map;
 
             Ok(())
         }
     }
 
     fn seq_value_end(&mut self) -> sval::Result {
+        if self.mask.is_skipping() {
+            return Ok(());
+        }
+
+        self.collection.value_depth = None;
+
+        // Values in a sequence tagged as a map must be map entries
+        if mem::take(&mut self.len.is_pending_entry) {
+            return Err(self.fail(ErrorKind::Unsupported));
+        }
+
         Ok(())
     }
 
     fn seq_end(&mut self) -> sval::Result {
+        if self.mask.is_skipping() {
+            return Ok(());
+        }
+
         self.len.is_prefixed = false;
+        self.len.is_pending_packed = false;
+
+        if mem::replace(&mut self.buf.state_mut().is_map, false) {
+            self.map_entries_end();
+            self.mask_map_end();
+        }
 
         if self.len.is_packed {
             self.len.is_packed = false;
+            self.len.in_packed = false;
+
+            if self.len.is_pending_prefix {
+                // The sequence was empty
+                self.len.is_pending_prefix = false;
+
+                self.field.number = self.buf.state_mut().number;
+
+                self.field.push_if_set(WireType::Len, &mut self.buf);
+                self.buf.push_len_varint_uint64(0);
+            } else if let Some((start, len)) = self.len.prefixed_len.take() {
+                // If the sequence didn't contain as many values as it said it would
+                // then the length is fixed up from what was actually written
+                self.buf.rewrite_len(start, len as u64);
+            } else {
+                self.buf.end_len();
+            }
+        }
 
-            self.buf.end_len();
+        self.collection_end();
 
-            Ok(())
-        } else {
-            Ok(())
-        }
+        Ok(())
     }
 
     fn enum_begin(
@@ -509,8 +1929,12 @@ impl<'sval> sval::Stream<'sval> for ProtoBufStream {
         _: Option<&Label>,
         index: Option<&Index>,
     ) -> sval::Result {
+        if self.mask.is_skipping() {
+            return Ok(());
+        }
+
         self.root_begin();
-        self.internally_tagged_begin(index);
+        self.internally_tagged_begin(index)?;
 
         self.one_of.is_internally_tagged = true;
 
@@ -523,6 +1947,10 @@ impl<'sval> sval::Stream<'sval> for ProtoBufStream {
         _: Option<&Label>,
         index: Option<&Index>,
     ) -> sval::Result {
+        if self.mask.is_skipping() {
+            return Ok(());
+        }
+
         if self.one_of.is_internally_tagged {
             self.one_of.is_internally_tagged = false;
 
@@ -540,7 +1968,11 @@ impl<'sval> sval::Stream<'sval> for ProtoBufStream {
         _: Option<&Label>,
         index: Option<&Index>,
     ) -> sval::Result {
-        self.internally_tagged_begin(index);
+        if self.mask.is_skipping() {
+            return Ok(());
+        }
+
+        self.internally_tagged_begin(index)?;
 
         match tag {
             Some(&tags::PROTOBUF_I32) => {
@@ -558,11 +1990,64 @@ impl<'sval> sval::Stream<'sval> for ProtoBufStream {
 
                 Ok(())
             }
+            Some(&tags::PROTOBUF_LEN_EXPANDED) => {
+                self.len.is_expanded = true;
+
+                Ok(())
+            }
+            Some(&tags::PROTOBUF_MAP) => {
+                self.len.is_map = true;
+
+                Ok(())
+            }
+            Some(&tags::PROTOBUF_TIMESTAMP) => {
+                self.time.is_timestamp = true;
+                self.time.is_message = true;
+
+                Ok(())
+            }
+            Some(&tags::PROTOBUF_DURATION) => {
+                self.time.is_message = true;
+
+                Ok(())
+            }
+            Some(&tags::PROTOBUF_WRAPPER) => {
+                // Messages can't be packed
+                if self.len.in_packed {
+                    return Err(self.fail(ErrorKind::Unsupported));
+                }
+
+                // At the root, the value is already written in field `1` of a message
+                if self.field.ty != FieldType::Root {
+                    self.field.is_wrapper = true;
+
+                    // Wrapped integers are always plain varints
+                    self.field.ty = FieldType::VarInt;
+                }
+
+                Ok(())
+            }
+            Some(&sval::tags::NUMBER) => {
+                self.number.is_number = self.number.encoding != NumberEncoding::Text;
+
+                Ok(())
+            }
             Some(&tags::PROTOBUF_VARINT_SIGNED) => {
                 self.field.ty = FieldType::Signed;
 
                 Ok(())
             }
+            Some(&tags::PROTOBUF_VARINT) => {
+                self.field.ty = FieldType::VarInt;
+
+                Ok(())
+            }
+            Some(&sval::tags::RUST_OPTION_SOME) => {
+                // Optional values are explicitly present
+                self.field.is_implicit = false;
+
+                Ok(())
+            }
             Some(&tags::PROTOBUF_PRE_ENCODED) => {
                 // Roundtrip `ProtoBuf` values when they appear at the root
                 if self.field.ty == FieldType::Root {
@@ -577,18 +2062,37 @@ impl<'sval> sval::Stream<'sval> for ProtoBufStream {
 
     fn tagged_end(
         &mut self,
-        _: Option<&Tag>,
+        tag: Option<&Tag>,
         _: Option<&Label>,
         index: Option<&Index>,
     ) -> sval::Result {
+        if self.mask.is_skipping() {
+            return Ok(());
+        }
+
         self.internally_tagged_end(index);
 
+        if tag == Some(&tags::PROTOBUF_WRAPPER) {
+            self.field.is_wrapper = false;
+
+            if self.buf.state_mut().is_wrapper {
+                self.buf.end_len();
+            }
+        }
+
         self.field.ty = FieldType::Any;
+        self.number.is_number = false;
+        self.time.is_timestamp = false;
+        self.time.is_message = false;
 
         Ok(())
     }
 
     fn tag(&mut self, tag: Option<&Tag>, _: Option<&Label>, index: Option<&Index>) -> sval::Result {
+        if self.mask.is_skipping() {
+            return Ok(());
+        }
+
         self.one_of.is_internally_tagged = false;
 
         match tag {
@@ -596,7 +2100,16 @@ impl<'sval> sval::Stream<'sval> for ProtoBufStream {
             _ => {
                 // Protobuf enums are i32 values
                 if let Some(index) = index.and_then(|index| index.to_i32()) {
-                    self.i32(index)
+                    // Enums are always plain varints, regardless of the default for integers
+                    let ty = self.field.ty;
+                    if let FieldType::Any | FieldType::Root = ty {
+                        self.field.ty = FieldType::VarInt;
+                    }
+
+                    let r = self.i32(index);
+                    self.field.ty = ty;
+
+                    r
                 } else {
                     self.null()
                 }
@@ -611,29 +2124,46 @@ impl<'sval> sval::Stream<'sval> for ProtoBufStream {
         index: Option<&Index>,
         num_entries: Option<usize>,
     ) -> sval::Result {
+        if self.mask.is_skipping() {
+            return Ok(());
+        }
+
+        self.packed_begin()?;
+
+        // Only scalars can be wrapped
+        if self.field.is_wrapper {
+            return Err(self.fail(ErrorKind::Unsupported));
+        }
+
         if let Some(num_entries) = num_entries {
             self.buf.reserve(num_entries);
         }
 
         self.root_begin();
-        self.internally_tagged_begin(index);
+        self.internally_tagged_begin(index)?;
 
         if self.field.is_set() {
-            self.field.push(WireType::Len, &mut self.buf);
-            self.buf.begin_len(1);
+            // Tuples and records in a sequence tagged as a map are map entries
+            if self.buf.state_mut().is_map && self.collection.value_depth == Some(self.buf.depth())
+            {
+                self.collection.value_depth = None;
+                self.len.is_pending_entry = false;
+
+                self.map_entry_begin(LenFrame {
+                    entry_values: Some(0),
+                    ..LenFrame::new()
+                });
+            } else {
+                self.message_begin()?;
+            }
         }
 
-        Ok(())
-    }
-
-    fn tuple_value_begin(&mut self, _: Option<&Tag>, index: &Index) -> sval::Result {
-        self.field_begin();
-        self.field.set(index);
-
-        Ok(())
-    }
+        // Timestamps and durations are proto3 messages, so their fields have implicit presence
+        if mem::take(&mut self.time.is_message) {
+            self.time.is_timestamp = false;
+            self.buf.state_mut().omit_defaults = true;
+        }
 
-    fn tuple_value_end(&mut self, _: Option<&Tag>, _: &Index) -> sval::Result {
         Ok(())
     }
 
@@ -643,8 +2173,19 @@ impl<'sval> sval::Stream<'sval> for ProtoBufStream {
         _: Option<&Label>,
         index: Option<&Index>,
     ) -> sval::Result {
+        if self.mask.is_skipping() {
+            return Ok(());
+        }
+
         self.internally_tagged_end(index);
 
+        match self.buf.state_mut().entry_values {
+            Some(2) => return self.map_value_end(),
+            // Map entries must have both a key and a value
+            Some(_) => return Err(self.fail(ErrorKind::Unsupported)),
+            None => (),
+        }
+
         // The root message isn't wrapped
         if self.buf.depth() != 0 {
             self.buf.end_len();
@@ -653,6 +2194,14 @@ impl<'sval> sval::Stream<'sval> for ProtoBufStream {
         Ok(())
     }
 
+    fn tuple_value_begin(&mut self, _: Option<&Tag>, index: &Index) -> sval::Result {
+        self.field_value_begin(None, index)
+    }
+
+    fn tuple_value_end(&mut self, _: Option<&Tag>, _: &Index) -> sval::Result {
+        self.field_value_end()
+    }
+
     fn record_tuple_begin(
         &mut self,
         tag: Option<&Tag>,
@@ -665,24 +2214,15 @@ impl<'sval> sval::Stream<'sval> for ProtoBufStream {
 
     fn record_tuple_value_begin(
         &mut self,
-        tag: Option<&Tag>,
+        _: Option<&Tag>,
         label: &Label,
         index: &Index,
     ) -> sval::Result {
-        let _ = label;
-
-        self.tuple_value_begin(tag, index)
+        self.field_value_begin(Some(label), index)
     }
 
-    fn record_tuple_value_end(
-        &mut self,
-        tag: Option<&Tag>,
-        label: &Label,
-        index: &Index,
-    ) -> sval::Result {
-        let _ = label;
-
-        self.tuple_value_end(tag, index)
+    fn record_tuple_value_end(&mut self, _: Option<&Tag>, _: &Label, _: &Index) -> sval::Result {
+        self.field_value_end()
     }
 
     fn record_tuple_end(
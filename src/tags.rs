@@ -19,6 +19,29 @@ This tag is only valid for sequences of numeric values.
 */
 pub const PROTOBUF_LEN_PACKED: sval::Tag = sval::Tag::new("PROTOBUF_LEN_PACKED");
 
+/**
+A tag for sequences that should not be packed.
+
+This tag can be used to opt-out of automatic packing for legacy consumers.
+*/
+pub const PROTOBUF_LEN_EXPANDED: sval::Tag = sval::Tag::new("PROTOBUF_LEN_EXPANDED");
+
+/**
+A tag for sequences that should be encoded as maps.
+
+This tag is only valid for sequences of 2-tuples or records with 2 fields.
+The first value of each is encoded as the map key and the second as the map value,
+regardless of their indexes.
+*/
+pub const PROTOBUF_MAP: sval::Tag = sval::Tag::new("PROTOBUF_MAP");
+
+/**
+A tag for numeric values that should be encoded as plain varints.
+
+This tag can be used to override any default integer encoding configured on the stream.
+*/
+pub const PROTOBUF_VARINT: sval::Tag = sval::Tag::new("PROTOBUF_VARINT");
+
 /**
 A tag for numeric values that should be zigzag encoded.
 
@@ -27,6 +50,33 @@ for negative values.
 */
 pub const PROTOBUF_VARINT_SIGNED: sval::Tag = sval::Tag::new("PROTOBUF_VARINT_SIGNED");
 
+/**
+A tag for values that should be encoded as a `google.protobuf.Timestamp`.
+
+This tag is valid for records with the seconds and nanoseconds of the timestamp as fields `1` and `2`,
+and for text containing an RFC 3339 timestamp, like `2024-02-29T13:45:00.5+10:00`.
+See [`crate::well_known::Timestamp`].
+*/
+pub const PROTOBUF_TIMESTAMP: sval::Tag = sval::Tag::new("PROTOBUF_TIMESTAMP");
+
+/**
+A tag for values that should be encoded as a `google.protobuf.Duration`.
+
+This tag is valid for records with the seconds and nanoseconds of the duration as fields `1` and `2`.
+See [`crate::well_known::Duration`].
+*/
+pub const PROTOBUF_DURATION: sval::Tag = sval::Tag::new("PROTOBUF_DURATION");
+
+/**
+A tag for scalar values that should be encoded as a well-known wrapper message, like `google.protobuf.Int32Value`.
+
+The value is written in field `1` of a message, even if it's the default for its type.
+This tag is typically used on `Option<T>`s, where `None` omits the field entirely.
+Wrapped integers are always encoded as plain varints. `Option<T>`s decoded through
+[`crate::decode::FromProtobuf`] with this tag read their value from the wrapper message.
+*/
+pub const PROTOBUF_WRAPPER: sval::Tag = sval::Tag::new("PROTOBUF_WRAPPER");
+
 /**
 A tag for round-tripping pre-encoded protobuf messages.
 */
@@ -0,0 +1,13 @@
+/*!
+Support for protobuf's [well-known types](https://protobuf.dev/reference/protobuf/google.protobuf/).
+
+These types encode the same way as their counterparts in the `google.protobuf` package,
+so they can be used in messages that are shared with other protobuf implementations.
+*/
+
+mod any;
+mod dynamic;
+mod field_mask;
+mod time;
+
+pub use self::{any::*, dynamic::*, field_mask::*, time::*};
@@ -0,0 +1,274 @@
+use crate::{
+    buf::ProtoBuf,
+    decode::FromProtobuf,
+    raw::{Fields, Payload},
+    Error, ErrorKind,
+};
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/**
+A value along with the URL of its type, encoded as a `google.protobuf.Any`.
+
+The value is encoded as a message in its own right, and embedded as bytes:
+
+```text
+message Any {
+    string type_url = 1;
+    bytes value = 2;
+}
+```
+
+The value is streamed as a nested message using the same options as the rest of the stream,
+so it should be a record or tuple. Already encoded [`ProtoBuf`]s are embedded as-is.
+
+When decoded through [`FromProtobuf`], the value is kept encoded as a [`ProtoBuf`].
+It can be decoded later with [`Any::unpack`] or an [`AnyRegistry`].
+*/
+#[derive(Debug, Clone)]
+pub struct Any<U, V> {
+    type_url: U,
+    value: V,
+}
+
+impl<U: AsRef<str>, V> Any<U, V> {
+    /**
+    Create a new `Any` from a type URL, like `type.googleapis.com/my.package.MyMessage`, and a value.
+    */
+    pub fn new(type_url: U, value: V) -> Self {
+        Any { type_url, value }
+    }
+
+    /**
+    The URL of the value's type.
+    */
+    pub fn type_url(&self) -> &str {
+        self.type_url.as_ref()
+    }
+
+    /**
+    The name of the value's type, which is the last segment of its URL.
+    */
+    pub fn type_name(&self) -> &str {
+        type_name(self.type_url())
+    }
+
+    /**
+    The value.
+    */
+    pub fn value(&self) -> &V {
+        &self.value
+    }
+}
+
+impl<U: AsRef<str>> Any<U, ProtoBuf> {
+    /**
+    Decode the value as a `T`.
+
+    This method doesn't check the value's type URL.
+    */
+    pub fn unpack<T: FromProtobuf>(&self) -> Result<T, Error> {
+        T::from_protobuf(&self.value.to_vec())
+    }
+}
+
+impl<U: AsRef<str>, V: sval::Value> sval::Value for Any<U, V> {
+    fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(&'sval self, stream: &mut S) -> sval::Result {
+        let label = sval::Label::new("Any");
+        let type_url_label = sval::Label::new("type_url");
+        let type_url_index = sval::Index::new(1);
+        let value_label = sval::Label::new("value");
+        let value_index = sval::Index::new(2);
+
+        stream.record_tuple_begin(None, Some(&label), None, Some(2))?;
+
+        stream.record_tuple_value_begin(None, &type_url_label, &type_url_index)?;
+        stream.value(self.type_url.as_ref())?;
+        stream.record_tuple_value_end(None, &type_url_label, &type_url_index)?;
+
+        // Messages are encoded the same way as bytes containing them
+        stream.record_tuple_value_begin(None, &value_label, &value_index)?;
+        stream.value(&self.value)?;
+        stream.record_tuple_value_end(None, &value_label, &value_index)?;
+
+        stream.record_tuple_end(None, Some(&label), None)
+    }
+}
+
+impl FromProtobuf for Any<String, ProtoBuf> {
+    fn empty() -> Self {
+        Any {
+            type_url: String::new(),
+            value: ProtoBuf::pre_encoded(Vec::new()),
+        }
+    }
+
+    fn merge_field(&mut self, payload: Payload<&[u8]>, _: Option<&sval::Tag>) -> Result<(), Error> {
+        match payload {
+            Payload::Len(buf) => self.merge_message(buf),
+            _ => Err(Error::new(ErrorKind::InvalidWireType)),
+        }
+    }
+
+    fn merge_message(&mut self, buf: &[u8]) -> Result<(), Error> {
+        for field in Fields::new(buf) {
+            let field = field?;
+
+            match (field.number(), field.into_payload()) {
+                (1, payload) => self.type_url.merge_field(payload, None)?,
+                (2, Payload::Len(value)) => self.value = ProtoBuf::pre_encoded(value.to_vec()),
+                (2, _) => return Err(Error::new(ErrorKind::InvalidWireType)),
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/**
+A set of decoders for the values of [`Any`]s, keyed by their type.
+
+Each decoder produces a `T`, which is typically an enum over the types that can be unpacked.
+Types are matched by their name, which is the last segment of their type URL, so
+`type.googleapis.com/my.package.MyMessage` and `example.com/types/my.package.MyMessage`
+are the same type.
+*/
+#[derive(Debug)]
+pub struct AnyRegistry<T> {
+    decoders: BTreeMap<String, Decoder<T>>,
+}
+
+type Decoder<T> = fn(&[u8]) -> Result<T, Error>;
+
+impl<T> Default for AnyRegistry<T> {
+    fn default() -> Self {
+        AnyRegistry {
+            decoders: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T> AnyRegistry<T> {
+    /**
+    Create a new, empty registry.
+    */
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+    Decode values with the given type URL as a `D`, converting them into a `T`.
+    */
+    pub fn with_type<D: FromProtobuf + Into<T>>(self, type_url: &str) -> Self {
+        self.with_decoder(type_url, |buf| D::from_protobuf(buf).map(Into::into))
+    }
+
+    /**
+    Decode values with the given type URL using a function.
+    */
+    pub fn with_decoder(mut self, type_url: &str, decoder: fn(&[u8]) -> Result<T, Error>) -> Self {
+        self.decoders
+            .insert(type_name(type_url).to_string(), decoder);
+        self
+    }
+
+    /**
+    Decode the value of an [`Any`] using the decoder registered for its type.
+
+    This method returns `None` if there's no decoder for the type.
+    */
+    pub fn unpack(&self, any: &Any<impl AsRef<str>, ProtoBuf>) -> Result<Option<T>, Error> {
+        match self.decoders.get(any.type_name()) {
+            Some(decoder) => decoder(&any.value.to_vec()).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+fn type_name(type_url: &str) -> &str {
+    type_url.rsplit('/').next().unwrap_or(type_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::stream_to_protobuf;
+
+    #[test]
+    fn type_name_is_last_segment() {
+        assert_eq!(
+            "my.package.MyMessage",
+            Any::new("type.googleapis.com/my.package.MyMessage", ()).type_name()
+        );
+        assert_eq!(
+            "my.package.MyMessage",
+            Any::new("my.package.MyMessage", ()).type_name()
+        );
+    }
+
+    #[test]
+    fn any_pre_encoded() {
+        let value = stream_to_protobuf((42,));
+
+        let encoded = stream_to_protobuf(Any::new("a/b", value.clone()));
+        let from_value = stream_to_protobuf(Any::new("a/b", (42,)));
+
+        assert_eq!(encoded.to_vec(), from_value.to_vec());
+
+        let decoded = Any::<String, ProtoBuf>::from_protobuf(&encoded.to_vec()).unwrap();
+
+        assert_eq!("a/b", decoded.type_url());
+        assert_eq!(value.to_vec(), decoded.value().to_vec());
+        assert_eq!(42, decoded.unpack::<i32>().unwrap());
+    }
+
+    #[test]
+    fn registry_unpack() {
+        #[derive(Debug, PartialEq)]
+        enum Payload {
+            Number(i32),
+            Text(String),
+        }
+
+        impl From<i32> for Payload {
+            fn from(value: i32) -> Self {
+                Payload::Number(value)
+            }
+        }
+
+        impl From<String> for Payload {
+            fn from(value: String) -> Self {
+                Payload::Text(value)
+            }
+        }
+
+        let registry = AnyRegistry::<Payload>::new()
+            .with_type::<i32>("type.googleapis.com/google.protobuf.Int32Value")
+            .with_type::<String>("type.googleapis.com/google.protobuf.StringValue");
+
+        let number = Any::new(
+            "example.com/google.protobuf.Int32Value",
+            stream_to_protobuf(42),
+        );
+        let text = Any::new(
+            "type.googleapis.com/google.protobuf.StringValue",
+            stream_to_protobuf("a"),
+        );
+        let unknown = Any::new(
+            "type.googleapis.com/google.protobuf.BoolValue",
+            stream_to_protobuf(true),
+        );
+
+        assert_eq!(Some(Payload::Number(42)), registry.unpack(&number).unwrap());
+        assert_eq!(
+            Some(Payload::Text("a".to_string())),
+            registry.unpack(&text).unwrap()
+        );
+        assert_eq!(None, registry.unpack(&unknown).unwrap());
+    }
+}
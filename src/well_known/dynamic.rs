@@ -0,0 +1,320 @@
+use alloc::string::{String, ToString};
+use sval::{Index, Label, Tag};
+
+const VALUE: Label<'static> = Label::new("Value");
+
+const NULL_VALUE: (Label<'static>, Index) = (Label::new("null_value"), Index::new(1));
+const NUMBER_VALUE: (Label<'static>, Index) = (Label::new("number_value"), Index::new(2));
+const STRING_VALUE: (Label<'static>, Index) = (Label::new("string_value"), Index::new(3));
+const BOOL_VALUE: (Label<'static>, Index) = (Label::new("bool_value"), Index::new(4));
+const STRUCT_VALUE: (Label<'static>, Index) = (Label::new("struct_value"), Index::new(5));
+const LIST_VALUE: (Label<'static>, Index) = (Label::new("list_value"), Index::new(6));
+
+const STRUCT_FIELDS: (Label<'static>, Index) = (Label::new("fields"), Index::new(1));
+const LIST_VALUES: (Label<'static>, Index) = (Label::new("values"), Index::new(1));
+
+/**
+A value without a schema, encoded as a `google.protobuf.Value`.
+
+Any `sval::Value` can be encoded, with its contents mapped onto the members of the `Value` oneof:
+
+```text
+message Value {
+    oneof kind {
+        NullValue null_value = 1;
+        double number_value = 2;
+        string string_value = 3;
+        bool bool_value = 4;
+        Struct struct_value = 5;
+        ListValue list_value = 6;
+    }
+}
+
+message Struct {
+    map<string, Value> fields = 1;
+}
+
+message ListValue {
+    repeated Value values = 1;
+}
+```
+
+- Nulls, including `None`s and `()`, are `null_value`s.
+- Numbers are `number_value`s. Integers that can't be exactly represented as a `double` lose precision.
+- Text is a `string_value`. Enum variants without any data are `string_value`s of their label.
+- Booleans are `bool_value`s.
+- Records and maps are `Struct`s. Keys that are numbers, booleans, or null are converted into text. Any other keys fail to encode.
+- Sequences, tuples, and binary are `ListValue`s.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct DynamicValue<V>(V);
+
+impl<V> DynamicValue<V> {
+    /**
+    Wrap a value so it's encoded as a `google.protobuf.Value`.
+    */
+    pub fn new(value: V) -> Self {
+        DynamicValue(value)
+    }
+
+    /**
+    Get the wrapped value.
+    */
+    pub fn into_inner(self) -> V {
+        self.0
+    }
+}
+
+impl<V: sval::Value> sval::Value for DynamicValue<V> {
+    fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(&'sval self, stream: &mut S) -> sval::Result {
+        self.0.stream(&mut DynamicStream {
+            stream,
+            in_key: false,
+            is_number: false,
+            number: None,
+        })
+    }
+}
+
+/**
+A stream that maps values onto the shape of a `google.protobuf.Value`.
+
+Most of the data model is handled by the default implementations on `sval::Stream`,
+which stream records as maps, tuples as sequences, and standalone tags as text or null.
+*/
+struct DynamicStream<'a, S: ?Sized> {
+    stream: &'a mut S,
+    // Whether the current value is a map key, which is written as plain text
+    in_key: bool,
+    // Whether the current value is text containing a number
+    is_number: bool,
+    number: Option<String>,
+}
+
+impl<'a, 'sval, S: sval::Stream<'sval> + ?Sized> DynamicStream<'a, S> {
+    fn kind_begin(&mut self, (label, index): &(Label<'static>, Index)) -> sval::Result {
+        self.stream.enum_begin(None, Some(&VALUE), None)?;
+        self.stream.tagged_begin(None, Some(label), Some(index))
+    }
+
+    fn kind_end(&mut self, (label, index): &(Label<'static>, Index)) -> sval::Result {
+        self.stream.tagged_end(None, Some(label), Some(index))?;
+        self.stream.enum_end(None, Some(&VALUE), None)
+    }
+
+    fn message_begin(
+        &mut self,
+        (label, index): &(Label<'static>, Index),
+        (field_label, field_index): &(Label<'static>, Index),
+    ) -> sval::Result {
+        self.stream.enum_begin(None, Some(&VALUE), None)?;
+        self.stream
+            .record_tuple_begin(None, Some(label), Some(index), Some(1))?;
+        self.stream
+            .record_tuple_value_begin(None, field_label, field_index)
+    }
+
+    fn message_end(
+        &mut self,
+        (label, index): &(Label<'static>, Index),
+        (field_label, field_index): &(Label<'static>, Index),
+    ) -> sval::Result {
+        self.stream
+            .record_tuple_value_end(None, field_label, field_index)?;
+        self.stream
+            .record_tuple_end(None, Some(label), Some(index))?;
+        self.stream.enum_end(None, Some(&VALUE), None)
+    }
+
+    fn key(&mut self, key: &str) -> sval::Result {
+        self.stream.value_computed(key)
+    }
+
+    fn number(&mut self, value: f64) -> sval::Result {
+        self.kind_begin(&NUMBER_VALUE)?;
+        self.stream.f64(value)?;
+        self.kind_end(&NUMBER_VALUE)
+    }
+}
+
+impl<'a, 'sval, S: sval::Stream<'sval> + ?Sized> sval::Stream<'sval> for DynamicStream<'a, S> {
+    fn null(&mut self) -> sval::Result {
+        if self.in_key {
+            return self.key("null");
+        }
+
+        self.kind_begin(&NULL_VALUE)?;
+        self.stream
+            .tag(None, Some(&Label::new("NULL_VALUE")), Some(&Index::new(0)))?;
+        self.kind_end(&NULL_VALUE)
+    }
+
+    fn bool(&mut self, value: bool) -> sval::Result {
+        if self.in_key {
+            return self.key(if value { "true" } else { "false" });
+        }
+
+        self.kind_begin(&BOOL_VALUE)?;
+        self.stream.bool(value)?;
+        self.kind_end(&BOOL_VALUE)
+    }
+
+    fn i64(&mut self, value: i64) -> sval::Result {
+        if self.in_key {
+            return self.key(&value.to_string());
+        }
+
+        self.number(value as f64)
+    }
+
+    fn f64(&mut self, value: f64) -> sval::Result {
+        if self.in_key {
+            return self.key(&value.to_string());
+        }
+
+        self.number(value)
+    }
+
+    fn text_begin(&mut self, num_bytes: Option<usize>) -> sval::Result {
+        if self.in_key {
+            return self.stream.text_begin(num_bytes);
+        }
+
+        // Numbers are buffered so they can be parsed
+        if self.is_number {
+            self.number = Some(String::new());
+
+            return Ok(());
+        }
+
+        self.kind_begin(&STRING_VALUE)?;
+        self.stream.text_begin(num_bytes)
+    }
+
+    fn text_fragment(&mut self, fragment: &'sval str) -> sval::Result {
+        match self.number {
+            Some(ref mut number) => {
+                number.push_str(fragment);
+
+                Ok(())
+            }
+            None => self.stream.text_fragment(fragment),
+        }
+    }
+
+    fn text_fragment_computed(&mut self, fragment: &str) -> sval::Result {
+        match self.number {
+            Some(ref mut number) => {
+                number.push_str(fragment);
+
+                Ok(())
+            }
+            None => self.stream.text_fragment_computed(fragment),
+        }
+    }
+
+    fn text_end(&mut self) -> sval::Result {
+        if self.in_key {
+            return self.stream.text_end();
+        }
+
+        if let Some(number) = self.number.take() {
+            return match number.parse::<f64>() {
+                Ok(value) => self.number(value),
+                // Numbers that can't be parsed are kept as text
+                Err(_) => {
+                    self.kind_begin(&STRING_VALUE)?;
+                    self.stream.value_computed(&*number)?;
+                    self.kind_end(&STRING_VALUE)
+                }
+            };
+        }
+
+        self.stream.text_end()?;
+        self.kind_end(&STRING_VALUE)
+    }
+
+    fn seq_begin(&mut self, num_entries: Option<usize>) -> sval::Result {
+        // Keys can only be text
+        if self.in_key {
+            return Err(sval::Error::new());
+        }
+
+        self.message_begin(&LIST_VALUE, &LIST_VALUES)?;
+        self.stream.seq_begin(num_entries)
+    }
+
+    fn seq_value_begin(&mut self) -> sval::Result {
+        self.stream.seq_value_begin()
+    }
+
+    fn seq_value_end(&mut self) -> sval::Result {
+        self.stream.seq_value_end()
+    }
+
+    fn seq_end(&mut self) -> sval::Result {
+        self.stream.seq_end()?;
+        self.message_end(&LIST_VALUE, &LIST_VALUES)
+    }
+
+    fn map_begin(&mut self, num_entries: Option<usize>) -> sval::Result {
+        // Keys can only be text
+        if self.in_key {
+            return Err(sval::Error::new());
+        }
+
+        self.message_begin(&STRUCT_VALUE, &STRUCT_FIELDS)?;
+        self.stream.map_begin(num_entries)
+    }
+
+    fn map_key_begin(&mut self) -> sval::Result {
+        self.in_key = true;
+
+        self.stream.map_key_begin()
+    }
+
+    fn map_key_end(&mut self) -> sval::Result {
+        self.in_key = false;
+
+        self.stream.map_key_end()
+    }
+
+    fn map_value_begin(&mut self) -> sval::Result {
+        self.stream.map_value_begin()
+    }
+
+    fn map_value_end(&mut self) -> sval::Result {
+        self.stream.map_value_end()
+    }
+
+    fn map_end(&mut self) -> sval::Result {
+        self.stream.map_end()?;
+        self.message_end(&STRUCT_VALUE, &STRUCT_FIELDS)
+    }
+
+    fn tagged_begin(
+        &mut self,
+        tag: Option<&Tag>,
+        _: Option<&Label>,
+        _: Option<&Index>,
+    ) -> sval::Result {
+        if tag == Some(&sval::tags::NUMBER) {
+            self.is_number = true;
+        }
+
+        Ok(())
+    }
+
+    fn tagged_end(
+        &mut self,
+        tag: Option<&Tag>,
+        _: Option<&Label>,
+        _: Option<&Index>,
+    ) -> sval::Result {
+        if tag == Some(&sval::tags::NUMBER) {
+            self.is_number = false;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,480 @@
+use crate::{
+    buf::{ProtoBuf, ProtoBufMut, ProtoBufRef},
+    decode::{FieldKind, FromProtobuf, MessageDescriptor},
+    raw::{Fields, Payload, WireType},
+    Error, ErrorKind,
+};
+use alloc::{string::String, vec, vec::Vec};
+use core::{fmt::Write, mem, ops::Range};
+
+/**
+A set of paths to fields within a message, encoded as a `google.protobuf.FieldMask`.
+
+```text
+message FieldMask {
+    repeated string paths = 1;
+}
+```
+
+Each path is a sequence of segments separated by `.`, like `user.address.city`. A segment
+is either the label of a field, or its number, like `1.3.2`. Numbers and labels can be
+mixed within the same path. Selecting a field selects all of the fields nested within it.
+
+A mask can be used to encode only the selected fields of a value, through
+[`crate::ProtoBufStream::with_field_mask`], or to project an already encoded message,
+through [`FieldMask::project`].
+
+A mask without any paths doesn't select any fields.
+*/
+#[derive(Debug, Clone)]
+pub struct FieldMask {
+    paths: Vec<String>,
+    // The tree of selected fields, where the first node is the root message
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    segment: Segment,
+    children: Vec<usize>,
+    // Whether the field is selected in full, rather than just some of its nested fields
+    is_selected: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Root,
+    Number(u64),
+    Label(String),
+}
+
+/**
+How much of a field is selected by a [`FieldMask`].
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FieldSelection {
+    // The field isn't selected
+    Excluded,
+    // Only some of the field's nested fields are selected
+    Partial,
+    // The field is selected along with all of its nested fields
+    Full,
+}
+
+impl Default for FieldMask {
+    fn default() -> Self {
+        FieldMask {
+            paths: Vec::new(),
+            nodes: vec![Node {
+                segment: Segment::Root,
+                children: Vec::new(),
+                is_selected: false,
+            }],
+        }
+    }
+}
+
+impl FieldMask {
+    /**
+    Create a new mask that doesn't select any fields.
+    */
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+    Select the field at the given path, like `user.address.city` or `1.3.2`.
+
+    Segments that are digits are field numbers. Any other segments are field labels.
+    */
+    pub fn with_path(mut self, path: &str) -> Self {
+        let mut node = self.root();
+
+        for segment in path.split('.') {
+            let segment = match segment.parse::<u64>() {
+                Ok(number) => Segment::Number(number),
+                Err(_) => Segment::Label(segment.into()),
+            };
+
+            node = self.child_or_insert(node, segment);
+        }
+
+        self.nodes[node].is_selected = true;
+        self.paths.push(path.into());
+
+        self
+    }
+
+    /**
+    Select the field at the given path of field numbers.
+    */
+    pub fn with_number_path(self, path: &[u64]) -> Self {
+        let mut segments = String::new();
+
+        for (i, number) in path.iter().enumerate() {
+            if i > 0 {
+                segments.push('.');
+            }
+
+            let _ = write!(segments, "{}", number);
+        }
+
+        self.with_path(&segments)
+    }
+
+    /**
+    The paths selected by this mask.
+    */
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.paths.iter().map(|path| &**path)
+    }
+
+    /**
+    Project an encoded message, keeping only the selected fields.
+
+    The wire format doesn't carry field labels, so only segments that are field numbers are
+    matched. It also isn't known which length-prefixed fields are messages, so fields selected
+    by a path with further segments are kept in full. Use [`FieldMask::project_with_descriptor`]
+    to also match labels and project nested messages.
+    */
+    pub fn project<'a>(&self, buf: impl Into<ProtoBufRef<'a>>) -> Result<ProtoBuf, Error> {
+        let mut projected = ProtoBufMut::new(());
+
+        self.project_message(
+            &mut vec![self.root()],
+            0..1,
+            buf.into(),
+            None,
+            &mut projected,
+        )?;
+
+        Ok(projected.freeze())
+    }
+
+    /**
+    Project an encoded message with the given descriptor, keeping only the selected fields.
+
+    Segments that are field labels are matched using the names of fields in the descriptor.
+    Map fields that are selected are always kept in full.
+    */
+    pub fn project_with_descriptor<'a>(
+        &self,
+        buf: impl Into<ProtoBufRef<'a>>,
+        descriptor: &MessageDescriptor,
+    ) -> Result<ProtoBuf, Error> {
+        let mut projected = ProtoBufMut::new(());
+
+        self.project_message(
+            &mut vec![self.root()],
+            0..1,
+            buf.into(),
+            Some(descriptor),
+            &mut projected,
+        )?;
+
+        Ok(projected.freeze())
+    }
+
+    fn project_message(
+        &self,
+        nodes: &mut Vec<usize>,
+        parents: Range<usize>,
+        buf: ProtoBufRef,
+        descriptor: Option<&MessageDescriptor>,
+        projected: &mut ProtoBufMut<()>,
+    ) -> Result<(), Error> {
+        for field in buf.fields() {
+            let field = field?;
+            let number = field.number();
+
+            let field_descriptor = descriptor.and_then(|descriptor| descriptor.field(number));
+
+            let start = nodes.len();
+            let selection = self.push_field(
+                nodes,
+                parents.clone(),
+                Some(number),
+                field_descriptor.map(|field| field.name()),
+            );
+
+            match (selection, field.into_payload()) {
+                (FieldSelection::Excluded, _) => continue,
+                (FieldSelection::Partial, Payload::Len(buf)) => {
+                    match field_descriptor.map(|field| field.kind()) {
+                        // Messages are projected recursively
+                        Some(FieldKind::Message(descriptor)) => {
+                            projected.push_field_len(number);
+                            projected.begin_len(());
+                            self.project_message(
+                                nodes,
+                                start..nodes.len(),
+                                buf,
+                                Some(descriptor),
+                                projected,
+                            )?;
+                            projected.end_len();
+                        }
+                        // Any other fields don't have nested fields to select
+                        // Without a descriptor, it's not known whether a field is a message
+                        _ => push_len(projected, number, buf),
+                    }
+                }
+                (_, Payload::Len(buf)) => push_len(projected, number, buf),
+                (_, Payload::VarInt(value)) => {
+                    projected.push_field(number, WireType::VarInt);
+                    projected.push_varint(value);
+                }
+                (_, Payload::I32(value)) => {
+                    projected.push_field(number, WireType::I32);
+                    projected.push_i32(value);
+                }
+                (_, Payload::I64(value)) => {
+                    projected.push_field(number, WireType::I64);
+                    projected.push_i64(value);
+                }
+            }
+
+            nodes.truncate(start);
+        }
+
+        Ok(())
+    }
+
+    /**
+    The root node, which is the message the mask is applied to.
+    */
+    pub(crate) fn root(&self) -> usize {
+        0
+    }
+
+    /**
+    Push the nodes of a field within a message onto `nodes`.
+
+    The message is selected by the nodes in `parents`. A field may be matched by more than one
+    path, like one using its number and another using its label, so the nested fields of all
+    of them are selected. If the field is selected in full then no nodes are pushed.
+    */
+    pub(crate) fn push_field(
+        &self,
+        nodes: &mut Vec<usize>,
+        parents: Range<usize>,
+        number: Option<u64>,
+        label: Option<&str>,
+    ) -> FieldSelection {
+        let start = nodes.len();
+        let mut selection = FieldSelection::Excluded;
+
+        for parent in parents {
+            let parent = nodes[parent];
+
+            for &child in &self.nodes[parent].children {
+                let is_match = match self.nodes[child].segment {
+                    Segment::Number(n) => Some(n) == number,
+                    Segment::Label(ref l) => Some(&**l) == label,
+                    Segment::Root => false,
+                };
+
+                if !is_match {
+                    continue;
+                }
+
+                if self.nodes[child].is_selected {
+                    nodes.truncate(start);
+
+                    return FieldSelection::Full;
+                }
+
+                nodes.push(child);
+                selection = FieldSelection::Partial;
+            }
+        }
+
+        selection
+    }
+
+    fn child_or_insert(&mut self, node: usize, segment: Segment) -> usize {
+        if let Some(child) = self.nodes[node]
+            .children
+            .iter()
+            .copied()
+            .find(|child| self.nodes[*child].segment == segment)
+        {
+            return child;
+        }
+
+        let child = self.nodes.len();
+
+        self.nodes.push(Node {
+            segment,
+            children: Vec::new(),
+            is_selected: false,
+        });
+        self.nodes[node].children.push(child);
+
+        child
+    }
+}
+
+fn push_len(projected: &mut ProtoBufMut<()>, number: u64, buf: ProtoBufRef) {
+    projected.push_field_len(number);
+    projected.push_len_varint_uint64(buf.len() as u64);
+    projected.push(&buf.to_vec());
+}
+
+impl sval::Value for FieldMask {
+    fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(&'sval self, stream: &mut S) -> sval::Result {
+        let label = sval::Label::new("FieldMask");
+        let paths_label = sval::Label::new("paths");
+        let paths_index = sval::Index::new(1);
+
+        stream.record_tuple_begin(None, Some(&label), None, Some(1))?;
+
+        stream.record_tuple_value_begin(None, &paths_label, &paths_index)?;
+        stream.seq_begin(Some(self.paths.len()))?;
+
+        for path in &self.paths {
+            stream.seq_value_begin()?;
+            stream.value(&**path)?;
+            stream.seq_value_end()?;
+        }
+
+        stream.seq_end()?;
+        stream.record_tuple_value_end(None, &paths_label, &paths_index)?;
+
+        stream.record_tuple_end(None, Some(&label), None)
+    }
+}
+
+impl FromProtobuf for FieldMask {
+    fn empty() -> Self {
+        FieldMask::new()
+    }
+
+    fn merge_field(&mut self, payload: Payload<&[u8]>, _: Option<&sval::Tag>) -> Result<(), Error> {
+        match payload {
+            Payload::Len(buf) => self.merge_message(buf),
+            _ => Err(Error::new(ErrorKind::InvalidWireType)),
+        }
+    }
+
+    fn merge_message(&mut self, buf: &[u8]) -> Result<(), Error> {
+        for field in Fields::new(buf) {
+            let field = field?;
+
+            if field.number() == 1 {
+                let mut path = String::new();
+                path.merge_field(field.into_payload(), None)?;
+
+                *self = mem::take(self).with_path(&path);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::stream_to_protobuf;
+
+    fn select(
+        mask: &FieldMask,
+        path: &[(Option<u64>, Option<&str>)],
+    ) -> (FieldSelection, Vec<usize>) {
+        let mut nodes = vec![mask.root()];
+        let mut parents = 0..1;
+        let mut selection = FieldSelection::Partial;
+
+        for (number, label) in path {
+            let start = nodes.len();
+            selection = mask.push_field(&mut nodes, parents.clone(), *number, *label);
+
+            if selection != FieldSelection::Partial {
+                break;
+            }
+
+            parents = start..nodes.len();
+        }
+
+        let selected = nodes[parents].to_vec();
+
+        (selection, selected)
+    }
+
+    #[test]
+    fn field_matches_number_or_label() {
+        let mask = FieldMask::new().with_path("a.b").with_path("2.3");
+
+        assert_eq!(
+            FieldSelection::Partial,
+            select(&mask, &[(Some(1), Some("a"))]).0
+        );
+        assert_eq!(
+            FieldSelection::Full,
+            select(&mask, &[(Some(1), Some("a")), (Some(7), Some("b"))]).0
+        );
+        assert_eq!(
+            FieldSelection::Full,
+            select(&mask, &[(Some(2), None), (Some(3), None)]).0
+        );
+
+        assert_eq!(
+            FieldSelection::Excluded,
+            select(&mask, &[(Some(3), Some("c"))]).0
+        );
+        assert_eq!(FieldSelection::Excluded, select(&mask, &[(None, None)]).0);
+    }
+
+    #[test]
+    fn field_merges_number_and_label() {
+        let mask = FieldMask::new().with_path("inner.a").with_path("2.b");
+
+        let (selection, nodes) = select(&mask, &[(Some(2), Some("inner"))]);
+
+        assert_eq!(FieldSelection::Partial, selection);
+        assert_eq!(2, nodes.len());
+
+        for nested in [(Some(1), Some("a")), (Some(2), Some("b"))] {
+            assert_eq!(
+                FieldSelection::Full,
+                select(&mask, &[(Some(2), Some("inner")), nested]).0
+            );
+        }
+
+        assert_eq!(
+            FieldSelection::Excluded,
+            select(&mask, &[(Some(2), Some("inner")), (Some(3), Some("c"))]).0
+        );
+    }
+
+    #[test]
+    fn field_prefers_selected() {
+        let mask = FieldMask::new().with_path("a.b").with_path("1");
+
+        assert_eq!(
+            FieldSelection::Full,
+            select(&mask, &[(Some(1), Some("a"))]).0
+        );
+    }
+
+    #[test]
+    fn number_path() {
+        let mask = FieldMask::new().with_number_path(&[1, 20, 3]);
+
+        assert_eq!(vec!["1.20.3"], mask.paths().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn field_mask_roundtrip() {
+        let mask = FieldMask::new().with_path("a.b").with_path("2");
+
+        let decoded = FieldMask::from_protobuf(&stream_to_protobuf(&mask).to_vec()).unwrap();
+
+        assert_eq!(
+            mask.paths().collect::<Vec<_>>(),
+            decoded.paths().collect::<Vec<_>>()
+        );
+    }
+}
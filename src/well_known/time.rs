@@ -0,0 +1,620 @@
+use crate::{
+    decode::FromProtobuf,
+    raw::{Fields, Payload},
+    tags, Error, ErrorKind,
+};
+use core::time;
+
+const NANOS_PER_SECOND: i32 = 1_000_000_000;
+const SECONDS_PER_DAY: i64 = 86_400;
+
+// 0001-01-01T00:00:00Z
+const MIN_TIMESTAMP_SECONDS: i64 = -62_135_596_800;
+// 9999-12-31T23:59:59Z
+const MAX_TIMESTAMP_SECONDS: i64 = 253_402_300_799;
+
+// About 10,000 years, in either direction
+const MAX_DURATION_SECONDS: i64 = 315_576_000_000;
+
+/**
+A point in time, encoded as a `google.protobuf.Timestamp`.
+
+The timestamp is a number of seconds since the Unix epoch, and a number of nanoseconds
+within that second. The nanoseconds are always positive, so a timestamp before the epoch
+counts forwards from a negative number of seconds:
+
+```text
+message Timestamp {
+    int64 seconds = 1;
+    int32 nanos = 2;
+}
+```
+
+Timestamps are streamed as records tagged with [`tags::PROTOBUF_TIMESTAMP`]. Timestamps outside
+the years 0001 to 9999 fail to convert or decode.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    seconds: i64,
+    nanos: i32,
+}
+
+/**
+A signed span of time, encoded as a `google.protobuf.Duration`.
+
+The duration is a number of seconds, and a number of nanoseconds within that second.
+The seconds and nanoseconds of a negative duration are both negative:
+
+```text
+message Duration {
+    int64 seconds = 1;
+    int32 nanos = 2;
+}
+```
+
+Durations are streamed as records tagged with [`tags::PROTOBUF_DURATION`]. Durations longer
+than about 10,000 years in either direction fail to convert or decode.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Duration {
+    seconds: i64,
+    nanos: i32,
+}
+
+impl Timestamp {
+    /**
+    Create a timestamp from a number of seconds and nanoseconds since the Unix epoch.
+
+    Nanoseconds outside the range `0..1_000_000_000` are carried into the seconds,
+    so `Timestamp::new(0, -1)` is one nanosecond before the epoch.
+    */
+    pub fn new(seconds: i64, nanos: i32) -> Self {
+        let seconds = seconds.saturating_add((nanos / NANOS_PER_SECOND) as i64);
+        let nanos = nanos % NANOS_PER_SECOND;
+
+        if nanos < 0 {
+            Timestamp {
+                seconds: seconds.saturating_sub(1),
+                nanos: nanos + NANOS_PER_SECOND,
+            }
+        } else {
+            Timestamp { seconds, nanos }
+        }
+    }
+
+    /**
+    Parse a timestamp from RFC 3339 text, like `2024-02-29T13:45:00.5+10:00`.
+
+    Fractional seconds beyond nanosecond precision are truncated. The timestamp must be
+    between `0001-01-01T00:00:00Z` and `9999-12-31T23:59:59.999999999Z`, and can't be a leap second.
+    */
+    pub fn parse_rfc3339(text: &str) -> Result<Self, Error> {
+        parse_rfc3339(text.as_bytes()).ok_or(Error::new(ErrorKind::InvalidValue))
+    }
+
+    /**
+    The number of seconds since the Unix epoch.
+    */
+    pub fn seconds(&self) -> i64 {
+        self.seconds
+    }
+
+    /**
+    The number of nanoseconds after [`Timestamp::seconds`].
+    */
+    pub fn nanos(&self) -> i32 {
+        self.nanos
+    }
+}
+
+impl Duration {
+    /**
+    Create a duration from a number of seconds and nanoseconds.
+
+    Nanoseconds outside the range `-999_999_999..=999_999_999` are carried into the seconds,
+    and the signs of the seconds and nanoseconds are made to agree, so `Duration::new(1, -1)`
+    is one nanosecond less than a second.
+    */
+    pub fn new(seconds: i64, nanos: i32) -> Self {
+        let seconds = seconds.saturating_add((nanos / NANOS_PER_SECOND) as i64);
+        let nanos = nanos % NANOS_PER_SECOND;
+
+        if seconds > 0 && nanos < 0 {
+            Duration {
+                seconds: seconds - 1,
+                nanos: nanos + NANOS_PER_SECOND,
+            }
+        } else if seconds < 0 && nanos > 0 {
+            Duration {
+                seconds: seconds + 1,
+                nanos: nanos - NANOS_PER_SECOND,
+            }
+        } else {
+            Duration { seconds, nanos }
+        }
+    }
+
+    /**
+    The number of whole seconds in the duration.
+    */
+    pub fn seconds(&self) -> i64 {
+        self.seconds
+    }
+
+    /**
+    The number of nanoseconds after [`Duration::seconds`].
+
+    The nanoseconds of a negative duration are negative.
+    */
+    pub fn nanos(&self) -> i32 {
+        self.nanos
+    }
+}
+
+impl TryFrom<time::Duration> for Duration {
+    type Error = Error;
+
+    fn try_from(value: time::Duration) -> Result<Self, Error> {
+        let seconds =
+            i64::try_from(value.as_secs()).map_err(|_| Error::new(ErrorKind::InvalidValue))?;
+
+        check_duration(Duration::new(seconds, value.subsec_nanos() as i32))
+    }
+}
+
+impl TryFrom<Duration> for time::Duration {
+    type Error = Error;
+
+    fn try_from(value: Duration) -> Result<Self, Error> {
+        // Negative durations can't be represented
+        if value.seconds < 0 || value.nanos < 0 {
+            return Err(Error::new(ErrorKind::InvalidValue));
+        }
+
+        Ok(time::Duration::new(
+            value.seconds as u64,
+            value.nanos as u32,
+        ))
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFrom<std::time::SystemTime> for Timestamp {
+    type Error = Error;
+
+    fn try_from(value: std::time::SystemTime) -> Result<Self, Error> {
+        let seconds = |d: time::Duration| {
+            i64::try_from(d.as_secs()).map_err(|_| Error::new(ErrorKind::InvalidValue))
+        };
+
+        let timestamp = match value.duration_since(std::time::UNIX_EPOCH) {
+            Ok(after) => Timestamp::new(seconds(after)?, after.subsec_nanos() as i32),
+            Err(before) => {
+                let before = before.duration();
+
+                Timestamp::new(-seconds(before)?, -(before.subsec_nanos() as i32))
+            }
+        };
+
+        check_timestamp(timestamp)
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFrom<Timestamp> for std::time::SystemTime {
+    type Error = Error;
+
+    fn try_from(value: Timestamp) -> Result<Self, Error> {
+        let epoch = std::time::UNIX_EPOCH;
+        let nanos = time::Duration::new(0, value.nanos as u32);
+
+        let time = if value.seconds >= 0 {
+            epoch.checked_add(time::Duration::new(value.seconds as u64, 0))
+        } else {
+            epoch.checked_sub(time::Duration::new(value.seconds.unsigned_abs(), 0))
+        };
+
+        time.and_then(|time| time.checked_add(nanos))
+            .ok_or(Error::new(ErrorKind::InvalidValue))
+    }
+}
+
+impl sval::Value for Timestamp {
+    fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(&'sval self, stream: &mut S) -> sval::Result {
+        stream_seconds_nanos(
+            stream,
+            &tags::PROTOBUF_TIMESTAMP,
+            "Timestamp",
+            self.seconds,
+            self.nanos,
+        )
+    }
+}
+
+impl sval::Value for Duration {
+    fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(&'sval self, stream: &mut S) -> sval::Result {
+        stream_seconds_nanos(
+            stream,
+            &tags::PROTOBUF_DURATION,
+            "Duration",
+            self.seconds,
+            self.nanos,
+        )
+    }
+}
+
+fn stream_seconds_nanos<'sval, S: sval::Stream<'sval> + ?Sized>(
+    stream: &mut S,
+    tag: &sval::Tag,
+    label: &'static str,
+    seconds: i64,
+    nanos: i32,
+) -> sval::Result {
+    let label = sval::Label::new(label);
+    let seconds_label = sval::Label::new("seconds");
+    let seconds_index = sval::Index::new(1);
+    let nanos_label = sval::Label::new("nanos");
+    let nanos_index = sval::Index::new(2);
+
+    stream.tagged_begin(Some(tag), Some(&label), None)?;
+    stream.record_tuple_begin(None, Some(&label), None, Some(2))?;
+
+    // The fields are always plain varints, regardless of any default integer encodings
+    stream.record_tuple_value_begin(None, &seconds_label, &seconds_index)?;
+    stream.tagged_begin(Some(&tags::PROTOBUF_VARINT), None, None)?;
+    stream.i64(seconds)?;
+    stream.tagged_end(Some(&tags::PROTOBUF_VARINT), None, None)?;
+    stream.record_tuple_value_end(None, &seconds_label, &seconds_index)?;
+
+    stream.record_tuple_value_begin(None, &nanos_label, &nanos_index)?;
+    stream.tagged_begin(Some(&tags::PROTOBUF_VARINT), None, None)?;
+    stream.i32(nanos)?;
+    stream.tagged_end(Some(&tags::PROTOBUF_VARINT), None, None)?;
+    stream.record_tuple_value_end(None, &nanos_label, &nanos_index)?;
+
+    stream.record_tuple_end(None, Some(&label), None)?;
+    stream.tagged_end(Some(tag), Some(&label), None)
+}
+
+impl FromProtobuf for Timestamp {
+    fn empty() -> Self {
+        Timestamp::default()
+    }
+
+    fn merge_field(&mut self, payload: Payload<&[u8]>, _: Option<&sval::Tag>) -> Result<(), Error> {
+        match payload {
+            Payload::Len(buf) => self.merge_message(buf),
+            _ => Err(Error::new(ErrorKind::InvalidWireType)),
+        }
+    }
+
+    fn merge_message(&mut self, buf: &[u8]) -> Result<(), Error> {
+        let (seconds, nanos) = merge_seconds_nanos(buf, self.seconds, self.nanos)?;
+
+        *self = check_timestamp(Timestamp::new(seconds, nanos))?;
+
+        Ok(())
+    }
+}
+
+impl FromProtobuf for Duration {
+    fn empty() -> Self {
+        Duration::default()
+    }
+
+    fn merge_field(&mut self, payload: Payload<&[u8]>, _: Option<&sval::Tag>) -> Result<(), Error> {
+        match payload {
+            Payload::Len(buf) => self.merge_message(buf),
+            _ => Err(Error::new(ErrorKind::InvalidWireType)),
+        }
+    }
+
+    fn merge_message(&mut self, buf: &[u8]) -> Result<(), Error> {
+        let (seconds, nanos) = merge_seconds_nanos(buf, self.seconds, self.nanos)?;
+
+        *self = check_duration(Duration::new(seconds, nanos))?;
+
+        Ok(())
+    }
+}
+
+fn check_timestamp(timestamp: Timestamp) -> Result<Timestamp, Error> {
+    // Timestamps must be between `0001-01-01T00:00:00Z` and `9999-12-31T23:59:59.999999999Z`
+    if !(MIN_TIMESTAMP_SECONDS..=MAX_TIMESTAMP_SECONDS).contains(&timestamp.seconds) {
+        return Err(Error::new(ErrorKind::InvalidValue));
+    }
+
+    Ok(timestamp)
+}
+
+fn check_duration(duration: Duration) -> Result<Duration, Error> {
+    if !(-MAX_DURATION_SECONDS..=MAX_DURATION_SECONDS).contains(&duration.seconds) {
+        return Err(Error::new(ErrorKind::InvalidValue));
+    }
+
+    Ok(duration)
+}
+
+fn merge_seconds_nanos(buf: &[u8], mut seconds: i64, mut nanos: i32) -> Result<(i64, i32), Error> {
+    for field in Fields::new(buf) {
+        let field = field?;
+
+        match field.number() {
+            1 => seconds.merge_field(field.into_payload(), None)?,
+            2 => nanos.merge_field(field.into_payload(), None)?,
+            _ => (),
+        }
+    }
+
+    Ok((seconds, nanos))
+}
+
+fn parse_rfc3339(text: &[u8]) -> Option<Timestamp> {
+    fn digits(text: &[u8]) -> Option<i64> {
+        text.iter().try_fold(0, |value, digit| {
+            digit
+                .is_ascii_digit()
+                .then(|| value * 10 + (digit - b'0') as i64)
+        })
+    }
+
+    // The date and time are a fixed width: `YYYY-MM-DDTHH:MM:SS`
+    if text.len() < 19 {
+        return None;
+    }
+
+    let (date_time, mut rest) = text.split_at(19);
+
+    let [y0, y1, y2, y3, b'-', m0, m1, b'-', d0, d1, b'T' | b't' | b' ', h0, h1, b':', mi0, mi1, b':', s0, s1] =
+        *date_time
+    else {
+        return None;
+    };
+
+    let year = digits(&[y0, y1, y2, y3])?;
+    let month = digits(&[m0, m1])?;
+    let day = digits(&[d0, d1])?;
+    let hour = digits(&[h0, h1])?;
+    let minute = digits(&[mi0, mi1])?;
+    let second = digits(&[s0, s1])?;
+
+    if !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+
+    // The fractional seconds are optional and can have any number of digits
+    let mut nanos = 0;
+    if let [b'.', fraction @ ..] = rest {
+        let len = fraction.iter().take_while(|b| b.is_ascii_digit()).count();
+
+        if len == 0 {
+            return None;
+        }
+
+        let mut scale = NANOS_PER_SECOND;
+        for digit in &fraction[..len.min(9)] {
+            scale /= 10;
+            nanos += (digit - b'0') as i32 * scale;
+        }
+
+        rest = &fraction[len..];
+    }
+
+    let offset = match *rest {
+        [b'Z' | b'z'] => 0,
+        [sign @ (b'+' | b'-'), h0, h1, b':', m0, m1] => {
+            let hours = digits(&[h0, h1])?;
+            let minutes = digits(&[m0, m1])?;
+
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+
+            let offset = hours * 3600 + minutes * 60;
+
+            if sign == b'-' {
+                -offset
+            } else {
+                offset
+            }
+        }
+        _ => return None,
+    };
+
+    let seconds =
+        days_from_civil(year, month, day) * SECONDS_PER_DAY + hour * 3600 + minute * 60 + second
+            - offset;
+
+    if !(MIN_TIMESTAMP_SECONDS..=MAX_TIMESTAMP_SECONDS).contains(&seconds) {
+        return None;
+    }
+
+    Some(Timestamp { seconds, nanos })
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// The number of days since the Unix epoch for a date in the proleptic Gregorian calendar
+// See: https://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamp_new_normalizes() {
+        assert_eq!((0, 1), parts(Timestamp::new(0, 1)));
+        assert_eq!((-1, 999_999_999), parts(Timestamp::new(0, -1)));
+        assert_eq!((2, 500_000_000), parts(Timestamp::new(1, 1_500_000_000)));
+        assert_eq!((-3, 500_000_000), parts(Timestamp::new(-1, -1_500_000_000)));
+
+        fn parts(ts: Timestamp) -> (i64, i32) {
+            (ts.seconds(), ts.nanos())
+        }
+    }
+
+    #[test]
+    fn duration_new_normalizes() {
+        assert_eq!((0, -1), parts(Duration::new(0, -1)));
+        assert_eq!((0, 999_999_999), parts(Duration::new(1, -1)));
+        assert_eq!((0, -999_999_999), parts(Duration::new(-1, 1)));
+        assert_eq!((-2, -500_000_000), parts(Duration::new(-1, -1_500_000_000)));
+
+        fn parts(d: Duration) -> (i64, i32) {
+            (d.seconds(), d.nanos())
+        }
+    }
+
+    #[test]
+    fn duration_core_conversions() {
+        let d = Duration::try_from(time::Duration::new(3, 5)).unwrap();
+        assert_eq!(Duration::new(3, 5), d);
+        assert_eq!(
+            time::Duration::new(3, 5),
+            time::Duration::try_from(d).unwrap()
+        );
+
+        assert!(time::Duration::try_from(Duration::new(0, -1)).is_err());
+        assert!(Duration::try_from(time::Duration::new(u64::MAX, 0)).is_err());
+
+        assert_eq!(
+            Duration::new(MAX_DURATION_SECONDS, 999_999_999),
+            Duration::try_from(time::Duration::new(
+                MAX_DURATION_SECONDS as u64,
+                999_999_999
+            ))
+            .unwrap()
+        );
+        assert_eq!(
+            ErrorKind::InvalidValue,
+            Duration::try_from(time::Duration::new(MAX_DURATION_SECONDS as u64 + 1, 0))
+                .unwrap_err()
+                .kind()
+        );
+    }
+
+    #[test]
+    fn decode_out_of_range() {
+        fn encode(seconds: i64) -> alloc::vec::Vec<u8> {
+            let mut buf = crate::buf::ProtoBufMut::new(());
+
+            buf.push_field_varint(1);
+            buf.push_varint_sint64(seconds);
+
+            buf.freeze().to_vec().into_owned()
+        }
+
+        for seconds in [MIN_TIMESTAMP_SECONDS, MAX_TIMESTAMP_SECONDS] {
+            assert!(Timestamp::from_protobuf(&encode(seconds)).is_ok());
+        }
+
+        for seconds in [MIN_TIMESTAMP_SECONDS - 1, MAX_TIMESTAMP_SECONDS + 1] {
+            assert_eq!(
+                ErrorKind::InvalidValue,
+                Timestamp::from_protobuf(&encode(seconds))
+                    .unwrap_err()
+                    .kind()
+            );
+        }
+
+        for seconds in [-MAX_DURATION_SECONDS, MAX_DURATION_SECONDS] {
+            assert!(Duration::from_protobuf(&encode(seconds)).is_ok());
+        }
+
+        for seconds in [-MAX_DURATION_SECONDS - 1, MAX_DURATION_SECONDS + 1] {
+            assert_eq!(
+                ErrorKind::InvalidValue,
+                Duration::from_protobuf(&encode(seconds))
+                    .unwrap_err()
+                    .kind()
+            );
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn timestamp_system_time_conversions() {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        for (time, expected) in [
+            (UNIX_EPOCH, Timestamp::new(0, 0)),
+            (UNIX_EPOCH + time::Duration::new(1, 5), Timestamp::new(1, 5)),
+            (
+                UNIX_EPOCH - time::Duration::new(1, 5),
+                Timestamp::new(-2, 999_999_995),
+            ),
+        ] {
+            let ts = Timestamp::try_from(time).unwrap();
+
+            assert_eq!(expected, ts);
+            assert_eq!(time, SystemTime::try_from(ts).unwrap());
+        }
+    }
+
+    #[test]
+    fn timestamp_parse_rfc3339() {
+        for (text, seconds, nanos) in [
+            ("1970-01-01T00:00:00Z", 0, 0),
+            ("1970-01-01t00:00:00z", 0, 0),
+            ("1970-01-01 00:00:00Z", 0, 0),
+            ("2024-02-29T13:45:00.5+10:00", 1_709_178_300, 500_000_000),
+            ("1969-12-31T23:59:59.999999999Z", -1, 999_999_999),
+            ("1970-01-01T00:00:00.1234567891Z", 0, 123_456_789),
+            ("1970-01-01T00:00:00-01:30", 5400, 0),
+            ("0001-01-01T00:00:00Z", MIN_TIMESTAMP_SECONDS, 0),
+            ("9999-12-31T23:59:59Z", MAX_TIMESTAMP_SECONDS, 0),
+        ] {
+            assert_eq!(
+                Timestamp { seconds, nanos },
+                Timestamp::parse_rfc3339(text).unwrap(),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn timestamp_parse_rfc3339_invalid() {
+        for text in [
+            "",
+            "1970-01-01",
+            "1970-01-01T00:00:00",
+            "1970-01-01T00:00:00.Z",
+            "1970-13-01T00:00:00Z",
+            "2023-02-29T00:00:00Z",
+            "1970-01-01T24:00:00Z",
+            "1970-01-01T23:59:60Z",
+            "1970-01-01T00:00:00+24:00",
+            "1970-01-01T00:00:00+0100",
+            "0001-01-01T00:00:00+00:01",
+            "9999-12-31T23:59:59-00:01",
+            "197O-01-01T00:00:00Z",
+        ] {
+            assert_eq!(
+                ErrorKind::InvalidValue,
+                Timestamp::parse_rfc3339(text).unwrap_err().kind(),
+                "{text}"
+            );
+        }
+    }
+}
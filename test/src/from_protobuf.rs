@@ -0,0 +1,513 @@
+#[cfg(all(test, feature = "prost"))]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use prost::Message;
+    use sval_derive::Value;
+    use sval_protobuf::decode::{Bytes, FromProtobuf};
+
+    use crate::protos;
+
+    fn roundtrip<T: sval::Value + FromProtobuf + PartialEq + std::fmt::Debug>(value: T) {
+        let encoded = sval_protobuf::stream_to_protobuf(&value)
+            .to_vec()
+            .into_owned();
+
+        assert_eq!(value, T::from_protobuf(&encoded).unwrap());
+    }
+
+    #[test]
+    fn scalar() {
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        pub struct Scalar {
+            f64: f64,
+            f32: f32,
+            vi32: i32,
+            vi64: i64,
+            vu32: u32,
+            vu64: u64,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_VARINT_SIGNED")]
+            si32: i32,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_VARINT_SIGNED")]
+            si64: i64,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_I32")]
+            fi32: u32,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_I64")]
+            fi64: u64,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_I32")]
+            sfi32: i32,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_I64")]
+            sfi64: i64,
+            bool: bool,
+            sbin: String,
+            bin: Bytes,
+        }
+
+        let prost = protos::cases::Scalar {
+            f64: 1.5,
+            f32: -2.5,
+            vi32: i32::MIN,
+            vi64: i64::MIN,
+            vu32: u32::MAX,
+            vu64: u64::MAX,
+            si32: i32::MIN,
+            si64: i64::MIN,
+            fi32: u32::MAX,
+            fi64: u64::MAX,
+            sfi32: i32::MIN,
+            sfi64: i64::MIN,
+            bool: true,
+            sbin: "abc".to_owned(),
+            bin: b"123".to_vec(),
+        }
+        .encode_to_vec();
+
+        let expected = Scalar {
+            f64: 1.5,
+            f32: -2.5,
+            vi32: i32::MIN,
+            vi64: i64::MIN,
+            vu32: u32::MAX,
+            vu64: u64::MAX,
+            si32: i32::MIN,
+            si64: i64::MIN,
+            fi32: u32::MAX,
+            fi64: u64::MAX,
+            sfi32: i32::MIN,
+            sfi64: i64::MIN,
+            bool: true,
+            sbin: "abc".to_owned(),
+            bin: Bytes::new(b"123".to_vec()),
+        };
+
+        assert_eq!(expected, Scalar::from_protobuf(&prost).unwrap());
+
+        roundtrip(expected);
+    }
+
+    #[test]
+    fn scalar_missing() {
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        pub struct Basic {
+            id: i32,
+            content: String,
+            index: Option<i32>,
+        }
+
+        let prost = protos::cases::Basic {
+            id: 0,
+            content: String::new(),
+            index: None,
+        }
+        .encode_to_vec();
+
+        assert_eq!(
+            Basic {
+                id: 0,
+                content: String::new(),
+                index: None,
+            },
+            Basic::from_protobuf(&prost).unwrap()
+        );
+    }
+
+    #[test]
+    fn num_128bit() {
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        pub struct Num128Bit {
+            u: u128,
+            i: i128,
+        }
+
+        let prost = protos::cases::Num128Bit {
+            u: u128::MAX.to_le_bytes().to_vec(),
+            i: i128::MIN.to_le_bytes().to_vec(),
+        }
+        .encode_to_vec();
+
+        assert_eq!(
+            Num128Bit {
+                u: u128::MAX,
+                i: i128::MIN,
+            },
+            Num128Bit::from_protobuf(&prost).unwrap()
+        );
+    }
+
+    #[test]
+    fn non_contiguous_fields() {
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        pub struct NonContiguousFields {
+            #[sval(index = 4)]
+            id: i32,
+            #[sval(index = 11)]
+            content: String,
+            #[sval(index = 19)]
+            index: Option<i32>,
+        }
+
+        let prost = protos::cases::NonContiguousFields {
+            id: 42,
+            content: "Some content".to_owned(),
+            index: Some(3),
+        }
+        .encode_to_vec();
+
+        let expected = NonContiguousFields {
+            id: 42,
+            content: "Some content".to_owned(),
+            index: Some(3),
+        };
+
+        assert_eq!(
+            expected,
+            NonContiguousFields::from_protobuf(&prost).unwrap()
+        );
+
+        roundtrip(expected);
+    }
+
+    #[test]
+    fn repeated() {
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        pub struct Repeated {
+            #[sval(index = 3)]
+            a: Vec<String>,
+        }
+
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        pub struct RepeatedPacked {
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_LEN_PACKED")]
+            a: Vec<i32>,
+        }
+
+        let prost = protos::cases::Repeated {
+            a: vec!["a".to_owned(), "b".to_owned()],
+        }
+        .encode_to_vec();
+
+        assert_eq!(
+            Repeated {
+                a: vec!["a".to_owned(), "b".to_owned()],
+            },
+            Repeated::from_protobuf(&prost).unwrap()
+        );
+
+        let prost = protos::cases::RepeatedPacked { a: vec![1, -2, 3] }.encode_to_vec();
+
+        assert_eq!(
+            RepeatedPacked { a: vec![1, -2, 3] },
+            RepeatedPacked::from_protobuf(&prost).unwrap()
+        );
+
+        roundtrip(RepeatedPacked { a: vec![1, -2, 3] });
+    }
+
+    #[test]
+    fn map() {
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        pub struct Map {
+            a: BTreeMap<String, i32>,
+        }
+
+        let a = BTreeMap::from_iter([("a".to_owned(), 1), ("b".to_owned(), 2), ("".to_owned(), 0)]);
+
+        let prost = protos::cases::Map {
+            a: a.clone().into_iter().collect(),
+        }
+        .encode_to_vec();
+
+        assert_eq!(Map { a: a.clone() }, Map::from_protobuf(&prost).unwrap());
+
+        roundtrip(Map { a });
+    }
+
+    #[test]
+    fn map_tagged_values() {
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        pub struct TaggedMaps {
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_VARINT_SIGNED")]
+            a: BTreeMap<String, i32>,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_I64")]
+            b: BTreeMap<String, i64>,
+        }
+
+        let a = BTreeMap::from_iter([("a".to_owned(), -1), ("b".to_owned(), i32::MIN)]);
+        let b = BTreeMap::from_iter([("a".to_owned(), -1), ("b".to_owned(), i64::MAX)]);
+
+        let prost = protos::cases::TaggedMaps {
+            a: a.clone().into_iter().collect(),
+            b: b.clone().into_iter().collect(),
+        }
+        .encode_to_vec();
+
+        let expected = TaggedMaps { a, b };
+
+        assert_eq!(expected, TaggedMaps::from_protobuf(&prost).unwrap());
+
+        roundtrip(expected);
+    }
+
+    #[test]
+    fn r#enum() {
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        #[repr(i32)]
+        pub enum EnumInner {
+            A = 0,
+            B = -3,
+            C = -6,
+        }
+
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        pub struct Enum {
+            value: EnumInner,
+        }
+
+        for (prost, expected) in [
+            (protos::cases::EnumInner::A, EnumInner::A),
+            (protos::cases::EnumInner::B, EnumInner::B),
+            (protos::cases::EnumInner::C, EnumInner::C),
+        ] {
+            let prost = protos::cases::Enum {
+                value: prost as i32,
+            }
+            .encode_to_vec();
+
+            assert_eq!(
+                Enum { value: expected },
+                Enum::from_protobuf(&prost).unwrap()
+            );
+        }
+
+        roundtrip(Enum {
+            value: EnumInner::C,
+        });
+    }
+
+    #[test]
+    fn oneof() {
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        pub enum Value {
+            Number(i32),
+            Boolean(bool),
+            Text(String),
+        }
+
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        pub struct Oneof {
+            #[sval(flatten)]
+            value: Value,
+        }
+
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        pub struct NestedOneof {
+            a: Value,
+        }
+
+        let prost = protos::cases::Oneof {
+            value: Some(protos::cases::oneof::Value::Text("abc".to_owned())),
+        }
+        .encode_to_vec();
+
+        assert_eq!(
+            Oneof {
+                value: Value::Text("abc".to_owned()),
+            },
+            Oneof::from_protobuf(&prost).unwrap()
+        );
+        assert_eq!(
+            Value::Text("abc".to_owned()),
+            Value::from_protobuf(&prost).unwrap()
+        );
+
+        let prost = protos::cases::NestedOneof {
+            a: Some(protos::cases::Oneof {
+                value: Some(protos::cases::oneof::Value::Boolean(true)),
+            }),
+        }
+        .encode_to_vec();
+
+        assert_eq!(
+            NestedOneof {
+                a: Value::Boolean(true),
+            },
+            NestedOneof::from_protobuf(&prost).unwrap()
+        );
+
+        roundtrip(Oneof {
+            value: Value::Number(42),
+        });
+        roundtrip(NestedOneof {
+            a: Value::Text("abc".to_owned()),
+        });
+    }
+
+    #[test]
+    fn oneof_last_one_wins() {
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        pub enum Value {
+            Number(i32),
+            Boolean(bool),
+            Text(String),
+        }
+
+        let mut encoded = protos::cases::Oneof {
+            value: Some(protos::cases::oneof::Value::Number(1)),
+        }
+        .encode_to_vec();
+        encoded.extend(
+            protos::cases::Oneof {
+                value: Some(protos::cases::oneof::Value::Boolean(true)),
+            }
+            .encode_to_vec(),
+        );
+
+        assert_eq!(
+            Value::Boolean(true),
+            Value::from_protobuf(&encoded).unwrap()
+        );
+    }
+
+    #[test]
+    fn nested() {
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        pub struct Optional {
+            a: Option<i32>,
+        }
+
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        pub struct NestedInner {
+            a: Optional,
+            b: Bytes,
+            c: i32,
+        }
+
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        pub struct Nested {
+            a: Option<NestedInner>,
+            b: String,
+            c: i32,
+        }
+
+        let prost = protos::cases::Nested {
+            a: Some(protos::cases::NestedInner {
+                a: Some(protos::cases::Optional { a: Some(1) }),
+                b: b"Some bytes".to_vec(),
+                c: 2,
+            }),
+            b: "Some text".to_owned(),
+            c: 3,
+        }
+        .encode_to_vec();
+
+        let expected = Nested {
+            a: Some(NestedInner {
+                a: Optional { a: Some(1) },
+                b: Bytes::new(b"Some bytes".to_vec()),
+                c: 2,
+            }),
+            b: "Some text".to_owned(),
+            c: 3,
+        };
+
+        assert_eq!(expected, Nested::from_protobuf(&prost).unwrap());
+
+        roundtrip(expected);
+    }
+
+    #[test]
+    fn well_known_time() {
+        use sval_protobuf::well_known::{Duration, Timestamp};
+
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        pub struct Times {
+            timestamp: Timestamp,
+            duration: Option<Duration>,
+            timestamps: Vec<Timestamp>,
+        }
+
+        let prost = protos::cases::Times {
+            timestamp: Some(protos::cases::Timestamp {
+                seconds: -1,
+                nanos: 999_999_999,
+            }),
+            duration: Some(protos::cases::Duration {
+                seconds: 0,
+                nanos: 0,
+            }),
+            timestamps: vec![protos::cases::Timestamp {
+                seconds: 1,
+                nanos: 0,
+            }],
+        }
+        .encode_to_vec();
+
+        let expected = Times {
+            timestamp: Timestamp::new(0, -1),
+            duration: Some(Duration::new(0, 0)),
+            timestamps: vec![Timestamp::new(1, 0)],
+        };
+
+        assert_eq!(expected, Times::from_protobuf(&prost).unwrap());
+
+        roundtrip(expected);
+    }
+
+    #[test]
+    fn well_known_wrappers() {
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        pub struct Wrappers {
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_WRAPPER")]
+            a: Option<i32>,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_WRAPPER")]
+            b: Option<u64>,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_WRAPPER")]
+            c: Option<String>,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_WRAPPER")]
+            d: Option<bool>,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_WRAPPER")]
+            e: Option<f64>,
+        }
+
+        let prost = protos::cases::Wrappers {
+            a: Some(protos::cases::Int32Value { value: 0 }),
+            b: Some(protos::cases::UInt64Value { value: 42 }),
+            c: Some(protos::cases::StringValue {
+                value: "abc".to_owned(),
+            }),
+            d: None,
+            e: Some(protos::cases::DoubleValue { value: 1.5 }),
+        }
+        .encode_to_vec();
+
+        let expected = Wrappers {
+            a: Some(0),
+            b: Some(42),
+            c: Some("abc".to_owned()),
+            d: None,
+            e: Some(1.5),
+        };
+
+        assert_eq!(expected, Wrappers::from_protobuf(&prost).unwrap());
+
+        roundtrip(expected);
+    }
+
+    #[test]
+    fn newtype_tagged() {
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        #[sval(tag = "sval_protobuf::tags::PROTOBUF_I64")]
+        pub struct Fixed(u64);
+
+        #[derive(Value, FromProtobuf, Debug, PartialEq)]
+        pub struct Record {
+            time: Fixed,
+            values: Vec<Fixed>,
+        }
+
+        roundtrip(Record {
+            time: Fixed(42),
+            values: vec![Fixed(1), Fixed(2)],
+        });
+    }
+}
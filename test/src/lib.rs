@@ -44,6 +44,7 @@ pub mod protos {
     }
 }
 
+mod from_protobuf;
 pub mod opentelemetry;
 
 #[cfg(all(test, feature = "prost"))]
@@ -659,15 +660,1891 @@ mod tests {
         assert_proto(&prost, &sval);
     }
 
+    #[test]
+    fn num_128bit_hi_lo() {
+        let prost = protos::cases::Num128BitHiLo {
+            u: Some(protos::cases::HiLo {
+                hi: 1,
+                lo: u64::MAX,
+            }),
+            i: Some(protos::cases::HiLo {
+                hi: i64::MIN as u64,
+                lo: 1,
+            }),
+        }
+        .encode_to_vec();
+
+        let sval = {
+            #[derive(Value)]
+            pub struct Num128Bit {
+                u: u128,
+                i: i128,
+            }
+
+            let mut stream = sval_protobuf::ProtoBufStream::new()
+                .with_int128_encoding(sval_protobuf::Int128Encoding::HiLo);
+
+            sval::stream(
+                &mut stream,
+                &Num128Bit {
+                    u: (1 << 64) | u64::MAX as u128,
+                    i: i128::MIN + 1,
+                },
+            )
+            .unwrap();
+
+            stream.freeze().to_vec().into_owned()
+        };
+
+        assert_proto(&prost, &sval);
+    }
+
+    #[test]
+    fn num_128bit_decimal() {
+        let prost = protos::cases::Num128BitDecimal {
+            u: u128::MAX.to_string(),
+            i: i128::MIN.to_string(),
+        }
+        .encode_to_vec();
+
+        let sval = {
+            #[derive(Value)]
+            pub struct Num128Bit {
+                u: u128,
+                i: i128,
+            }
+
+            let mut stream = sval_protobuf::ProtoBufStream::new()
+                .with_int128_encoding(sval_protobuf::Int128Encoding::Decimal);
+
+            sval::stream(
+                &mut stream,
+                &Num128Bit {
+                    u: u128::MAX,
+                    i: i128::MIN,
+                },
+            )
+            .unwrap();
+
+            stream.freeze().to_vec().into_owned()
+        };
+
+        assert_proto(&prost, &sval);
+
+        let prost = protos::cases::Num128BitDecimal {
+            u: "0".to_owned(),
+            i: "-1".to_owned(),
+        }
+        .encode_to_vec();
+
+        let sval = {
+            let mut stream = sval_protobuf::ProtoBufStream::new()
+                .with_int128_encoding(sval_protobuf::Int128Encoding::Decimal);
+
+            sval::stream(&mut stream, &(0u128, -1i128)).unwrap();
+
+            stream.freeze().to_vec().into_owned()
+        };
+
+        assert_proto(&prost, &sval);
+    }
+
+    struct Number<'a>(&'a str);
+
+    impl sval::Value for Number<'_> {
+        fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(
+            &'sval self,
+            stream: &mut S,
+        ) -> sval::Result {
+            stream.tagged_begin(Some(&sval::tags::NUMBER), None, None)?;
+            stream.value(self.0)?;
+            stream.tagged_end(Some(&sval::tags::NUMBER), None, None)
+        }
+    }
+
+    #[test]
+    fn number_text() {
+        let prost = protos::cases::Num128BitDecimal {
+            u: "1.5".to_owned(),
+            i: "-1e3".to_owned(),
+        }
+        .encode_to_vec();
+
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new()
+                .with_number_encoding(sval_protobuf::NumberEncoding::Text),
+            (Number("1.5"), Number("-1e3")),
+        )
+        .unwrap();
+
+        assert_proto(&prost, &sval);
+    }
+
+    #[test]
+    fn number_double() {
+        #[derive(Value)]
+        pub struct Scalar<'a> {
+            f64: Number<'a>,
+        }
+
+        let prost = protos::cases::Scalar {
+            f64: -1500.0,
+            ..Default::default()
+        }
+        .encode_to_vec();
+
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new()
+                .with_number_encoding(sval_protobuf::NumberEncoding::Double),
+            Scalar {
+                f64: Number("-1.5e3"),
+            },
+        )
+        .unwrap();
+
+        assert_proto(&prost, &sval);
+
+        let err = encode_with(
+            sval_protobuf::ProtoBufStream::new()
+                .with_number_encoding(sval_protobuf::NumberEncoding::Double),
+            Scalar {
+                f64: Number("not a number"),
+            },
+        )
+        .unwrap_err();
+
+        assert_eq!(sval_protobuf::ErrorKind::InvalidValue, err.kind());
+    }
+
+    #[test]
+    fn number_integer() {
+        #[derive(Value)]
+        pub struct Scalar<'a> {
+            #[sval(index = 4)]
+            vi64: Number<'a>,
+            #[sval(index = 6)]
+            vu64: Number<'a>,
+            #[sval(index = 8, data_tag = "sval_protobuf::tags::PROTOBUF_VARINT_SIGNED")]
+            si64: Number<'a>,
+            #[sval(index = 14)]
+            sbin: Number<'a>,
+        }
+
+        let prost = protos::cases::Scalar {
+            vi64: i64::MIN,
+            vu64: u64::MAX,
+            si64: -42,
+            sbin: "1.5".to_owned(),
+            ..Default::default()
+        }
+        .encode_to_vec();
+
+        let min = i64::MIN.to_string();
+        let max = u64::MAX.to_string();
+
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new()
+                .with_number_encoding(sval_protobuf::NumberEncoding::Integer),
+            Scalar {
+                vi64: Number(&min),
+                vu64: Number(&max),
+                si64: Number("-42"),
+                sbin: Number("1.5"),
+            },
+        )
+        .unwrap();
+
+        assert_proto(&prost, &sval);
+    }
+
+    #[test]
+    fn integer_defaults() {
+        #[derive(Value)]
+        pub struct Scalar {
+            #[sval(index = 3, data_tag = "sval_protobuf::tags::PROTOBUF_VARINT")]
+            vi32: i32,
+            #[sval(index = 4, data_tag = "sval_protobuf::tags::PROTOBUF_VARINT")]
+            vi64: i64,
+            #[sval(index = 7)]
+            si32: i32,
+            #[sval(index = 8)]
+            si64: i64,
+            #[sval(index = 9)]
+            fi32: u32,
+            #[sval(index = 10)]
+            fi64: u64,
+            #[sval(index = 11, data_tag = "sval_protobuf::tags::PROTOBUF_I32")]
+            sfi32: i32,
+        }
+
+        let prost = protos::cases::Scalar {
+            vi32: -1,
+            vi64: -2,
+            si32: -3,
+            si64: -4,
+            fi32: 5,
+            fi64: 6,
+            sfi32: -7,
+            ..Default::default()
+        }
+        .encode_to_vec();
+
+        let sval = {
+            let mut stream = sval_protobuf::ProtoBufStream::new().with_integers(
+                sval_protobuf::IntegerOptions::new()
+                    .with_signed(sval_protobuf::IntegerEncoding::Signed)
+                    .with_unsigned(sval_protobuf::IntegerEncoding::Fixed),
+            );
+
+            sval::stream(
+                &mut stream,
+                &Scalar {
+                    vi32: -1,
+                    vi64: -2,
+                    si32: -3,
+                    si64: -4,
+                    fi32: 5,
+                    fi64: 6,
+                    sfi32: -7,
+                },
+            )
+            .unwrap();
+
+            stream.freeze().to_vec().into_owned()
+        };
+
+        assert_proto(&prost, &sval);
+    }
+
+    #[test]
+    fn integer_defaults_enum() {
+        #[derive(Value)]
+        #[repr(i32)]
+        pub enum EnumInner {
+            C = -6,
+        }
+
+        #[derive(Value)]
+        pub struct Enum {
+            value: EnumInner,
+        }
+
+        let prost = protos::cases::Enum {
+            value: protos::cases::EnumInner::C as i32,
+        }
+        .encode_to_vec();
+
+        let sval = {
+            let mut stream = sval_protobuf::ProtoBufStream::new().with_integers(
+                sval_protobuf::IntegerOptions::new()
+                    .with_signed(sval_protobuf::IntegerEncoding::Fixed),
+            );
+
+            sval::stream(
+                &mut stream,
+                &Enum {
+                    value: EnumInner::C,
+                },
+            )
+            .unwrap();
+
+            stream.freeze().to_vec().into_owned()
+        };
+
+        assert_proto(&prost, &sval);
+    }
+
+    #[test]
+    fn well_known_time() {
+        use sval_protobuf::well_known::{Duration, Timestamp};
+
+        #[derive(Value)]
+        pub struct Times {
+            timestamp: Timestamp,
+            duration: Duration,
+            timestamps: Vec<Timestamp>,
+        }
+
+        let prost = protos::cases::Times {
+            timestamp: Some(protos::cases::Timestamp {
+                seconds: 1_709_178_300,
+                nanos: 500_000_000,
+            }),
+            duration: Some(protos::cases::Duration {
+                seconds: -1,
+                nanos: -5,
+            }),
+            timestamps: vec![
+                protos::cases::Timestamp {
+                    seconds: 0,
+                    nanos: 0,
+                },
+                protos::cases::Timestamp {
+                    seconds: -1,
+                    nanos: 999_999_999,
+                },
+            ],
+        }
+        .encode_to_vec();
+
+        let times = Times {
+            timestamp: Timestamp::new(1_709_178_300, 500_000_000),
+            duration: Duration::new(-1, -5),
+            timestamps: vec![Timestamp::new(0, 0), Timestamp::new(0, -1)],
+        };
+
+        let sval = sval_protobuf::stream_to_protobuf(&times)
+            .to_vec()
+            .into_owned();
+
+        // The fields are plain varints regardless of the default integer encodings
+        let sval_signed = {
+            let mut stream = sval_protobuf::ProtoBufStream::new().with_integers(
+                sval_protobuf::IntegerOptions::new()
+                    .with_signed(sval_protobuf::IntegerEncoding::Signed),
+            );
+
+            sval::stream(&mut stream, &times).unwrap();
+
+            stream.freeze().to_vec().into_owned()
+        };
+
+        assert_proto(&prost, &sval);
+        assert_proto(&prost, &sval_signed);
+    }
+
+    #[test]
+    fn well_known_timestamp_rfc3339() {
+        #[derive(Value)]
+        pub struct Times<'a> {
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_TIMESTAMP")]
+            timestamp: &'a str,
+        }
+
+        let prost = protos::cases::Times {
+            timestamp: Some(protos::cases::Timestamp {
+                seconds: 1_709_178_300,
+                nanos: 500_000_000,
+            }),
+            ..Default::default()
+        }
+        .encode_to_vec();
+
+        let sval = sval_protobuf::stream_to_protobuf(Times {
+            timestamp: "2024-02-29T13:45:00.5+10:00",
+        })
+        .to_vec()
+        .into_owned();
+
+        assert_proto(&prost, &sval);
+
+        let err = sval_protobuf::try_stream_to_protobuf(Times {
+            timestamp: "2024-02-30T13:45:00Z",
+        })
+        .unwrap_err();
+
+        assert_eq!(sval_protobuf::ErrorKind::InvalidValue, err.kind());
+    }
+
+    #[test]
+    fn well_known_any() {
+        use sval_protobuf::{decode::FromProtobuf, well_known::Any};
+
+        #[derive(Value)]
+        pub struct Basic<'a> {
+            id: i32,
+            content: &'a str,
+            index: Option<i32>,
+        }
+
+        #[derive(Value)]
+        pub struct Envelope<V> {
+            payload: V,
+        }
+
+        let basic = protos::cases::Basic {
+            id: 1,
+            content: "Some content".to_owned(),
+            index: Some(2),
+        };
+
+        let prost = protos::cases::Envelope {
+            payload: Some(protos::cases::Any {
+                type_url: "type.googleapis.com/cases.Basic".to_owned(),
+                value: basic.encode_to_vec(),
+            }),
+        }
+        .encode_to_vec();
+
+        let sval = sval_protobuf::stream_to_protobuf(Envelope {
+            payload: Any::new(
+                "type.googleapis.com/cases.Basic",
+                Basic {
+                    id: 1,
+                    content: "Some content",
+                    index: Some(2),
+                },
+            ),
+        })
+        .to_vec()
+        .into_owned();
+
+        assert_proto(&prost, &sval);
+
+        // Decoded values can be re-encoded without decoding their payload
+        let any = Any::from_protobuf(
+            &protos::cases::Any {
+                type_url: "type.googleapis.com/cases.Basic".to_owned(),
+                value: basic.encode_to_vec(),
+            }
+            .encode_to_vec(),
+        )
+        .unwrap();
+
+        let sval = sval_protobuf::stream_to_protobuf(Envelope { payload: any })
+            .to_vec()
+            .into_owned();
+
+        assert_proto(&prost, &sval);
+    }
+
+    #[test]
+    fn well_known_wrappers() {
+        #[derive(Value)]
+        pub struct Wrappers<'a> {
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_WRAPPER")]
+            a: Option<i32>,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_WRAPPER")]
+            b: Option<u64>,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_WRAPPER")]
+            c: Option<&'a str>,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_WRAPPER")]
+            d: Option<bool>,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_WRAPPER")]
+            e: Option<f64>,
+        }
+
+        let prost = protos::cases::Wrappers {
+            a: Some(protos::cases::Int32Value { value: -1 }),
+            b: Some(protos::cases::UInt64Value { value: u64::MAX }),
+            c: Some(protos::cases::StringValue {
+                value: "abc".to_owned(),
+            }),
+            d: Some(protos::cases::BoolValue { value: true }),
+            e: Some(protos::cases::DoubleValue { value: -1.5 }),
+        }
+        .encode_to_vec();
+
+        let wrappers = Wrappers {
+            a: Some(-1),
+            b: Some(u64::MAX),
+            c: Some("abc"),
+            d: Some(true),
+            e: Some(-1.5),
+        };
+
+        let sval = sval_protobuf::stream_to_protobuf(&wrappers)
+            .to_vec()
+            .into_owned();
+
+        // The values are plain varints regardless of the default integer encodings
+        let sval_fixed = {
+            let mut stream = sval_protobuf::ProtoBufStream::new().with_integers(
+                sval_protobuf::IntegerOptions::new()
+                    .with_signed(sval_protobuf::IntegerEncoding::Signed)
+                    .with_unsigned(sval_protobuf::IntegerEncoding::Fixed),
+            );
+
+            sval::stream(&mut stream, &wrappers).unwrap();
+
+            stream.freeze().to_vec().into_owned()
+        };
+
+        assert_proto(&prost, &sval);
+        assert_proto(&prost, &sval_fixed);
+    }
+
+    #[test]
+    fn well_known_wrappers_default() {
+        #[derive(Value)]
+        pub struct Wrappers<'a> {
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_WRAPPER")]
+            a: Option<i32>,
+            #[sval(index = 3, data_tag = "sval_protobuf::tags::PROTOBUF_WRAPPER")]
+            c: Option<&'a str>,
+        }
+
+        // Default values are still written, even when defaults are omitted
+        let raw = {
+            let mut buf = ProtoBufMut::new(());
+
+            buf.push_field_len(1);
+            buf.begin_len(());
+            buf.push_field_varint(1);
+            buf.push_varint_uint64(0);
+            buf.end_len();
+
+            buf.push_field_len(3);
+            buf.begin_len(());
+            buf.push_field_len(1);
+            buf.push_len_varint_uint64(0);
+            buf.end_len();
+
+            buf.freeze().to_vec().into_owned()
+        };
+
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new().with_omit_defaults(true),
+            Wrappers {
+                a: Some(0),
+                c: Some(""),
+            },
+        )
+        .unwrap();
+
+        assert_proto(&raw, &sval);
+
+        assert_eq!(
+            protos::cases::Wrappers {
+                a: Some(protos::cases::Int32Value { value: 0 }),
+                c: Some(protos::cases::StringValue {
+                    value: String::new(),
+                }),
+                ..Default::default()
+            },
+            protos::cases::Wrappers::decode(&*sval).unwrap()
+        );
+    }
+
+    #[test]
+    fn well_known_wrappers_err_unsupported() {
+        #[derive(Value)]
+        pub struct Wrappers<'a> {
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_WRAPPER")]
+            a: Option<&'a [i32]>,
+        }
+
+        let err = sval_protobuf::try_stream_to_protobuf(Wrappers { a: Some(&[1]) }).unwrap_err();
+
+        assert_eq!(sval_protobuf::ErrorKind::Unsupported, err.kind());
+    }
+
+    #[test]
+    fn well_known_dynamic() {
+        use protos::cases::{value::Kind, ListValue, Struct, Value as DynValue};
+
+        fn value(kind: Kind) -> DynValue {
+            DynValue { kind: Some(kind) }
+        }
+
+        #[derive(Value)]
+        pub struct Attributes<'a> {
+            a: i32,
+            b: &'a str,
+            c: Option<bool>,
+            d: &'a [f64],
+            e: &'a sval::MapSlice<&'a str, u64>,
+        }
+
+        #[derive(Value)]
+        pub struct Dynamic<'a> {
+            #[sval(index = 1)]
+            value: sval_protobuf::well_known::DynamicValue<Option<i32>>,
+            #[sval(index = 2)]
+            attributes: sval_protobuf::well_known::DynamicValue<Attributes<'a>>,
+        }
+
+        let sval = sval_protobuf::stream_to_protobuf(Dynamic {
+            value: sval_protobuf::well_known::DynamicValue::new(None),
+            attributes: sval_protobuf::well_known::DynamicValue::new(Attributes {
+                a: 0,
+                b: "text",
+                c: Some(true),
+                d: &[1.5, -2.0],
+                e: sval::MapSlice::new(&[("x", u64::MAX)]),
+            }),
+        })
+        .to_vec()
+        .into_owned();
+
+        let decoded = protos::cases::Dynamic::decode(&*sval).unwrap();
+
+        let expected = protos::cases::Dynamic {
+            value: Some(value(Kind::NullValue(0))),
+            attributes: Some(value(Kind::StructValue(Struct {
+                fields: [
+                    ("a".to_owned(), value(Kind::NumberValue(0.0))),
+                    ("b".to_owned(), value(Kind::StringValue("text".to_owned()))),
+                    ("c".to_owned(), value(Kind::BoolValue(true))),
+                    (
+                        "d".to_owned(),
+                        value(Kind::ListValue(ListValue {
+                            values: vec![
+                                value(Kind::NumberValue(1.5)),
+                                value(Kind::NumberValue(-2.0)),
+                            ],
+                        })),
+                    ),
+                    (
+                        "e".to_owned(),
+                        value(Kind::StructValue(Struct {
+                            fields: [("x".to_owned(), value(Kind::NumberValue(u64::MAX as f64)))]
+                                .into_iter()
+                                .collect(),
+                        })),
+                    ),
+                ]
+                .into_iter()
+                .collect(),
+            }))),
+        };
+
+        assert_eq!(expected, decoded);
+    }
+
+    #[test]
+    fn well_known_dynamic_root() {
+        use protos::cases::{value::Kind, ListValue, Value as DynValue};
+
+        let sval = sval_protobuf::stream_to_protobuf(sval_protobuf::well_known::DynamicValue::new(
+            (1, "a", [false]),
+        ))
+        .to_vec()
+        .into_owned();
+
+        let decoded = DynValue::decode(&*sval).unwrap();
+
+        let expected = DynValue {
+            kind: Some(Kind::ListValue(ListValue {
+                values: vec![
+                    DynValue {
+                        kind: Some(Kind::NumberValue(1.0)),
+                    },
+                    DynValue {
+                        kind: Some(Kind::StringValue("a".to_owned())),
+                    },
+                    DynValue {
+                        kind: Some(Kind::ListValue(ListValue {
+                            values: vec![DynValue {
+                                kind: Some(Kind::BoolValue(false)),
+                            }],
+                        })),
+                    },
+                ],
+            })),
+        };
+
+        assert_eq!(expected, decoded);
+    }
+
+    #[test]
+    fn well_known_dynamic_err_non_text_key() {
+        let err = sval_protobuf::try_stream_to_protobuf(
+            sval_protobuf::well_known::DynamicValue::new(sval::MapSlice::new(&[([1], 2)])),
+        )
+        .unwrap_err();
+
+        assert_eq!(sval_protobuf::ErrorKind::Stream, err.kind());
+    }
+
+    #[derive(Value)]
+    pub struct MaskInner<'a> {
+        a: i32,
+        b: &'a str,
+    }
+
+    #[derive(Value)]
+    pub struct MaskOuter<'a> {
+        id: i32,
+        inner: MaskInner<'a>,
+        items: &'a [MaskInner<'a>],
+        attributes: &'a sval::MapSlice<&'a str, MaskInner<'a>>,
+        title: &'a str,
+    }
+
+    fn mask_outer() -> MaskOuter<'static> {
+        MaskOuter {
+            id: 42,
+            inner: MaskInner { a: 1, b: "inner" },
+            items: &[MaskInner { a: 2, b: "x" }, MaskInner { a: 3, b: "y" }],
+            attributes: sval::MapSlice::new(&[("k", MaskInner { a: 4, b: "z" })]),
+            title: "title",
+        }
+    }
+
+    #[test]
+    fn field_mask() {
+        #[derive(Value)]
+        pub struct InnerB<'a> {
+            #[sval(index = 2)]
+            b: &'a str,
+        }
+
+        #[derive(Value)]
+        pub struct Projected<'a> {
+            #[sval(index = 1)]
+            id: i32,
+            #[sval(index = 2)]
+            inner: InnerB<'a>,
+            #[sval(index = 3)]
+            items: &'a [InnerB<'a>],
+        }
+
+        let expected = sval_protobuf::stream_to_protobuf(Projected {
+            id: 42,
+            inner: InnerB { b: "inner" },
+            items: &[InnerB { b: "x" }, InnerB { b: "y" }],
+        })
+        .to_vec()
+        .into_owned();
+
+        let by_label = encode_with(
+            sval_protobuf::ProtoBufStream::new()
+                .with_field_mask(
+                    sval_protobuf::well_known::FieldMask::new()
+                        .with_path("id")
+                        .with_path("inner.b")
+                        .with_path("items.b"),
+                )
+                .with_strict(true),
+            mask_outer(),
+        )
+        .unwrap();
+
+        let by_number = encode_with(
+            sval_protobuf::ProtoBufStream::new()
+                .with_field_mask(
+                    sval_protobuf::well_known::FieldMask::new()
+                        .with_number_path(&[1])
+                        .with_number_path(&[2, 2])
+                        .with_path("3.b"),
+                )
+                .with_strict(true),
+            mask_outer(),
+        )
+        .unwrap();
+
+        assert_eq!(expected, by_label);
+        assert_eq!(expected, by_number);
+    }
+
+    #[test]
+    fn field_mask_selected_in_full() {
+        #[derive(Value)]
+        pub struct Projected<'a> {
+            #[sval(index = 2)]
+            inner: MaskInner<'a>,
+            #[sval(index = 4)]
+            attributes: &'a sval::MapSlice<&'a str, MaskInner<'a>>,
+        }
+
+        let outer = mask_outer();
+
+        let expected = sval_protobuf::stream_to_protobuf(Projected {
+            inner: MaskInner { a: 1, b: "inner" },
+            attributes: outer.attributes,
+        })
+        .to_vec()
+        .into_owned();
+
+        // Selecting a field selects all of its nested fields, and maps are always kept in full
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new()
+                .with_field_mask(
+                    sval_protobuf::well_known::FieldMask::new()
+                        .with_path("inner")
+                        .with_path("inner.a")
+                        .with_path("attributes.a"),
+                )
+                .with_strict(true),
+            outer,
+        )
+        .unwrap();
+
+        assert_eq!(expected, sval);
+    }
+
+    #[test]
+    fn field_mask_mixed_paths() {
+        #[derive(Value)]
+        pub struct Projected<'a> {
+            #[sval(index = 2)]
+            inner: MaskInner<'a>,
+        }
+
+        let expected = sval_protobuf::stream_to_protobuf(Projected {
+            inner: MaskInner { a: 1, b: "inner" },
+        })
+        .to_vec()
+        .into_owned();
+
+        // Paths that match the same field by label and by number are merged
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new()
+                .with_field_mask(
+                    sval_protobuf::well_known::FieldMask::new()
+                        .with_path("inner.a")
+                        .with_path("2.b"),
+                )
+                .with_strict(true),
+            mask_outer(),
+        )
+        .unwrap();
+
+        assert_eq!(expected, sval);
+    }
+
+    #[test]
+    fn field_mask_empty() {
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new()
+                .with_field_mask(sval_protobuf::well_known::FieldMask::new())
+                .with_strict(true),
+            mask_outer(),
+        )
+        .unwrap();
+
+        assert_eq!(Vec::<u8>::new(), sval);
+    }
+
+    #[test]
+    fn field_mask_skips_all_values() {
+        #[derive(Value)]
+        pub enum Kind<'a> {
+            Text(&'a str),
+        }
+
+        #[derive(Value)]
+        pub struct Values<'a> {
+            id: i32,
+            big: u128,
+            maybe: Option<f64>,
+            #[sval(data_tag = "sval::tags::NUMBER")]
+            number: &'a str,
+            bytes: &'a sval::BinarySlice,
+            kind: Kind<'a>,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_LEN_PACKED")]
+            packed: &'a [f32],
+        }
+
+        #[derive(Value)]
+        pub struct Projected {
+            id: i32,
+        }
+
+        let expected = sval_protobuf::stream_to_protobuf(Projected { id: 42 })
+            .to_vec()
+            .into_owned();
+
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new()
+                .with_field_mask(sval_protobuf::well_known::FieldMask::new().with_path("id"))
+                .with_strict(true),
+            Values {
+                id: 42,
+                big: u128::MAX,
+                maybe: Some(1.5),
+                number: "1.5",
+                bytes: sval::BinarySlice::new(b"abc"),
+                kind: Kind::Text("text"),
+                packed: &[1.0, 2.0],
+            },
+        )
+        .unwrap();
+
+        assert_eq!(expected, sval);
+    }
+
+    #[test]
+    fn field_mask_project() {
+        use sval_protobuf::decode::{FieldDescriptor, FieldKind, MessageDescriptor};
+
+        const INNER: MessageDescriptor = MessageDescriptor::new(
+            "MaskInner",
+            &[
+                FieldDescriptor::new(1, "a", FieldKind::Int32),
+                FieldDescriptor::new(2, "b", FieldKind::String),
+            ],
+        );
+
+        const OUTER: MessageDescriptor = MessageDescriptor::new(
+            "MaskOuter",
+            &[
+                FieldDescriptor::new(1, "id", FieldKind::Int32),
+                FieldDescriptor::new(2, "inner", FieldKind::Message(&INNER)),
+                FieldDescriptor::new(3, "items", FieldKind::Message(&INNER)).repeated(),
+                FieldDescriptor::new(
+                    4,
+                    "attributes",
+                    FieldKind::Map(&FieldKind::String, &FieldKind::Message(&INNER)),
+                ),
+                FieldDescriptor::new(5, "title", FieldKind::String),
+            ],
+        );
+
+        let encoded = sval_protobuf::stream_to_protobuf(mask_outer());
+
+        let mask = sval_protobuf::well_known::FieldMask::new()
+            .with_path("title")
+            .with_path("inner.b")
+            .with_path("items.a")
+            .with_path("attributes.a");
+
+        let expected = encode_with(
+            sval_protobuf::ProtoBufStream::new()
+                .with_field_mask(mask.clone())
+                .with_strict(true),
+            mask_outer(),
+        )
+        .unwrap();
+
+        let by_descriptor = mask.project_with_descriptor(&encoded, &OUTER).unwrap();
+
+        assert_eq!(expected, by_descriptor.to_vec().into_owned());
+
+        // Without a descriptor, nested messages are kept in full
+        let by_number = sval_protobuf::well_known::FieldMask::new()
+            .with_path("5")
+            .with_path("2.2")
+            .with_path("3.1")
+            .with_path("4")
+            .project(&*encoded.to_vec())
+            .unwrap();
+
+        let expected_in_full = encode_with(
+            sval_protobuf::ProtoBufStream::new()
+                .with_field_mask(
+                    sval_protobuf::well_known::FieldMask::new()
+                        .with_path("title")
+                        .with_path("inner")
+                        .with_path("items")
+                        .with_path("attributes"),
+                )
+                .with_strict(true),
+            mask_outer(),
+        )
+        .unwrap();
+
+        assert_eq!(expected_in_full, by_number.to_vec().into_owned());
+
+        // Labels can't be matched without a descriptor
+        let by_label = mask.project(&encoded).unwrap();
+
+        assert_eq!(Vec::<u8>::new(), by_label.to_vec().into_owned());
+
+        let mixed = sval_protobuf::well_known::FieldMask::new()
+            .with_path("inner.a")
+            .with_path("2.b");
+
+        let expected = encode_with(
+            sval_protobuf::ProtoBufStream::new()
+                .with_field_mask(mixed.clone())
+                .with_strict(true),
+            mask_outer(),
+        )
+        .unwrap();
+
+        let by_descriptor = mixed.project_with_descriptor(&encoded, &OUTER).unwrap();
+
+        assert_eq!(expected, by_descriptor.to_vec().into_owned());
+    }
+
+    #[test]
+    fn field_mask_project_nested_without_descriptor() {
+        #[derive(Value)]
+        pub struct S<'a> {
+            t: &'a str,
+        }
+
+        // Without a descriptor, text is kept in full, even if it could be read as a message
+        for t in ["\u{1}\u{2}zz", "\u{8}\u{1}"] {
+            let encoded = sval_protobuf::stream_to_protobuf(S { t });
+
+            let projected = sval_protobuf::well_known::FieldMask::new()
+                .with_path("1.1")
+                .project(&encoded)
+                .unwrap();
+
+            assert_eq!(
+                encoded.to_vec().into_owned(),
+                projected.to_vec().into_owned()
+            );
+        }
+    }
+
+    #[test]
+    fn wrap_nested_repeated() {
+        let prost = protos::cases::NestedRepeated {
+            a: vec![
+                protos::cases::RepeatedValues { values: vec![1, 2] },
+                protos::cases::RepeatedValues { values: vec![] },
+                protos::cases::RepeatedValues { values: vec![3] },
+            ],
+        }
+        .encode_to_vec();
+
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new()
+                .with_wrap_nested(true)
+                .with_strict(true),
+            (vec![vec![1, 2], vec![], vec![3]],),
+        )
+        .unwrap();
+
+        assert_proto(&prost, &sval);
+
+        let prost = protos::cases::NestedRepeatedMap {
+            a: vec![protos::cases::MapValues {
+                values: BTreeMap::from_iter([("a".to_owned(), 1), ("b".to_owned(), 2)]),
+            }],
+        }
+        .encode_to_vec();
+
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new()
+                .with_wrap_nested(true)
+                .with_strict(true),
+            (vec![BTreeMap::from_iter([("a", 1), ("b", 2)])],),
+        )
+        .unwrap();
+
+        assert_proto(&prost, &sval);
+    }
+
+    #[test]
+    fn wrap_nested_map() {
+        let prost = protos::cases::NestedMap {
+            a: BTreeMap::from_iter([(
+                "a".to_owned(),
+                protos::cases::MapValues {
+                    values: BTreeMap::from_iter([("b".to_owned(), 1)]),
+                },
+            )]),
+        }
+        .encode_to_vec();
+
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new()
+                .with_wrap_nested(true)
+                .with_strict(true),
+            (BTreeMap::from_iter([(
+                "a",
+                BTreeMap::from_iter([("b", 1)]),
+            )]),),
+        )
+        .unwrap();
+
+        assert_proto(&prost, &sval);
+
+        let prost = protos::cases::NestedMapRepeated {
+            a: BTreeMap::from_iter([(
+                "a".to_owned(),
+                protos::cases::RepeatedValues { values: vec![1, 2] },
+            )]),
+        }
+        .encode_to_vec();
+
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new()
+                .with_wrap_nested(true)
+                .with_strict(true),
+            (BTreeMap::from_iter([("a", vec![1, 2])]),),
+        )
+        .unwrap();
+
+        assert_proto(&prost, &sval);
+    }
+
+    #[test]
+    fn wrap_nested_sibling() {
+        #[derive(Value)]
+        pub struct Siblings {
+            a: Vec<Vec<i32>>,
+            b: Vec<i32>,
+        }
+
+        let raw = {
+            let mut buf = ProtoBufMut::new(());
+
+            buf.push_field_len(1);
+            buf.begin_len(());
+            buf.push_field_varint(1);
+            buf.push_varint_uint64(1);
+            buf.end_len();
+
+            buf.push_field_varint(2);
+            buf.push_varint_uint64(2);
+
+            buf.freeze().to_vec().into_owned()
+        };
+
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new()
+                .with_wrap_nested(true)
+                .with_strict(true),
+            Siblings {
+                a: vec![vec![1]],
+                b: vec![2],
+            },
+        )
+        .unwrap();
+
+        assert_proto(&raw, &sval);
+    }
+
+    #[test]
+    fn deterministic_maps() {
+        let prost = protos::cases::Map {
+            a: BTreeMap::from_iter([
+                ("a".to_owned(), 1),
+                ("b".to_owned(), 2),
+                ("c".to_owned(), 3),
+            ]),
+        }
+        .encode_to_vec();
+
+        #[derive(Value)]
+        pub struct Map<'a> {
+            a: &'a sval::MapSlice<&'a str, i32>,
+        }
+
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new().with_deterministic_maps(true),
+            Map {
+                a: sval::MapSlice::new(&[("c", 3), ("a", 1), ("b", 2)]),
+            },
+        )
+        .unwrap();
+
+        assert_proto(&prost, &sval);
+    }
+
+    #[test]
+    fn deterministic_maps_any() {
+        use sval_protobuf::well_known::Any;
+
+        #[derive(Value)]
+        pub struct Map<'a> {
+            a: &'a sval::MapSlice<&'a str, i32>,
+        }
+
+        let prost = protos::cases::Envelope {
+            payload: Some(protos::cases::Any {
+                type_url: "type.googleapis.com/cases.Map".to_owned(),
+                value: protos::cases::Map {
+                    a: BTreeMap::from_iter([("a".to_owned(), 1), ("b".to_owned(), 2)]),
+                }
+                .encode_to_vec(),
+            }),
+        }
+        .encode_to_vec();
+
+        #[derive(Value)]
+        pub struct Envelope<V> {
+            payload: V,
+        }
+
+        // The value of an `Any` is encoded with the same options as the rest of the message
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new().with_deterministic_maps(true),
+            Envelope {
+                payload: Any::new(
+                    "type.googleapis.com/cases.Map",
+                    Map {
+                        a: sval::MapSlice::new(&[("b", 2), ("a", 1)]),
+                    },
+                ),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(prost, sval);
+    }
+
+    #[test]
+    fn deterministic_maps_nested() {
+        let prost = protos::cases::NestedMap {
+            a: BTreeMap::from_iter([
+                (
+                    "a".to_owned(),
+                    protos::cases::MapValues {
+                        values: BTreeMap::from_iter([("x".to_owned(), 1), ("y".to_owned(), 2)]),
+                    },
+                ),
+                (
+                    "b".to_owned(),
+                    protos::cases::MapValues {
+                        values: BTreeMap::from_iter([("z".to_owned(), 3)]),
+                    },
+                ),
+            ]),
+        }
+        .encode_to_vec();
+
+        #[derive(Value)]
+        pub struct MapValues {
+            values: &'static sval::MapSlice<&'static str, i32>,
+        }
+
+        #[derive(Value)]
+        pub struct NestedMap {
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_MAP")]
+            a: Vec<(&'static str, MapValues)>,
+        }
+
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new().with_deterministic_maps(true),
+            NestedMap {
+                a: vec![
+                    (
+                        "b",
+                        MapValues {
+                            values: sval::MapSlice::new(&[("z", 3)]),
+                        },
+                    ),
+                    (
+                        "a",
+                        MapValues {
+                            values: sval::MapSlice::new(&[("y", 2), ("x", 1)]),
+                        },
+                    ),
+                ],
+            },
+        )
+        .unwrap();
+
+        assert_proto(&prost, &sval);
+    }
+
     #[test]
     fn non_contiguous_fields() {
         let prost = {
             let mut buf = Vec::new();
 
-            protos::cases::NonContiguousFields {
-                id: 1,
-                content: "Some content".to_owned(),
-                index: Some(8),
+            protos::cases::NonContiguousFields {
+                id: 1,
+                content: "Some content".to_owned(),
+                index: Some(8),
+            }
+            .encode(&mut buf)
+            .unwrap();
+
+            buf
+        };
+
+        let raw = {
+            let mut buf = ProtoBufMut::new(());
+
+            buf.push_field_varint(4);
+            buf.push_varint_uint64(1);
+
+            buf.push_field_len(11);
+            buf.begin_len(());
+            buf.push(b"Some content");
+            buf.end_len();
+
+            buf.push_field_varint(19);
+            buf.push_varint_uint64(8);
+
+            buf.freeze().to_vec().into_owned()
+        };
+
+        let sval = {
+            #[derive(Value)]
+            pub struct NonContiguousFields<'a> {
+                #[sval(index = 4)]
+                id: i32,
+                #[sval(index = 11)]
+                content: &'a str,
+                #[sval(index = 19)]
+                index: Option<i32>,
+            }
+
+            sval_protobuf::stream_to_protobuf(NonContiguousFields {
+                id: 1,
+                content: "Some content",
+                index: Some(8),
+            })
+            .to_vec()
+            .into_owned()
+        };
+
+        assert_proto(&prost, &raw);
+        assert_proto(&prost, &sval);
+    }
+
+    #[test]
+    fn repeated() {
+        let prost = {
+            let mut buf = Vec::new();
+
+            protos::cases::Repeated {
+                a: vec!["1".to_owned(), "2".to_owned(), "3".to_owned()],
+            }
+            .encode(&mut buf)
+            .unwrap();
+
+            buf
+        };
+
+        let raw = {
+            let mut buf = ProtoBufMut::new(());
+
+            buf.push_field_len(3);
+            buf.begin_len(());
+            buf.push(b"1");
+            buf.end_len();
+
+            buf.push_field_len(3);
+            buf.begin_len(());
+            buf.push(b"2");
+            buf.end_len();
+
+            buf.push_field_len(3);
+            buf.begin_len(());
+            buf.push(b"3");
+            buf.end_len();
+
+            buf.freeze().to_vec().into_owned()
+        };
+
+        let sval1 = {
+            #[derive(Value)]
+            pub struct Repeated<'a> {
+                #[sval(index = 3)]
+                a: &'a [&'a str],
+            }
+
+            sval_protobuf::stream_to_protobuf(Repeated {
+                a: &["1", "2", "3"],
+            })
+            .to_vec()
+            .into_owned()
+        };
+
+        let sval2 = {
+            #[derive(Value)]
+            pub struct Repeated<'a> {
+                #[sval(index = 3)]
+                a: &'a [Option<&'a str>],
+            }
+
+            sval_protobuf::stream_to_protobuf(Repeated {
+                a: &[None, Some("1"), None, Some("2"), Some("3"), None, None],
+            })
+            .to_vec()
+            .into_owned()
+        };
+
+        assert_proto(&prost, &raw);
+        assert_proto(&prost, &sval1);
+        assert_proto(&prost, &sval2);
+    }
+
+    #[test]
+    fn repeated_default() {
+        let prost = {
+            let mut buf = Vec::new();
+
+            protos::cases::Repeated {
+                a: vec!["".to_owned(), "".to_owned(), "".to_owned()],
+            }
+            .encode(&mut buf)
+            .unwrap();
+
+            buf
+        };
+
+        let raw = {
+            let mut buf = ProtoBufMut::new(());
+
+            buf.push_field_len(3);
+            buf.begin_len(());
+            buf.push(b"");
+            buf.end_len();
+
+            buf.push_field_len(3);
+            buf.begin_len(());
+            buf.push(b"");
+            buf.end_len();
+
+            buf.push_field_len(3);
+            buf.begin_len(());
+            buf.push(b"");
+            buf.end_len();
+
+            buf.freeze().to_vec().into_owned()
+        };
+
+        let sval1 = {
+            #[derive(Value)]
+            pub struct Repeated<'a> {
+                #[sval(index = 3)]
+                a: &'a [&'a str],
+            }
+
+            sval_protobuf::stream_to_protobuf(Repeated { a: &["", "", ""] })
+                .to_vec()
+                .into_owned()
+        };
+
+        let sval2 = {
+            #[derive(Value)]
+            pub struct Repeated<'a> {
+                #[sval(index = 3)]
+                a: &'a [Option<&'a str>],
+            }
+
+            sval_protobuf::stream_to_protobuf(Repeated {
+                a: &[Some(""), Some(""), Some("")],
+            })
+            .to_vec()
+            .into_owned()
+        };
+
+        assert_proto(&prost, &raw);
+        assert_proto(&prost, &sval1);
+        assert_proto(&prost, &sval2);
+    }
+
+    #[test]
+    fn repeated_optional_none() {
+        let raw = {
+            let buf = ProtoBufMut::new(());
+
+            buf.freeze().to_vec().into_owned()
+        };
+
+        let sval = {
+            #[derive(Value)]
+            pub struct Repeated<'a> {
+                #[sval(index = 3)]
+                a: &'a [Option<&'a str>],
+            }
+
+            sval_protobuf::stream_to_protobuf(Repeated {
+                a: &[None, None, None],
+            })
+            .to_vec()
+            .into_owned()
+        };
+
+        assert_proto(&raw, &sval);
+    }
+
+    #[test]
+    fn repeated_packed() {
+        let prost = {
+            let mut buf = Vec::new();
+
+            protos::cases::RepeatedPacked { a: vec![1, 2, 3] }
+                .encode(&mut buf)
+                .unwrap();
+
+            buf
+        };
+
+        let raw = {
+            let mut buf = ProtoBufMut::new(());
+
+            buf.push_field_len(1);
+            buf.begin_len(());
+            buf.push_varint_uint64(1);
+            buf.push_varint_uint64(2);
+            buf.push_varint_uint64(3);
+            buf.end_len();
+
+            buf.freeze().to_vec().into_owned()
+        };
+
+        let sval = {
+            #[derive(Value)]
+            pub struct Repeated<'a> {
+                #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_LEN_PACKED")]
+                a: &'a [i32],
+            }
+
+            sval_protobuf::stream_to_protobuf(Repeated { a: &[1, 2, 3] })
+                .to_vec()
+                .into_owned()
+        };
+
+        assert_proto(&prost, &raw);
+        assert_proto(&prost, &sval);
+    }
+
+    #[test]
+    fn repeated_packed_default() {
+        let prost = {
+            let mut buf = Vec::new();
+
+            protos::cases::RepeatedPacked { a: vec![0, 0, 0] }
+                .encode(&mut buf)
+                .unwrap();
+
+            buf
+        };
+
+        let raw = {
+            let mut buf = ProtoBufMut::new(());
+
+            buf.push_field_len(1);
+            buf.begin_len(());
+            buf.push_varint_uint64(0);
+            buf.push_varint_uint64(0);
+            buf.push_varint_uint64(0);
+            buf.end_len();
+
+            buf.freeze().to_vec().into_owned()
+        };
+
+        let sval = {
+            #[derive(Value)]
+            pub struct Repeated<'a> {
+                #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_LEN_PACKED")]
+                a: &'a [i32],
+            }
+
+            sval_protobuf::stream_to_protobuf(Repeated { a: &[0, 0, 0] })
+                .to_vec()
+                .into_owned()
+        };
+
+        assert_proto(&prost, &raw);
+        assert_proto(&prost, &sval);
+    }
+
+    #[test]
+    fn repeated_packed_exact_len() {
+        #[derive(Value)]
+        #[sval(tag = "sval_protobuf::tags::PROTOBUF_I32")]
+        pub struct Fixed(u32);
+
+        #[derive(Value)]
+        pub struct Repeated<'a> {
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_LEN_PACKED")]
+            a: &'a [f64],
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_LEN_PACKED")]
+            b: &'a [Fixed],
+        }
+
+        let raw = {
+            let mut buf = ProtoBufMut::new(());
+
+            buf.push_field_len(1);
+            buf.push_len_varint_uint64(16);
+            buf.push_i64_double(1.5);
+            buf.push_i64_double(-2.5);
+
+            buf.push_field_len(2);
+            buf.push_len_varint_uint64(8);
+            buf.push_i32_fixed32(1);
+            buf.push_i32_fixed32(2);
+
+            buf.freeze().to_vec().into_owned()
+        };
+
+        let sval = sval_protobuf::stream_to_protobuf(Repeated {
+            a: &[1.5, -2.5],
+            b: &[Fixed(1), Fixed(2)],
+        });
+
+        // No length-prefixed chunks are needed, so the buffer is already contiguous
+        assert!(matches!(sval.to_vec(), std::borrow::Cow::Borrowed(_)));
+
+        assert_proto(&raw, &sval.to_vec());
+    }
+
+    #[test]
+    fn repeated_packed_exact_len_mismatch() {
+        struct Lying;
+
+        impl sval::Value for Lying {
+            fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(
+                &'sval self,
+                stream: &mut S,
+            ) -> sval::Result {
+                stream.tagged_begin(Some(&sval_protobuf::tags::PROTOBUF_LEN_PACKED), None, None)?;
+
+                stream.seq_begin(Some(3))?;
+
+                stream.seq_value_begin()?;
+                stream.f32(1.5)?;
+                stream.seq_value_end()?;
+
+                stream.seq_end()?;
+
+                stream.tagged_end(Some(&sval_protobuf::tags::PROTOBUF_LEN_PACKED), None, None)
+            }
+        }
+
+        let raw = {
+            let mut buf = ProtoBufMut::new(());
+
+            buf.push_field_len(1);
+            buf.push_len_varint_uint64(4);
+            buf.push_i32_float(1.5);
+
+            buf.freeze().to_vec().into_owned()
+        };
+
+        // The length is fixed up from the values that were actually written
+        let sval = sval_protobuf::try_stream_to_protobuf((Lying,)).unwrap();
+
+        assert_proto(&raw, &sval.to_vec());
+    }
+
+    #[test]
+    fn map() {
+        let prost = {
+            let mut buf = Vec::new();
+
+            protos::cases::Map {
+                a: {
+                    let mut map = BTreeMap::new();
+                    map.insert("a".to_owned(), 1);
+                    map.insert("b".to_owned(), 2);
+                    map.insert("c".to_owned(), 3);
+                    map
+                },
+            }
+            .encode(&mut buf)
+            .unwrap();
+
+            buf
+        };
+
+        let raw = {
+            let mut buf = ProtoBufMut::new(());
+
+            buf.push_field_len(1);
+            buf.begin_len(());
+            buf.push_field_len(1);
+            buf.begin_len(());
+            buf.push(b"a");
+            buf.end_len();
+            buf.push_field_varint(2);
+            buf.push_varint_uint64(1);
+            buf.end_len();
+
+            buf.push_field_len(1);
+            buf.begin_len(());
+            buf.push_field_len(1);
+            buf.begin_len(());
+            buf.push(b"b");
+            buf.end_len();
+            buf.push_field_varint(2);
+            buf.push_varint_uint64(2);
+            buf.end_len();
+
+            buf.push_field_len(1);
+            buf.begin_len(());
+            buf.push_field_len(1);
+            buf.begin_len(());
+            buf.push(b"c");
+            buf.end_len();
+            buf.push_field_varint(2);
+            buf.push_varint_uint64(3);
+            buf.end_len();
+
+            buf.freeze().to_vec().into_owned()
+        };
+
+        let sval = {
+            #[derive(Value)]
+            pub struct Map<'a> {
+                a: &'a sval::MapSlice<&'a str, i32>,
+            }
+
+            sval_protobuf::stream_to_protobuf(Map {
+                a: sval::MapSlice::new(&[("a", 1), ("b", 2), ("c", 3)]),
+            })
+            .to_vec()
+            .into_owned()
+        };
+
+        assert_proto(&prost, &raw);
+        assert_proto(&prost, &sval);
+    }
+
+    #[test]
+    fn map_tagged_values() {
+        #[derive(Value)]
+        pub struct TaggedMaps<'a> {
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_VARINT_SIGNED")]
+            a: &'a sval::MapSlice<&'a str, i32>,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_I64")]
+            b: &'a sval::MapSlice<&'a str, i64>,
+        }
+
+        let prost = protos::cases::TaggedMaps {
+            a: [("a".to_owned(), -1)].into_iter().collect(),
+            b: [("b".to_owned(), -2)].into_iter().collect(),
+        }
+        .encode_to_vec();
+
+        let sval = sval_protobuf::stream_to_protobuf(TaggedMaps {
+            a: sval::MapSlice::new(&[("a", -1)]),
+            b: sval::MapSlice::new(&[("b", -2)]),
+        });
+
+        assert_eq!(prost, sval.to_vec().into_owned());
+    }
+
+    #[test]
+    fn map_tagged_tuples() {
+        let prost = protos::cases::Map {
+            a: BTreeMap::from_iter([("a".to_owned(), 1), ("b".to_owned(), 2)]),
+        }
+        .encode_to_vec();
+
+        let sval = {
+            #[derive(Value)]
+            pub struct Map<'a> {
+                #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_MAP")]
+                a: &'a [(&'a str, i32)],
+            }
+
+            sval_protobuf::stream_to_protobuf(Map {
+                a: &[("a", 1), ("b", 2)],
+            })
+            .to_vec()
+            .into_owned()
+        };
+
+        assert_proto(&prost, &sval);
+    }
+
+    #[test]
+    fn map_tagged_records() {
+        let prost = protos::cases::NestedMap {
+            a: BTreeMap::from_iter([(
+                "a".to_owned(),
+                protos::cases::MapValues {
+                    values: BTreeMap::from_iter([("b".to_owned(), 1), ("c".to_owned(), 2)]),
+                },
+            )]),
+        }
+        .encode_to_vec();
+
+        let sval = {
+            // The indexes of entries don't matter, only their order
+            #[derive(Value)]
+            pub struct Entry<K, V> {
+                #[sval(index = 3)]
+                key: K,
+                #[sval(index = 7)]
+                value: V,
+            }
+
+            #[derive(Value)]
+            pub struct MapValues {
+                #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_MAP")]
+                values: Vec<Entry<&'static str, i32>>,
+            }
+
+            #[derive(Value)]
+            pub struct NestedMap {
+                #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_MAP")]
+                a: Vec<Entry<&'static str, MapValues>>,
+            }
+
+            sval_protobuf::stream_to_protobuf(NestedMap {
+                a: vec![Entry {
+                    key: "a",
+                    value: MapValues {
+                        values: vec![Entry { key: "b", value: 1 }, Entry { key: "c", value: 2 }],
+                    },
+                }],
+            })
+            .to_vec()
+            .into_owned()
+        };
+
+        assert_proto(&prost, &sval);
+    }
+
+    #[test]
+    fn map_tagged_err_unsupported() {
+        #[derive(Value)]
+        pub struct Map<'a> {
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_MAP")]
+            a: &'a [(&'a str, i32, i32)],
+        }
+
+        let err = sval_protobuf::try_stream_to_protobuf(Map { a: &[("a", 1, 2)] }).unwrap_err();
+
+        assert_eq!(sval_protobuf::ErrorKind::Unsupported, err.kind());
+
+        #[derive(Value)]
+        pub struct Tagged<T> {
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_MAP")]
+            a: T,
+        }
+
+        // Values that aren't pairs are never encoded as plain repeated fields
+        for err in [
+            sval_protobuf::try_stream_to_protobuf(Tagged {
+                a: &[1, 2] as &[i32],
+            }),
+            sval_protobuf::try_stream_to_protobuf(Tagged {
+                a: &["a"] as &[&str],
+            }),
+            sval_protobuf::try_stream_to_protobuf(Tagged {
+                a: &[("a",)] as &[(&str,)],
+            }),
+            sval_protobuf::try_stream_to_protobuf(Tagged {
+                a: &[&[("a", 1)] as &[(&str, i32)]] as &[&[(&str, i32)]],
+            }),
+        ] {
+            assert_eq!(
+                sval_protobuf::ErrorKind::Unsupported,
+                err.unwrap_err().kind()
+            );
+        }
+    }
+
+    #[test]
+    fn map_default() {
+        let raw = {
+            let mut buf = ProtoBufMut::new(());
+
+            buf.push_field_len(1);
+            buf.begin_len(());
+            buf.push_field_len(1);
+            buf.begin_len(());
+            buf.push(b"");
+            buf.end_len();
+            buf.push_field_varint(2);
+            buf.push_varint_uint64(0);
+            buf.end_len();
+
+            buf.freeze().to_vec().into_owned()
+        };
+
+        let sval = {
+            #[derive(Value)]
+            pub struct Map<'a> {
+                a: &'a sval::MapSlice<&'a str, i32>,
+            }
+
+            sval_protobuf::stream_to_protobuf(Map {
+                a: sval::MapSlice::new(&[("", 0)]),
+            })
+            .to_vec()
+            .into_owned()
+        };
+
+        assert_proto(&raw, &sval);
+    }
+
+    #[test]
+    fn r#enum() {
+        let prost = {
+            let mut buf = Vec::new();
+
+            protos::cases::Enum {
+                value: protos::cases::EnumInner::B as i32,
             }
             .encode(&mut buf)
             .unwrap();
@@ -678,35 +2555,29 @@ mod tests {
         let raw = {
             let mut buf = ProtoBufMut::new(());
 
-            buf.push_field_varint(4);
-            buf.push_varint_uint64(1);
-
-            buf.push_field_len(11);
-            buf.begin_len(());
-            buf.push(b"Some content");
-            buf.end_len();
-
-            buf.push_field_varint(19);
-            buf.push_varint_uint64(8);
+            buf.push_field_varint(1);
+            buf.push_varint_sint64(-3);
 
             buf.freeze().to_vec().into_owned()
         };
 
         let sval = {
             #[derive(Value)]
-            pub struct NonContiguousFields<'a> {
-                #[sval(index = 4)]
-                id: i32,
-                #[sval(index = 11)]
-                content: &'a str,
-                #[sval(index = 19)]
-                index: Option<i32>,
+            #[repr(i32)]
+            #[allow(dead_code)]
+            pub enum EnumInner {
+                A = -1,
+                B = -3,
+                C = -6,
             }
 
-            sval_protobuf::stream_to_protobuf(NonContiguousFields {
-                id: 1,
-                content: "Some content",
-                index: Some(8),
+            #[derive(Value)]
+            pub struct Enum {
+                value: EnumInner,
+            }
+
+            sval_protobuf::stream_to_protobuf(Enum {
+                value: EnumInner::B,
             })
             .to_vec()
             .into_owned()
@@ -717,12 +2588,12 @@ mod tests {
     }
 
     #[test]
-    fn repeated() {
+    fn oneof() {
         let prost = {
             let mut buf = Vec::new();
 
-            protos::cases::Repeated {
-                a: vec!["1".to_owned(), "2".to_owned(), "3".to_owned()],
+            protos::cases::Oneof {
+                value: Some(protos::cases::oneof::Value::Boolean(true)),
             }
             .encode(&mut buf)
             .unwrap();
@@ -733,33 +2604,142 @@ mod tests {
         let raw = {
             let mut buf = ProtoBufMut::new(());
 
-            buf.push_field_len(3);
-            buf.begin_len(());
-            buf.push(b"1");
-            buf.end_len();
+            buf.push_field_varint(2);
+            buf.push_varint_bool(true);
 
-            buf.push_field_len(3);
-            buf.begin_len(());
-            buf.push(b"2");
-            buf.end_len();
+            buf.freeze().to_vec().into_owned()
+        };
 
-            buf.push_field_len(3);
-            buf.begin_len(());
-            buf.push(b"3");
-            buf.end_len();
+        let sval1 = {
+            #[derive(Value)]
+            #[allow(dead_code)]
+            pub enum Value<'a> {
+                Number(i32),
+                Boolean(bool),
+                Text(&'a str),
+            }
+
+            #[derive(Value)]
+            pub struct Oneof<'a> {
+                #[sval(flatten)]
+                value: Value<'a>,
+            }
+
+            sval_protobuf::stream_to_protobuf(Oneof {
+                value: Value::Boolean(true),
+            })
+            .to_vec()
+            .into_owned()
+        };
+
+        let sval2 = {
+            #[derive(Value)]
+            #[allow(dead_code)]
+            pub enum Value<'a> {
+                Number(i32),
+                Boolean(bool),
+                Text(&'a str),
+            }
+
+            sval_protobuf::stream_to_protobuf(Value::Boolean(true))
+                .to_vec()
+                .into_owned()
+        };
+
+        assert_proto(&prost, &raw);
+        assert_proto(&prost, &sval1);
+        assert_proto(&prost, &sval2);
+    }
+
+    #[test]
+    fn oneof_default() {
+        let prost = {
+            let mut buf = Vec::new();
+
+            protos::cases::Oneof {
+                value: Some(protos::cases::oneof::Value::Boolean(false)),
+            }
+            .encode(&mut buf)
+            .unwrap();
+
+            buf
+        };
+
+        let raw = {
+            let mut buf = ProtoBufMut::new(());
+
+            buf.push_field_varint(2);
+            buf.push_varint_bool(false);
+
+            buf.freeze().to_vec().into_owned()
+        };
+
+        let sval1 = {
+            #[derive(Value)]
+            #[allow(dead_code)]
+            pub enum Value<'a> {
+                Number(i32),
+                Boolean(bool),
+                Text(&'a str),
+            }
+
+            #[derive(Value)]
+            pub struct Oneof<'a> {
+                #[sval(flatten)]
+                value: Value<'a>,
+            }
+
+            sval_protobuf::stream_to_protobuf(Oneof {
+                value: Value::Boolean(false),
+            })
+            .to_vec()
+            .into_owned()
+        };
+
+        let sval2 = {
+            #[derive(Value)]
+            #[allow(dead_code)]
+            pub enum Value<'a> {
+                Number(i32),
+                Boolean(bool),
+                Text(&'a str),
+            }
+
+            sval_protobuf::stream_to_protobuf(Value::Boolean(false))
+                .to_vec()
+                .into_owned()
+        };
+
+        assert_proto(&prost, &raw);
+        assert_proto(&prost, &sval1);
+        assert_proto(&prost, &sval2);
+    }
+
+    #[test]
+    fn oneof_optional_none() {
+        let raw = {
+            let buf = ProtoBufMut::new(());
 
             buf.freeze().to_vec().into_owned()
         };
 
-        let sval1 = {
+        let sval1 = {
+            #[derive(Value)]
+            #[allow(dead_code)]
+            pub enum Value<'a> {
+                Number(Option<i32>),
+                Boolean(Option<bool>),
+                Text(Option<&'a str>),
+            }
+
             #[derive(Value)]
-            pub struct Repeated<'a> {
-                #[sval(index = 3)]
-                a: &'a [&'a str],
+            pub struct Oneof<'a> {
+                #[sval(flatten)]
+                value: Value<'a>,
             }
 
-            sval_protobuf::stream_to_protobuf(Repeated {
-                a: &["1", "2", "3"],
+            sval_protobuf::stream_to_protobuf(Oneof {
+                value: Value::Boolean(None),
             })
             .to_vec()
             .into_owned()
@@ -767,30 +2747,31 @@ mod tests {
 
         let sval2 = {
             #[derive(Value)]
-            pub struct Repeated<'a> {
-                #[sval(index = 3)]
-                a: &'a [Option<&'a str>],
+            #[allow(dead_code)]
+            pub enum Value<'a> {
+                Number(Option<i32>),
+                Boolean(Option<bool>),
+                Text(Option<&'a str>),
             }
 
-            sval_protobuf::stream_to_protobuf(Repeated {
-                a: &[None, Some("1"), None, Some("2"), Some("3"), None, None],
-            })
-            .to_vec()
-            .into_owned()
+            sval_protobuf::stream_to_protobuf(Value::Boolean(None))
+                .to_vec()
+                .into_owned()
         };
 
-        assert_proto(&prost, &raw);
-        assert_proto(&prost, &sval1);
-        assert_proto(&prost, &sval2);
+        assert_proto(&raw, &sval1);
+        assert_proto(&raw, &sval2);
     }
 
     #[test]
-    fn repeated_default() {
+    fn oneof_nested() {
         let prost = {
             let mut buf = Vec::new();
 
-            protos::cases::Repeated {
-                a: vec!["".to_owned(), "".to_owned(), "".to_owned()],
+            protos::cases::NestedOneof {
+                a: Some(protos::cases::Oneof {
+                    value: Some(protos::cases::oneof::Value::Boolean(true)),
+                }),
             }
             .encode(&mut buf)
             .unwrap();
@@ -801,19 +2782,10 @@ mod tests {
         let raw = {
             let mut buf = ProtoBufMut::new(());
 
-            buf.push_field_len(3);
-            buf.begin_len(());
-            buf.push(b"");
-            buf.end_len();
-
-            buf.push_field_len(3);
-            buf.begin_len(());
-            buf.push(b"");
-            buf.end_len();
-
-            buf.push_field_len(3);
+            buf.push_field_len(1);
             buf.begin_len(());
-            buf.push(b"");
+            buf.push_field_varint(2);
+            buf.push_varint_bool(true);
             buf.end_len();
 
             buf.freeze().to_vec().into_owned()
@@ -821,68 +2793,75 @@ mod tests {
 
         let sval1 = {
             #[derive(Value)]
-            pub struct Repeated<'a> {
-                #[sval(index = 3)]
-                a: &'a [&'a str],
+            #[allow(dead_code)]
+            pub enum Value<'a> {
+                Number(i32),
+                Boolean(bool),
+                Text(&'a str),
             }
 
-            sval_protobuf::stream_to_protobuf(Repeated { a: &["", "", ""] })
-                .to_vec()
-                .into_owned()
-        };
+            #[derive(Value)]
+            pub struct Oneof<'a> {
+                #[sval(flatten)]
+                value: Value<'a>,
+            }
 
-        let sval2 = {
             #[derive(Value)]
-            pub struct Repeated<'a> {
-                #[sval(index = 3)]
-                a: &'a [Option<&'a str>],
+            pub struct NestedOneof<'a> {
+                a: Oneof<'a>,
             }
 
-            sval_protobuf::stream_to_protobuf(Repeated {
-                a: &[Some(""), Some(""), Some("")],
+            sval_protobuf::stream_to_protobuf(NestedOneof {
+                a: Oneof {
+                    value: Value::Boolean(true),
+                },
             })
             .to_vec()
             .into_owned()
         };
 
-        assert_proto(&prost, &raw);
-        assert_proto(&prost, &sval1);
-        assert_proto(&prost, &sval2);
-    }
-
-    #[test]
-    fn repeated_optional_none() {
-        let raw = {
-            let buf = ProtoBufMut::new(());
-
-            buf.freeze().to_vec().into_owned()
-        };
+        let sval2 = {
+            #[derive(Value)]
+            #[allow(dead_code)]
+            pub enum Value<'a> {
+                Number(i32),
+                Boolean(bool),
+                Text(&'a str),
+            }
 
-        let sval = {
             #[derive(Value)]
-            pub struct Repeated<'a> {
-                #[sval(index = 3)]
-                a: &'a [Option<&'a str>],
+            pub struct NestedOneof<'a> {
+                value: Value<'a>,
             }
 
-            sval_protobuf::stream_to_protobuf(Repeated {
-                a: &[None, None, None],
+            sval_protobuf::stream_to_protobuf(NestedOneof {
+                value: Value::Boolean(true),
             })
             .to_vec()
             .into_owned()
         };
 
-        assert_proto(&raw, &sval);
+        assert_proto(&prost, &raw);
+        assert_proto(&prost, &sval1);
+        assert_proto(&prost, &sval2);
     }
 
     #[test]
-    fn repeated_packed() {
+    fn nested() {
         let prost = {
             let mut buf = Vec::new();
 
-            protos::cases::RepeatedPacked { a: vec![1, 2, 3] }
-                .encode(&mut buf)
-                .unwrap();
+            protos::cases::Nested {
+                a: Some(protos::cases::NestedInner {
+                    a: Some(protos::cases::Optional { a: Some(1) }),
+                    b: b"Some bytes".to_vec(),
+                    c: 2,
+                }),
+                b: "Some text".to_owned(),
+                c: 2,
+            }
+            .encode(&mut buf)
+            .unwrap();
 
             buf
         };
@@ -890,67 +2869,73 @@ mod tests {
         let raw = {
             let mut buf = ProtoBufMut::new(());
 
+            // a: NestedInner
+            buf.push_field_len(1);
+            buf.begin_len(());
+
+            // a: Basic
             buf.push_field_len(1);
             buf.begin_len(());
+            buf.push_field_varint(1);
             buf.push_varint_uint64(1);
-            buf.push_varint_uint64(2);
-            buf.push_varint_uint64(3);
             buf.end_len();
 
-            buf.freeze().to_vec().into_owned()
-        };
-
-        let sval = {
-            #[derive(Value)]
-            pub struct Repeated<'a> {
-                #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_LEN_PACKED")]
-                a: &'a [i32],
-            }
-
-            sval_protobuf::stream_to_protobuf(Repeated { a: &[1, 2, 3] })
-                .to_vec()
-                .into_owned()
-        };
-
-        assert_proto(&prost, &raw);
-        assert_proto(&prost, &sval);
-    }
-
-    #[test]
-    fn repeated_packed_default() {
-        let prost = {
-            let mut buf = Vec::new();
-
-            protos::cases::RepeatedPacked { a: vec![0, 0, 0] }
-                .encode(&mut buf)
-                .unwrap();
+            // b: bytes
+            buf.push_field_len(2);
+            buf.begin_len(());
+            buf.push(b"Some bytes");
+            buf.end_len();
 
-            buf
-        };
+            // c: int64
+            buf.push_field_varint(3);
+            buf.push_varint_uint64(2);
 
-        let raw = {
-            let mut buf = ProtoBufMut::new(());
+            buf.end_len();
 
-            buf.push_field_len(1);
+            // b: string
+            buf.push_field_len(2);
             buf.begin_len(());
-            buf.push_varint_uint64(0);
-            buf.push_varint_uint64(0);
-            buf.push_varint_uint64(0);
+            buf.push(b"Some text");
             buf.end_len();
 
+            // c: int64
+            buf.push_field_varint(3);
+            buf.push_varint_uint64(2);
+
             buf.freeze().to_vec().into_owned()
         };
 
         let sval = {
             #[derive(Value)]
-            pub struct Repeated<'a> {
-                #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_LEN_PACKED")]
-                a: &'a [i32],
+            pub struct Nested<'a> {
+                a: NestedInner<'a>,
+                b: &'a str,
+                c: i32,
             }
 
-            sval_protobuf::stream_to_protobuf(Repeated { a: &[0, 0, 0] })
-                .to_vec()
-                .into_owned()
+            #[derive(Value)]
+            pub struct NestedInner<'a> {
+                a: Optional,
+                b: &'a sval::BinarySlice,
+                c: i32,
+            }
+
+            #[derive(Value)]
+            pub struct Optional {
+                a: Option<i32>,
+            }
+
+            sval_protobuf::stream_to_protobuf(Nested {
+                a: NestedInner {
+                    a: Optional { a: Some(1) },
+                    b: sval::BinarySlice::new(b"Some bytes"),
+                    c: 2,
+                },
+                b: "Some text",
+                c: 2,
+            })
+            .to_vec()
+            .into_owned()
         };
 
         assert_proto(&prost, &raw);
@@ -958,934 +2943,1237 @@ mod tests {
     }
 
     #[test]
-    fn map() {
-        let prost = {
-            let mut buf = Vec::new();
-
-            protos::cases::Map {
-                a: {
-                    let mut map = BTreeMap::new();
-                    map.insert("a".to_owned(), 1);
-                    map.insert("b".to_owned(), 2);
-                    map.insert("c".to_owned(), 3);
-                    map
-                },
-            }
-            .encode(&mut buf)
-            .unwrap();
+    fn exotic_enum_tuple() {
+        #[derive(Value)]
+        #[allow(dead_code)]
+        pub enum Enum {
+            Tag,
+            Tuple(i32, i32),
+        }
 
-            buf
-        };
+        #[derive(Value)]
+        pub struct Struct {
+            a: i32,
+            b: Enum,
+        }
 
         let raw = {
             let mut buf = ProtoBufMut::new(());
 
-            buf.push_field_len(1);
-            buf.begin_len(());
-            buf.push_field_len(1);
+            // a
+            buf.push_field_varint(1);
+            buf.push_varint_uint64(2);
+
+            // b
+            buf.push_field_len(2);
             buf.begin_len(());
-            buf.push(b"a");
-            buf.end_len();
-            buf.push_field_varint(2);
-            buf.push_varint_uint64(1);
-            buf.end_len();
 
-            buf.push_field_len(1);
-            buf.begin_len(());
-            buf.push_field_len(1);
+            // Enum::Tuple
+            buf.push_field_len(2);
             buf.begin_len(());
-            buf.push(b"b");
-            buf.end_len();
+
+            // Enum::Tuple.0
+            buf.push_field_varint(1);
+            buf.push_varint_uint64(3);
+
+            // Enum::Tuple.1
             buf.push_field_varint(2);
-            buf.push_varint_uint64(2);
-            buf.end_len();
+            buf.push_varint_uint64(4);
 
-            buf.push_field_len(1);
-            buf.begin_len(());
-            buf.push_field_len(1);
-            buf.begin_len(());
-            buf.push(b"c");
             buf.end_len();
-            buf.push_field_varint(2);
-            buf.push_varint_uint64(3);
+
             buf.end_len();
 
             buf.freeze().to_vec().into_owned()
         };
 
         let sval = {
-            #[derive(Value)]
-            pub struct Map<'a> {
-                a: &'a sval::MapSlice<&'a str, i32>,
-            }
-
-            sval_protobuf::stream_to_protobuf(Map {
-                a: sval::MapSlice::new(&[("a", 1), ("b", 2), ("c", 3)]),
+            sval_protobuf::stream_to_protobuf(Struct {
+                a: 2,
+                b: Enum::Tuple(3, 4),
             })
             .to_vec()
             .into_owned()
         };
 
-        assert_proto(&prost, &raw);
-        assert_proto(&prost, &sval);
+        assert_proto(&raw, &sval);
     }
 
     #[test]
-    fn map_default() {
+    fn exotic_tagged_empty() {
+        struct Tagged;
+
+        impl sval::Value for Tagged {
+            fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(
+                &'sval self,
+                stream: &mut S,
+            ) -> sval::Result {
+                stream.tagged_begin(None, Some(&sval::Label::new("Tagged")), None)?;
+                stream.tagged_end(None, Some(&sval::Label::new("Tagged")), None)
+            }
+        }
+
+        #[derive(Value)]
+        pub struct Struct {
+            a: i32,
+            b: Tagged,
+            c: i32,
+        }
+
         let raw = {
             let mut buf = ProtoBufMut::new(());
 
-            buf.push_field_len(1);
-            buf.begin_len(());
-            buf.push_field_len(1);
-            buf.begin_len(());
-            buf.push(b"");
-            buf.end_len();
-            buf.push_field_varint(2);
-            buf.push_varint_uint64(0);
-            buf.end_len();
+            // a
+            buf.push_field_varint(1);
+            buf.push_varint_uint64(2);
+
+            // c
+            buf.push_field_varint(3);
+            buf.push_varint_uint64(5);
 
             buf.freeze().to_vec().into_owned()
         };
 
         let sval = {
-            #[derive(Value)]
-            pub struct Map<'a> {
-                a: &'a sval::MapSlice<&'a str, i32>,
-            }
-
-            sval_protobuf::stream_to_protobuf(Map {
-                a: sval::MapSlice::new(&[("", 0)]),
+            sval_protobuf::stream_to_protobuf(Struct {
+                a: 2,
+                b: Tagged,
+                c: 5,
             })
             .to_vec()
             .into_owned()
         };
 
         assert_proto(&raw, &sval);
+
+        let raw = {
+            let buf = ProtoBufMut::new(());
+
+            buf.freeze().to_vec().into_owned()
+        };
+
+        let sval = {
+            sval_protobuf::stream_to_protobuf(Tagged)
+                .to_vec()
+                .into_owned()
+        };
+
+        assert_proto(&raw, &sval);
     }
 
     #[test]
-    fn r#enum() {
-        let prost = {
-            let mut buf = Vec::new();
+    fn exotic_enum_empty() {
+        struct Enum;
 
-            protos::cases::Enum {
-                value: protos::cases::EnumInner::B as i32,
+        impl sval::Value for Enum {
+            fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(
+                &'sval self,
+                stream: &mut S,
+            ) -> sval::Result {
+                stream.enum_begin(None, Some(&sval::Label::new("Enum")), None)?;
+                stream.enum_end(None, Some(&sval::Label::new("Enum")), None)
             }
-            .encode(&mut buf)
-            .unwrap();
+        }
 
-            buf
-        };
+        #[derive(Value)]
+        pub struct Struct {
+            a: i32,
+            b: Enum,
+            c: i32,
+        }
 
         let raw = {
             let mut buf = ProtoBufMut::new(());
 
+            // a
             buf.push_field_varint(1);
-            buf.push_varint_sint64(-3);
+            buf.push_varint_uint64(2);
+
+            // c
+            buf.push_field_varint(3);
+            buf.push_varint_uint64(5);
 
             buf.freeze().to_vec().into_owned()
         };
 
         let sval = {
-            #[derive(Value)]
-            #[repr(i32)]
-            #[allow(dead_code)]
-            pub enum EnumInner {
-                A = -1,
-                B = -3,
-                C = -6,
-            }
-
-            #[derive(Value)]
-            pub struct Enum {
-                value: EnumInner,
-            }
-
-            sval_protobuf::stream_to_protobuf(Enum {
-                value: EnumInner::B,
+            sval_protobuf::stream_to_protobuf(Struct {
+                a: 2,
+                b: Enum,
+                c: 5,
             })
             .to_vec()
             .into_owned()
         };
 
-        assert_proto(&prost, &raw);
-        assert_proto(&prost, &sval);
+        assert_proto(&raw, &sval);
+
+        let raw = {
+            let buf = ProtoBufMut::new(());
+
+            buf.freeze().to_vec().into_owned()
+        };
+
+        let sval = {
+            sval_protobuf::stream_to_protobuf(Enum)
+                .to_vec()
+                .into_owned()
+        };
+
+        assert_proto(&raw, &sval);
     }
 
     #[test]
-    fn oneof() {
-        let prost = {
-            let mut buf = Vec::new();
+    fn exotic_nested_enum_empty() {
+        struct Outer;
 
-            protos::cases::Oneof {
-                value: Some(protos::cases::oneof::Value::Boolean(true)),
+        impl sval::Value for Outer {
+            fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(
+                &'sval self,
+                stream: &mut S,
+            ) -> sval::Result {
+                stream.enum_begin(None, Some(&sval::Label::new("Outer")), None)?;
+                stream.enum_begin(
+                    None,
+                    Some(&sval::Label::new("Inner")),
+                    Some(&sval::Index::new(0).with_tag(&sval::tags::VALUE_OFFSET)),
+                )?;
+                stream.enum_begin(
+                    None,
+                    Some(&sval::Label::new("Core")),
+                    Some(&sval::Index::new(0).with_tag(&sval::tags::VALUE_OFFSET)),
+                )?;
+                stream.enum_end(
+                    None,
+                    Some(&sval::Label::new("Core")),
+                    Some(&sval::Index::new(0).with_tag(&sval::tags::VALUE_OFFSET)),
+                )?;
+                stream.enum_end(
+                    None,
+                    Some(&sval::Label::new("Inner")),
+                    Some(&sval::Index::new(0).with_tag(&sval::tags::VALUE_OFFSET)),
+                )?;
+                stream.enum_end(None, Some(&sval::Label::new("Outer")), None)
             }
-            .encode(&mut buf)
-            .unwrap();
+        }
 
-            buf
-        };
+        #[derive(Value)]
+        pub struct Struct {
+            a: i32,
+            b: Outer,
+            c: i32,
+        }
 
         let raw = {
             let mut buf = ProtoBufMut::new(());
 
-            buf.push_field_varint(2);
-            buf.push_varint_bool(true);
+            // a
+            buf.push_field_varint(1);
+            buf.push_varint_uint64(2);
 
-            buf.freeze().to_vec().into_owned()
-        };
+            // b
+            buf.push_field_len(2);
+            buf.begin_len(());
 
-        let sval1 = {
-            #[derive(Value)]
-            #[allow(dead_code)]
-            pub enum Value<'a> {
-                Number(i32),
-                Boolean(bool),
-                Text(&'a str),
-            }
+            buf.push_field_len(1);
+            buf.begin_len(());
+            buf.end_len();
 
-            #[derive(Value)]
-            pub struct Oneof<'a> {
-                #[sval(flatten)]
-                value: Value<'a>,
-            }
+            buf.end_len();
+
+            // c
+            buf.push_field_varint(3);
+            buf.push_varint_uint64(5);
 
-            sval_protobuf::stream_to_protobuf(Oneof {
-                value: Value::Boolean(true),
+            buf.freeze().to_vec().into_owned()
+        };
+
+        let sval = {
+            sval_protobuf::stream_to_protobuf(Struct {
+                a: 2,
+                b: Outer,
+                c: 5,
             })
             .to_vec()
             .into_owned()
         };
 
-        let sval2 = {
-            #[derive(Value)]
-            #[allow(dead_code)]
-            pub enum Value<'a> {
-                Number(i32),
-                Boolean(bool),
-                Text(&'a str),
-            }
+        assert_proto(&raw, &sval);
 
-            sval_protobuf::stream_to_protobuf(Value::Boolean(true))
+        let raw = {
+            let mut buf = ProtoBufMut::new(());
+
+            buf.push_field_len(1);
+
+            buf.begin_len(());
+            buf.end_len();
+
+            buf.end_len();
+
+            buf.freeze().to_vec().into_owned()
+        };
+
+        let sval = {
+            sval_protobuf::stream_to_protobuf(Outer)
                 .to_vec()
                 .into_owned()
         };
 
-        assert_proto(&prost, &raw);
-        assert_proto(&prost, &sval1);
-        assert_proto(&prost, &sval2);
+        assert_proto(&raw, &sval);
     }
 
     #[test]
-    fn oneof_default() {
-        let prost = {
-            let mut buf = Vec::new();
-
-            protos::cases::Oneof {
-                value: Some(protos::cases::oneof::Value::Boolean(false)),
-            }
-            .encode(&mut buf)
-            .unwrap();
-
-            buf
-        };
-
+    fn pre_encoded_len() {
         let raw = {
             let mut buf = ProtoBufMut::new(());
 
+            buf.push_field_len(1);
+            buf.begin_len(());
+
+            buf.push_field_varint(1);
+            buf.push_varint_uint64(1);
+
             buf.push_field_varint(2);
-            buf.push_varint_bool(false);
+            buf.push_varint_uint64(2);
+
+            buf.end_len();
 
             buf.freeze().to_vec().into_owned()
         };
 
-        let sval1 = {
+        let sval = {
             #[derive(Value)]
-            #[allow(dead_code)]
-            pub enum Value<'a> {
-                Number(i32),
-                Boolean(bool),
-                Text(&'a str),
+            struct Outer<'a> {
+                inner: &'a ProtoBuf,
             }
 
             #[derive(Value)]
-            pub struct Oneof<'a> {
-                #[sval(flatten)]
-                value: Value<'a>,
+            struct Inner {
+                a: u64,
+                b: u64,
             }
 
-            sval_protobuf::stream_to_protobuf(Oneof {
-                value: Value::Boolean(false),
-            })
-            .to_vec()
-            .into_owned()
-        };
-
-        let sval2 = {
-            #[derive(Value)]
-            #[allow(dead_code)]
-            pub enum Value<'a> {
-                Number(i32),
-                Boolean(bool),
-                Text(&'a str),
-            }
+            let inner = sval_protobuf::stream_to_protobuf(Inner { a: 1, b: 2 });
 
-            sval_protobuf::stream_to_protobuf(Value::Boolean(false))
+            sval_protobuf::stream_to_protobuf(Outer { inner: &inner })
                 .to_vec()
                 .into_owned()
         };
 
-        assert_proto(&prost, &raw);
-        assert_proto(&prost, &sval1);
-        assert_proto(&prost, &sval2);
+        assert_proto(&raw, &sval);
     }
 
     #[test]
-    fn oneof_optional_none() {
+    fn pre_encoded_varint() {
         let raw = {
-            let buf = ProtoBufMut::new(());
+            let mut buf = ProtoBufMut::new(());
 
-            buf.freeze().to_vec().into_owned()
-        };
+            buf.push_field_len(1);
+            buf.begin_len(());
 
-        let sval1 = {
-            #[derive(Value)]
-            #[allow(dead_code)]
-            pub enum Value<'a> {
-                Number(Option<i32>),
-                Boolean(Option<bool>),
-                Text(Option<&'a str>),
-            }
+            buf.push_field_varint(1);
+            buf.push_varint_uint64(42);
 
-            #[derive(Value)]
-            pub struct Oneof<'a> {
-                #[sval(flatten)]
-                value: Value<'a>,
-            }
+            buf.end_len();
 
-            sval_protobuf::stream_to_protobuf(Oneof {
-                value: Value::Boolean(None),
-            })
-            .to_vec()
-            .into_owned()
+            buf.freeze().to_vec().into_owned()
         };
 
-        let sval2 = {
+        let sval = {
             #[derive(Value)]
-            #[allow(dead_code)]
-            pub enum Value<'a> {
-                Number(Option<i32>),
-                Boolean(Option<bool>),
-                Text(Option<&'a str>),
+            struct Outer<'a> {
+                inner: &'a ProtoBuf,
             }
 
-            sval_protobuf::stream_to_protobuf(Value::Boolean(None))
+            let inner = sval_protobuf::stream_to_protobuf(42);
+
+            sval_protobuf::stream_to_protobuf(Outer { inner: &inner })
                 .to_vec()
                 .into_owned()
         };
 
-        assert_proto(&raw, &sval1);
-        assert_proto(&raw, &sval2);
+        assert_proto(&raw, &sval);
     }
 
     #[test]
-    fn oneof_nested() {
-        let prost = {
-            let mut buf = Vec::new();
-
-            protos::cases::NestedOneof {
-                a: Some(protos::cases::Oneof {
-                    value: Some(protos::cases::oneof::Value::Boolean(true)),
-                }),
-            }
-            .encode(&mut buf)
-            .unwrap();
-
-            buf
-        };
-
+    fn pre_encoded_i32() {
         let raw = {
             let mut buf = ProtoBufMut::new(());
 
             buf.push_field_len(1);
             buf.begin_len(());
-            buf.push_field_varint(2);
-            buf.push_varint_bool(true);
-            buf.end_len();
-
-            buf.freeze().to_vec().into_owned()
-        };
-
-        let sval1 = {
-            #[derive(Value)]
-            #[allow(dead_code)]
-            pub enum Value<'a> {
-                Number(i32),
-                Boolean(bool),
-                Text(&'a str),
-            }
 
-            #[derive(Value)]
-            pub struct Oneof<'a> {
-                #[sval(flatten)]
-                value: Value<'a>,
-            }
+            buf.push_field_i32(1);
+            buf.push_i32_float(3.14);
 
-            #[derive(Value)]
-            pub struct NestedOneof<'a> {
-                a: Oneof<'a>,
-            }
+            buf.end_len();
 
-            sval_protobuf::stream_to_protobuf(NestedOneof {
-                a: Oneof {
-                    value: Value::Boolean(true),
-                },
-            })
-            .to_vec()
-            .into_owned()
+            buf.freeze().to_vec().into_owned()
         };
 
-        let sval2 = {
+        let sval = {
             #[derive(Value)]
-            #[allow(dead_code)]
-            pub enum Value<'a> {
-                Number(i32),
-                Boolean(bool),
-                Text(&'a str),
+            struct Outer<'a> {
+                inner: &'a ProtoBuf,
             }
 
-            #[derive(Value)]
-            pub struct NestedOneof<'a> {
-                value: Value<'a>,
-            }
+            let inner = sval_protobuf::stream_to_protobuf(3.14f32);
 
-            sval_protobuf::stream_to_protobuf(NestedOneof {
-                value: Value::Boolean(true),
-            })
-            .to_vec()
-            .into_owned()
+            sval_protobuf::stream_to_protobuf(Outer { inner: &inner })
+                .to_vec()
+                .into_owned()
         };
 
-        assert_proto(&prost, &raw);
-        assert_proto(&prost, &sval1);
-        assert_proto(&prost, &sval2);
+        assert_proto(&raw, &sval);
     }
 
     #[test]
-    fn nested() {
-        let prost = {
-            let mut buf = Vec::new();
-
-            protos::cases::Nested {
-                a: Some(protos::cases::NestedInner {
-                    a: Some(protos::cases::Optional { a: Some(1) }),
-                    b: b"Some bytes".to_vec(),
-                    c: 2,
-                }),
-                b: "Some text".to_owned(),
-                c: 2,
-            }
-            .encode(&mut buf)
-            .unwrap();
-
-            buf
-        };
-
+    fn pre_encoded_i64() {
         let raw = {
-            let mut buf = ProtoBufMut::new(());
-
-            // a: NestedInner
-            buf.push_field_len(1);
-            buf.begin_len(());
-
-            // a: Basic
-            buf.push_field_len(1);
-            buf.begin_len(());
-            buf.push_field_varint(1);
-            buf.push_varint_uint64(1);
-            buf.end_len();
+            let mut buf = ProtoBufMut::new(());
 
-            // b: bytes
-            buf.push_field_len(2);
+            buf.push_field_len(1);
             buf.begin_len(());
-            buf.push(b"Some bytes");
-            buf.end_len();
-
-            // c: int64
-            buf.push_field_varint(3);
-            buf.push_varint_uint64(2);
 
-            buf.end_len();
+            buf.push_field_i64(1);
+            buf.push_i64_double(3.1415);
 
-            // b: string
-            buf.push_field_len(2);
-            buf.begin_len(());
-            buf.push(b"Some text");
             buf.end_len();
 
-            // c: int64
-            buf.push_field_varint(3);
-            buf.push_varint_uint64(2);
-
             buf.freeze().to_vec().into_owned()
         };
 
         let sval = {
             #[derive(Value)]
-            pub struct Nested<'a> {
-                a: NestedInner<'a>,
-                b: &'a str,
-                c: i32,
-            }
-
-            #[derive(Value)]
-            pub struct NestedInner<'a> {
-                a: Optional,
-                b: &'a sval::BinarySlice,
-                c: i32,
+            struct Outer<'a> {
+                inner: &'a ProtoBuf,
             }
 
-            #[derive(Value)]
-            pub struct Optional {
-                a: Option<i32>,
-            }
+            let inner = sval_protobuf::stream_to_protobuf(3.1415f64);
 
-            sval_protobuf::stream_to_protobuf(Nested {
-                a: NestedInner {
-                    a: Optional { a: Some(1) },
-                    b: sval::BinarySlice::new(b"Some bytes"),
-                    c: 2,
-                },
-                b: "Some text",
-                c: 2,
-            })
-            .to_vec()
-            .into_owned()
+            sval_protobuf::stream_to_protobuf(Outer { inner: &inner })
+                .to_vec()
+                .into_owned()
         };
 
-        assert_proto(&prost, &raw);
-        assert_proto(&prost, &sval);
+        assert_proto(&raw, &sval);
     }
 
     #[test]
-    fn exotic_enum_tuple() {
+    fn root_raw_scalar() {
+        let raw_scalars = sval_protobuf::RootOptions::new().with_raw_scalars(true);
+
         #[derive(Value)]
-        #[allow(dead_code)]
-        pub enum Enum {
-            Tag,
-            Tuple(i32, i32),
-        }
+        #[sval(tag = "sval_protobuf::tags::PROTOBUF_I64")]
+        pub struct Fixed(u64);
+
+        assert_eq!(
+            vec![42],
+            encode_with(
+                sval_protobuf::ProtoBufStream::new().with_root(raw_scalars),
+                42
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            vec![1],
+            encode_with(
+                sval_protobuf::ProtoBufStream::new().with_root(raw_scalars),
+                true
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            42u64.to_le_bytes().to_vec(),
+            encode_with(
+                sval_protobuf::ProtoBufStream::new().with_root(raw_scalars),
+                Fixed(42)
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            b"abc".to_vec(),
+            encode_with(
+                sval_protobuf::ProtoBufStream::new().with_root(raw_scalars),
+                "abc"
+            )
+            .unwrap()
+        );
+
+        // Messages aren't affected by raw scalars
+        assert_eq!(
+            sval_protobuf::stream_to_protobuf((42, "abc"))
+                .to_vec()
+                .into_owned(),
+            encode_with(
+                sval_protobuf::ProtoBufStream::new().with_root(raw_scalars),
+                (42, "abc")
+            )
+            .unwrap()
+        );
+    }
 
+    #[test]
+    fn root_raw_scalar_pre_encoded() {
         #[derive(Value)]
-        pub struct Struct {
+        struct Outer<'a, T> {
             a: i32,
-            b: Enum,
+            inner: &'a T,
         }
 
-        let raw = {
-            let mut buf = ProtoBufMut::new(());
+        let expected = sval_protobuf::stream_to_protobuf(Outer {
+            a: 1,
+            inner: &"Some content",
+        })
+        .to_vec()
+        .into_owned();
+
+        let inner = ProtoBuf::pre_encoded(
+            encode_with(
+                sval_protobuf::ProtoBufStream::new()
+                    .with_root(sval_protobuf::RootOptions::new().with_raw_scalars(true)),
+                "Some content",
+            )
+            .unwrap(),
+        );
+
+        let actual = sval_protobuf::stream_to_protobuf(Outer {
+            a: 1,
+            inner: &inner,
+        })
+        .to_vec()
+        .into_owned();
+
+        assert_proto(&expected, &actual);
+    }
 
-            // a
-            buf.push_field_varint(1);
-            buf.push_varint_uint64(2);
+    #[test]
+    fn root_field_number() {
+        let prost = protos::cases::Repeated {
+            a: vec!["a".to_owned(), "b".to_owned()],
+        }
+        .encode_to_vec();
 
-            // b
-            buf.push_field_len(2);
-            buf.begin_len(());
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new()
+                .with_root(sval_protobuf::RootOptions::new().with_field_number(3)),
+            ["a", "b"],
+        )
+        .unwrap();
 
-            // Enum::Tuple
-            buf.push_field_len(2);
-            buf.begin_len(());
+        assert_proto(&prost, &sval);
 
-            // Enum::Tuple.0
-            buf.push_field_varint(1);
-            buf.push_varint_uint64(3);
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new().with_root(
+                sval_protobuf::RootOptions::new()
+                    .with_field_number(3)
+                    .with_raw_scalars(true),
+            ),
+            ["a", "b"],
+        )
+        .unwrap();
 
-            // Enum::Tuple.1
-            buf.push_field_varint(2);
-            buf.push_varint_uint64(4);
+        assert_proto(&prost, &sval);
 
-            buf.end_len();
+        let prost = protos::cases::NonContiguousFields {
+            id: 42,
+            ..Default::default()
+        }
+        .encode_to_vec();
 
-            buf.end_len();
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new()
+                .with_root(sval_protobuf::RootOptions::new().with_field_number(4)),
+            42,
+        )
+        .unwrap();
 
-            buf.freeze().to_vec().into_owned()
+        assert_proto(&prost, &sval);
+    }
+
+    #[test]
+    fn try_stream_ok() {
+        #[derive(Value)]
+        pub struct Basic<'a> {
+            id: i32,
+            content: &'a str,
+            index: Option<i32>,
+        }
+
+        let value = Basic {
+            id: 1,
+            content: "Some content",
+            index: Some(3),
         };
 
-        let sval = {
-            sval_protobuf::stream_to_protobuf(Struct {
-                a: 2,
-                b: Enum::Tuple(3, 4),
-            })
+        let expected = sval_protobuf::stream_to_protobuf(&value)
             .to_vec()
-            .into_owned()
-        };
+            .into_owned();
 
-        assert_proto(&raw, &sval);
+        let actual = sval_protobuf::try_stream_to_protobuf(&value)
+            .unwrap()
+            .to_vec()
+            .into_owned();
+
+        assert_proto(&expected, &actual);
     }
 
     #[test]
-    fn exotic_tagged_empty() {
-        struct Tagged;
+    fn try_stream_err_stream() {
+        struct Failing;
 
-        impl sval::Value for Tagged {
+        impl sval::Value for Failing {
             fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(
                 &'sval self,
-                stream: &mut S,
+                _: &mut S,
             ) -> sval::Result {
-                stream.tagged_begin(None, Some(&sval::Label::new("Tagged")), None)?;
-                stream.tagged_end(None, Some(&sval::Label::new("Tagged")), None)
+                Err(sval::Error::new())
             }
         }
 
         #[derive(Value)]
         pub struct Struct {
             a: i32,
-            b: Tagged,
-            c: i32,
+            b: Failing,
         }
 
-        let raw = {
-            let mut buf = ProtoBufMut::new(());
-
-            // a
-            buf.push_field_varint(1);
-            buf.push_varint_uint64(2);
-
-            // c
-            buf.push_field_varint(3);
-            buf.push_varint_uint64(5);
-
-            buf.freeze().to_vec().into_owned()
-        };
-
-        let sval = {
-            sval_protobuf::stream_to_protobuf(Struct {
-                a: 2,
-                b: Tagged,
-                c: 5,
-            })
-            .to_vec()
-            .into_owned()
-        };
+        let err = sval_protobuf::try_stream_to_protobuf(Struct { a: 1, b: Failing }).unwrap_err();
 
-        assert_proto(&raw, &sval);
-
-        let raw = {
-            let buf = ProtoBufMut::new(());
-
-            buf.freeze().to_vec().into_owned()
-        };
-
-        let sval = {
-            sval_protobuf::stream_to_protobuf(Tagged)
-                .to_vec()
-                .into_owned()
-        };
-
-        assert_proto(&raw, &sval);
+        assert_eq!(sval_protobuf::ErrorKind::Stream, err.kind());
     }
 
     #[test]
-    fn exotic_enum_empty() {
-        struct Enum;
+    fn try_stream_invalid_field_number() {
+        struct Negative;
 
-        impl sval::Value for Enum {
+        impl sval::Value for Negative {
             fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(
                 &'sval self,
                 stream: &mut S,
             ) -> sval::Result {
-                stream.enum_begin(None, Some(&sval::Label::new("Enum")), None)?;
-                stream.enum_end(None, Some(&sval::Label::new("Enum")), None)
+                stream.record_tuple_begin(None, None, None, Some(1))?;
+
+                let label = sval::Label::new("a");
+                let index = sval::Index::new_i64(-1);
+
+                stream.record_tuple_value_begin(None, &label, &index)?;
+                stream.i32(1)?;
+                stream.record_tuple_value_end(None, &label, &index)?;
+
+                stream.record_tuple_end(None, None, None)
             }
         }
 
-        #[derive(Value)]
-        pub struct Struct {
-            a: i32,
-            b: Enum,
-            c: i32,
-        }
+        // Outside of strict mode, invalid indexes fall back to field number `1`
+        let encoded = sval_protobuf::try_stream_to_protobuf(Negative).unwrap();
 
-        let raw = {
-            let mut buf = ProtoBufMut::new(());
+        assert_eq!(vec![8, 1], encoded.to_vec().into_owned());
 
-            // a
-            buf.push_field_varint(1);
-            buf.push_varint_uint64(2);
+        let err = encode_with(
+            sval_protobuf::ProtoBufStream::new().with_strict(true),
+            Negative,
+        )
+        .unwrap_err();
 
-            // c
-            buf.push_field_varint(3);
-            buf.push_varint_uint64(5);
+        assert_eq!(sval_protobuf::ErrorKind::InvalidFieldNumber, err.kind());
+    }
 
-            buf.freeze().to_vec().into_owned()
-        };
+    #[test]
+    fn try_stream_err_unsupported_packed() {
+        #[derive(Value)]
+        pub struct RepeatedPacked<'a> {
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_LEN_PACKED")]
+            a: &'a [&'a str],
+        }
 
-        let sval = {
-            sval_protobuf::stream_to_protobuf(Struct {
-                a: 2,
-                b: Enum,
-                c: 5,
-            })
-            .to_vec()
-            .into_owned()
-        };
+        let err =
+            sval_protobuf::try_stream_to_protobuf(RepeatedPacked { a: &["a", "b"] }).unwrap_err();
 
-        assert_proto(&raw, &sval);
+        assert_eq!(sval_protobuf::ErrorKind::Unsupported, err.kind());
+    }
 
-        let raw = {
-            let buf = ProtoBufMut::new(());
+    #[test]
+    fn try_freeze_err_unbalanced() {
+        use sval::Stream as _;
 
-            buf.freeze().to_vec().into_owned()
-        };
+        let mut stream = sval_protobuf::ProtoBufStream::new();
 
-        let sval = {
-            sval_protobuf::stream_to_protobuf(Enum)
-                .to_vec()
-                .into_owned()
-        };
+        let label = sval::Label::new("a");
+        let index = sval::Index::new(0).with_tag(&sval::tags::VALUE_OFFSET);
 
-        assert_proto(&raw, &sval);
+        stream.record_tuple_begin(None, None, None, None).unwrap();
+        stream
+            .record_tuple_value_begin(None, &label, &index)
+            .unwrap();
+        stream.record_tuple_begin(None, None, None, None).unwrap();
+
+        let err = stream.try_freeze().unwrap_err();
+
+        assert_eq!(sval_protobuf::ErrorKind::Unbalanced, err.kind());
     }
 
     #[test]
-    fn exotic_nested_enum_empty() {
-        struct Outer;
+    fn strict_ok() {
+        #[derive(Value)]
+        pub enum Value<'a> {
+            Number(i32),
+            Text(&'a str),
+        }
 
-        impl sval::Value for Outer {
-            fn stream<'sval, S: sval::Stream<'sval> + ?Sized>(
-                &'sval self,
-                stream: &mut S,
-            ) -> sval::Result {
-                stream.enum_begin(None, Some(&sval::Label::new("Outer")), None)?;
-                stream.enum_begin(
-                    None,
-                    Some(&sval::Label::new("Inner")),
-                    Some(&sval::Index::new(0).with_tag(&sval::tags::VALUE_OFFSET)),
-                )?;
-                stream.enum_begin(
-                    None,
-                    Some(&sval::Label::new("Core")),
-                    Some(&sval::Index::new(0).with_tag(&sval::tags::VALUE_OFFSET)),
-                )?;
-                stream.enum_end(
-                    None,
-                    Some(&sval::Label::new("Core")),
-                    Some(&sval::Index::new(0).with_tag(&sval::tags::VALUE_OFFSET)),
-                )?;
-                stream.enum_end(
-                    None,
-                    Some(&sval::Label::new("Inner")),
-                    Some(&sval::Index::new(0).with_tag(&sval::tags::VALUE_OFFSET)),
-                )?;
-                stream.enum_end(None, Some(&sval::Label::new("Outer")), None)
-            }
+        #[derive(Value)]
+        pub struct Inner<'a> {
+            a: i32,
+            b: Value<'a>,
+        }
+
+        #[derive(Value)]
+        pub struct Outer<'a> {
+            a: &'a [Inner<'a>],
+            b: BTreeMap<&'a str, Inner<'a>>,
+            #[sval(flatten)]
+            c: Value<'a>,
+            #[sval(index = 5)]
+            d: Inner<'a>,
         }
 
+        let value = Outer {
+            a: &[
+                Inner {
+                    a: 1,
+                    b: Value::Number(1),
+                },
+                Inner {
+                    a: 2,
+                    b: Value::Text("a"),
+                },
+            ],
+            b: BTreeMap::from_iter([(
+                "a",
+                Inner {
+                    a: 3,
+                    b: Value::Number(3),
+                },
+            )]),
+            c: Value::Text("b"),
+            d: Inner {
+                a: 4,
+                b: Value::Number(4),
+            },
+        };
+
+        let expected = sval_protobuf::stream_to_protobuf(&value)
+            .to_vec()
+            .into_owned();
+
+        let actual = encode_with(
+            sval_protobuf::ProtoBufStream::new().with_strict(true),
+            &value,
+        )
+        .unwrap();
+
+        assert_proto(&expected, &actual);
+    }
+
+    #[test]
+    fn strict_err_invalid_field_number() {
         #[derive(Value)]
-        pub struct Struct {
+        pub struct Zero {
+            #[sval(index = 0)]
             a: i32,
-            b: Outer,
-            c: i32,
         }
 
-        let raw = {
-            let mut buf = ProtoBufMut::new(());
+        #[derive(Value)]
+        pub struct Reserved {
+            #[sval(index = 19000)]
+            a: i32,
+        }
 
-            // a
-            buf.push_field_varint(1);
-            buf.push_varint_uint64(2);
+        #[derive(Value)]
+        pub struct TooLarge {
+            #[sval(index = 536870912)]
+            a: i32,
+        }
 
-            // b
-            buf.push_field_len(2);
-            buf.begin_len(());
+        assert_eq!(
+            sval_protobuf::ErrorKind::InvalidFieldNumber,
+            encode_with(
+                sval_protobuf::ProtoBufStream::new().with_strict(true),
+                Zero { a: 1 }
+            )
+            .unwrap_err()
+            .kind()
+        );
+        assert_eq!(
+            sval_protobuf::ErrorKind::InvalidFieldNumber,
+            encode_with(
+                sval_protobuf::ProtoBufStream::new().with_strict(true),
+                Reserved { a: 1 }
+            )
+            .unwrap_err()
+            .kind()
+        );
+        assert_eq!(
+            sval_protobuf::ErrorKind::InvalidFieldNumber,
+            encode_with(
+                sval_protobuf::ProtoBufStream::new().with_strict(true),
+                TooLarge { a: 1 }
+            )
+            .unwrap_err()
+            .kind()
+        );
+
+        assert!(sval_protobuf::try_stream_to_protobuf(Reserved { a: 1 }).is_ok());
+    }
 
-            buf.push_field_len(1);
-            buf.begin_len(());
-            buf.end_len();
+    #[test]
+    fn strict_err_duplicate_field() {
+        #[derive(Value)]
+        pub struct Duplicate {
+            #[sval(index = 1)]
+            a: i32,
+            #[sval(index = 1)]
+            b: i32,
+        }
 
-            buf.end_len();
+        assert_eq!(
+            sval_protobuf::ErrorKind::DuplicateField,
+            encode_with(
+                sval_protobuf::ProtoBufStream::new().with_strict(true),
+                Duplicate { a: 1, b: 2 }
+            )
+            .unwrap_err()
+            .kind()
+        );
+
+        assert!(sval_protobuf::try_stream_to_protobuf(Duplicate { a: 1, b: 2 }).is_ok());
+    }
 
-            // c
-            buf.push_field_varint(3);
-            buf.push_varint_uint64(5);
+    #[test]
+    fn strict_err_map_shape() {
+        let message_key = BTreeMap::from_iter([((1, 2), 3)]);
+
+        assert_eq!(
+            sval_protobuf::ErrorKind::Unsupported,
+            encode_with(
+                sval_protobuf::ProtoBufStream::new().with_strict(true),
+                (&message_key,)
+            )
+            .unwrap_err()
+            .kind()
+        );
+
+        let map_value = BTreeMap::from_iter([("a", BTreeMap::from_iter([("b", 1)]))]);
+
+        assert_eq!(
+            sval_protobuf::ErrorKind::Unsupported,
+            encode_with(
+                sval_protobuf::ProtoBufStream::new().with_strict(true),
+                (&map_value,)
+            )
+            .unwrap_err()
+            .kind()
+        );
+
+        let message_value = BTreeMap::from_iter([("a", (BTreeMap::from_iter([("b", 1)]),))]);
+
+        assert!(encode_with(
+            sval_protobuf::ProtoBufStream::new().with_strict(true),
+            (&message_value,)
+        )
+        .is_ok());
+    }
 
-            buf.freeze().to_vec().into_owned()
-        };
+    #[test]
+    fn omit_defaults_scalar() {
+        #[derive(Value)]
+        pub struct Scalar<'a> {
+            f64: f64,
+            f32: f32,
+            vi32: i32,
+            vi64: i64,
+            vu32: u32,
+            vu64: u64,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_VARINT_SIGNED")]
+            si32: i32,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_VARINT_SIGNED")]
+            si64: i64,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_I32")]
+            fi32: i32,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_I64")]
+            fi64: i64,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_I32")]
+            sfi32: i32,
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_I64")]
+            sfi64: i64,
+            bool: bool,
+            sbin: &'a str,
+            bin: &'a sval::BinarySlice,
+        }
 
-        let sval = {
-            sval_protobuf::stream_to_protobuf(Struct {
-                a: 2,
-                b: Outer,
-                c: 5,
-            })
-            .to_vec()
-            .into_owned()
-        };
+        let prost = protos::cases::Scalar::default().encode_to_vec();
 
-        assert_proto(&raw, &sval);
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new().with_omit_defaults(true),
+            Scalar {
+                f64: 0.0,
+                f32: 0.0,
+                vi32: 0,
+                vi64: 0,
+                vu32: 0,
+                vu64: 0,
+                si32: 0,
+                si64: 0,
+                fi32: 0,
+                fi64: 0,
+                sfi32: 0,
+                sfi64: 0,
+                bool: false,
+                sbin: "",
+                bin: sval::BinarySlice::new(b""),
+            },
+        )
+        .unwrap();
 
-        let raw = {
-            let mut buf = ProtoBufMut::new(());
+        assert_proto(&prost, &sval);
 
-            buf.push_field_len(1);
+        let prost = protos::cases::Scalar {
+            vi32: 1,
+            sbin: "a".to_owned(),
+            ..Default::default()
+        }
+        .encode_to_vec();
 
-            buf.begin_len(());
-            buf.end_len();
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new().with_omit_defaults(true),
+            Scalar {
+                f64: 0.0,
+                f32: 0.0,
+                vi32: 1,
+                vi64: 0,
+                vu32: 0,
+                vu64: 0,
+                si32: 0,
+                si64: 0,
+                fi32: 0,
+                fi64: 0,
+                sfi32: 0,
+                sfi64: 0,
+                bool: false,
+                sbin: "a",
+                bin: sval::BinarySlice::new(b""),
+            },
+        )
+        .unwrap();
 
-            buf.end_len();
+        assert_proto(&prost, &sval);
+    }
 
-            buf.freeze().to_vec().into_owned()
-        };
+    #[test]
+    fn omit_defaults_explicit_presence() {
+        #[derive(Value)]
+        pub struct Basic<'a> {
+            id: i32,
+            content: &'a str,
+            index: Option<i32>,
+        }
 
-        let sval = {
-            sval_protobuf::stream_to_protobuf(Outer)
-                .to_vec()
-                .into_owned()
-        };
+        let prost = protos::cases::Basic {
+            id: 0,
+            content: String::new(),
+            index: Some(0),
+        }
+        .encode_to_vec();
+
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new().with_omit_defaults(true),
+            Basic {
+                id: 0,
+                content: "",
+                index: Some(0),
+            },
+        )
+        .unwrap();
 
-        assert_proto(&raw, &sval);
-    }
+        assert_proto(&prost, &sval);
 
-    #[test]
-    fn pre_encoded_len() {
-        let raw = {
-            let mut buf = ProtoBufMut::new(());
+        #[derive(Value)]
+        pub enum Value<'a> {
+            Number(i32),
+            Boolean(bool),
+            Text(&'a str),
+        }
 
-            buf.push_field_len(1);
-            buf.begin_len(());
+        #[derive(Value)]
+        pub struct NestedOneof<'a> {
+            a: Value<'a>,
+        }
 
-            buf.push_field_varint(1);
-            buf.push_varint_uint64(1);
+        let prost = protos::cases::NestedOneof {
+            a: Some(protos::cases::Oneof {
+                value: Some(protos::cases::oneof::Value::Number(0)),
+            }),
+        }
+        .encode_to_vec();
 
-            buf.push_field_varint(2);
-            buf.push_varint_uint64(2);
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new().with_omit_defaults(true),
+            NestedOneof {
+                a: Value::Number(0),
+            },
+        )
+        .unwrap();
 
-            buf.end_len();
+        assert_proto(&prost, &sval);
 
-            buf.freeze().to_vec().into_owned()
-        };
+        let prost = protos::cases::NestedOneof {
+            a: Some(protos::cases::Oneof {
+                value: Some(protos::cases::oneof::Value::Text(String::new())),
+            }),
+        }
+        .encode_to_vec();
 
-        let sval = {
-            #[derive(Value)]
-            struct Outer<'a> {
-                inner: &'a ProtoBuf,
-            }
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new().with_omit_defaults(true),
+            NestedOneof { a: Value::Text("") },
+        )
+        .unwrap();
 
-            #[derive(Value)]
-            struct Inner {
-                a: u64,
-                b: u64,
-            }
+        assert_proto(&prost, &sval);
 
-            let inner = sval_protobuf::stream_to_protobuf(Inner { a: 1, b: 2 });
+        let prost = protos::cases::NestedOneof {
+            a: Some(protos::cases::Oneof {
+                value: Some(protos::cases::oneof::Value::Boolean(false)),
+            }),
+        }
+        .encode_to_vec();
 
-            sval_protobuf::stream_to_protobuf(Outer { inner: &inner })
-                .to_vec()
-                .into_owned()
-        };
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new().with_omit_defaults(true),
+            NestedOneof {
+                a: Value::Boolean(false),
+            },
+        )
+        .unwrap();
 
-        assert_proto(&raw, &sval);
+        assert_proto(&prost, &sval);
     }
 
     #[test]
-    fn pre_encoded_varint() {
-        let raw = {
-            let mut buf = ProtoBufMut::new(());
+    fn omit_defaults_nested() {
+        #[derive(Value)]
+        #[repr(i32)]
+        pub enum EnumInner {
+            A = 0,
+        }
 
-            buf.push_field_len(1);
-            buf.begin_len(());
+        #[derive(Value)]
+        pub struct Enum {
+            value: EnumInner,
+        }
 
-            buf.push_field_varint(1);
-            buf.push_varint_uint64(42);
+        #[derive(Value)]
+        pub struct Repeated<'a> {
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_LEN_PACKED")]
+            a: &'a [i32],
+        }
 
-            buf.end_len();
+        #[derive(Value)]
+        pub struct Map<'a> {
+            a: BTreeMap<&'a str, i32>,
+        }
 
-            buf.freeze().to_vec().into_owned()
-        };
+        #[derive(Value)]
+        pub struct Nested<'a> {
+            a: (Option<i32>,),
+            b: &'a str,
+            c: i32,
+        }
 
-        let sval = {
-            #[derive(Value)]
-            struct Outer<'a> {
-                inner: &'a ProtoBuf,
+        assert_proto(
+            &protos::cases::Enum::default().encode_to_vec(),
+            &encode_with(
+                sval_protobuf::ProtoBufStream::new().with_omit_defaults(true),
+                Enum {
+                    value: EnumInner::A,
+                },
+            )
+            .unwrap(),
+        );
+
+        assert_proto(
+            &protos::cases::RepeatedPacked { a: vec![0, 1, 0] }.encode_to_vec(),
+            &encode_with(
+                sval_protobuf::ProtoBufStream::new().with_omit_defaults(true),
+                Repeated { a: &[0, 1, 0] },
+            )
+            .unwrap(),
+        );
+
+        // Map entries always contain both their key and value
+        assert_eq!(
+            protos::cases::Map {
+                a: BTreeMap::from_iter([("".to_owned(), 0)]),
+            },
+            protos::cases::Map::decode(
+                &*encode_with(
+                    sval_protobuf::ProtoBufStream::new().with_omit_defaults(true),
+                    Map {
+                        a: BTreeMap::from_iter([("", 0)]),
+                    }
+                )
+                .unwrap()
+            )
+            .unwrap(),
+        );
+
+        assert_proto(
+            &protos::cases::NestedOneof {
+                a: Some(protos::cases::Oneof { value: None }),
             }
-
-            let inner = sval_protobuf::stream_to_protobuf(42);
-
-            sval_protobuf::stream_to_protobuf(Outer { inner: &inner })
-                .to_vec()
-                .into_owned()
-        };
-
-        assert_proto(&raw, &sval);
+            .encode_to_vec(),
+            &encode_with(
+                sval_protobuf::ProtoBufStream::new().with_omit_defaults(true),
+                Nested {
+                    a: (None,),
+                    b: "",
+                    c: 0,
+                },
+            )
+            .unwrap(),
+        );
     }
 
     #[test]
-    fn pre_encoded_i32() {
+    fn auto_packed() {
+        #[derive(Value)]
+        #[sval(tag = "sval_protobuf::tags::PROTOBUF_I64")]
+        pub struct Fixed(u64);
+
+        #[derive(Value)]
+        pub struct Repeated<'a> {
+            a: &'a [i32],
+            b: &'a [&'a str],
+            c: &'a [Fixed],
+            d: &'a [Option<bool>],
+            e: &'a [(i32,)],
+            f: &'a [i32],
+        }
+
         let raw = {
             let mut buf = ProtoBufMut::new(());
 
             buf.push_field_len(1);
             buf.begin_len(());
+            buf.push_varint_uint64(1);
+            buf.push_varint_uint64(2);
+            buf.end_len();
 
-            buf.push_field_i32(1);
-            buf.push_i32_float(3.14);
+            buf.push_field_len(2);
+            buf.begin_len(());
+            buf.push(b"a");
+            buf.end_len();
 
+            buf.push_field_len(2);
+            buf.begin_len(());
+            buf.push(b"b");
             buf.end_len();
 
-            buf.freeze().to_vec().into_owned()
-        };
+            buf.push_field_len(3);
+            buf.begin_len(());
+            buf.push_i64_fixed64(1);
+            buf.push_i64_fixed64(2);
+            buf.end_len();
 
-        let sval = {
-            #[derive(Value)]
-            struct Outer<'a> {
-                inner: &'a ProtoBuf,
-            }
+            buf.push_field_len(4);
+            buf.begin_len(());
+            buf.push_varint_bool(true);
+            buf.push_varint_bool(false);
+            buf.end_len();
 
-            let inner = sval_protobuf::stream_to_protobuf(3.14f32);
+            buf.push_field_len(5);
+            buf.begin_len(());
+            buf.push_field_varint(1);
+            buf.push_varint_uint64(1);
+            buf.end_len();
 
-            sval_protobuf::stream_to_protobuf(Outer { inner: &inner })
-                .to_vec()
-                .into_owned()
+            buf.freeze().to_vec().into_owned()
         };
 
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new().with_auto_packed(true),
+            Repeated {
+                a: &[1, 2],
+                b: &["a", "b"],
+                c: &[Fixed(1), Fixed(2)],
+                d: &[None, Some(true), Some(false)],
+                e: &[(1,)],
+                f: &[],
+            },
+        )
+        .unwrap();
+
         assert_proto(&raw, &sval);
+
+        let prost = protos::cases::RepeatedPacked { a: vec![1, -2, 3] }.encode_to_vec();
+
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new().with_auto_packed(true),
+            (&[1, -2, 3],),
+        )
+        .unwrap();
+
+        assert_proto(&prost, &sval);
     }
 
     #[test]
-    fn pre_encoded_i64() {
+    fn auto_packed_expanded() {
+        #[derive(Value)]
+        pub struct Repeated<'a> {
+            #[sval(data_tag = "sval_protobuf::tags::PROTOBUF_LEN_EXPANDED")]
+            a: &'a [i32],
+            b: &'a [i32],
+        }
+
         let raw = {
             let mut buf = ProtoBufMut::new(());
 
-            buf.push_field_len(1);
-            buf.begin_len(());
+            buf.push_field_varint(1);
+            buf.push_varint_uint64(1);
 
-            buf.push_field_i64(1);
-            buf.push_i64_double(3.1415);
+            buf.push_field_varint(1);
+            buf.push_varint_uint64(2);
 
+            buf.push_field_len(2);
+            buf.begin_len(());
+            buf.push_varint_uint64(3);
             buf.end_len();
 
             buf.freeze().to_vec().into_owned()
         };
 
-        let sval = {
-            #[derive(Value)]
-            struct Outer<'a> {
-                inner: &'a ProtoBuf,
-            }
-
-            let inner = sval_protobuf::stream_to_protobuf(3.1415f64);
-
-            sval_protobuf::stream_to_protobuf(Outer { inner: &inner })
-                .to_vec()
-                .into_owned()
-        };
+        let sval = encode_with(
+            sval_protobuf::ProtoBufStream::new().with_auto_packed(true),
+            Repeated {
+                a: &[1, 2],
+                b: &[3],
+            },
+        )
+        .unwrap();
 
         assert_proto(&raw, &sval);
     }
 }
 
+#[cfg(all(test, feature = "prost"))]
+fn encode_with(
+    mut stream: sval_protobuf::ProtoBufStream,
+    v: impl sval::Value,
+) -> Result<Vec<u8>, sval_protobuf::Error> {
+    let _ = v.stream(&mut stream);
+
+    Ok(stream.try_freeze()?.to_vec().into_owned())
+}
+
 #[track_caller]
 #[cfg(all(test, feature = "prost"))]
 fn assert_proto(expected: &[u8], actual: &[u8]) {
@@ -32,4 +32,223 @@ mod tests {
         assert_eq!(prost, decoded_prost1);
         assert_eq!(prost, decoded_prost2);
     }
+
+    #[test]
+    fn export_logs_service_request_decode_roundtrip() {
+        let prost = data_prost::export_logs_service_request().encode_to_vec();
+
+        let sval =
+            sval_protobuf::stream_to_protobuf(sval_protobuf::decode::RawMessage::new(&prost))
+                .to_vec()
+                .into_owned();
+
+        assert_eq!(prost, sval);
+    }
+
+    #[test]
+    fn export_logs_service_request_decode_typed() {
+        use sval_protobuf::decode::{FieldDescriptor, FieldKind, MessageDescriptor, TypedMessage};
+
+        static ANY_VALUE: MessageDescriptor = MessageDescriptor::new(
+            "AnyValue",
+            &[
+                FieldDescriptor::new(1, "string_value", FieldKind::String).one_of("value"),
+                FieldDescriptor::new(2, "bool_value", FieldKind::Bool).one_of("value"),
+                FieldDescriptor::new(3, "int_value", FieldKind::Int64).one_of("value"),
+                FieldDescriptor::new(4, "double_value", FieldKind::Double).one_of("value"),
+                FieldDescriptor::new(5, "array_value", FieldKind::Message(&ARRAY_VALUE))
+                    .one_of("value"),
+                FieldDescriptor::new(6, "kvlist_value", FieldKind::Message(&KEY_VALUE_LIST))
+                    .one_of("value"),
+                FieldDescriptor::new(7, "bytes_value", FieldKind::Bytes).one_of("value"),
+            ],
+        );
+
+        static ARRAY_VALUE: MessageDescriptor = MessageDescriptor::new(
+            "ArrayValue",
+            &[FieldDescriptor::new(1, "values", FieldKind::Message(&ANY_VALUE)).repeated()],
+        );
+
+        static KEY_VALUE_LIST: MessageDescriptor = MessageDescriptor::new(
+            "KeyValueList",
+            &[FieldDescriptor::new(1, "values", FieldKind::Message(&KEY_VALUE)).repeated()],
+        );
+
+        static KEY_VALUE: MessageDescriptor = MessageDescriptor::new(
+            "KeyValue",
+            &[
+                FieldDescriptor::new(1, "key", FieldKind::String),
+                FieldDescriptor::new(2, "value", FieldKind::Message(&ANY_VALUE)),
+            ],
+        );
+
+        static INSTRUMENTATION_SCOPE: MessageDescriptor = MessageDescriptor::new(
+            "InstrumentationScope",
+            &[
+                FieldDescriptor::new(1, "name", FieldKind::String),
+                FieldDescriptor::new(2, "version", FieldKind::String),
+                FieldDescriptor::new(3, "attributes", FieldKind::Message(&KEY_VALUE)).repeated(),
+                FieldDescriptor::new(4, "dropped_attributes_count", FieldKind::UInt32),
+            ],
+        );
+
+        static RESOURCE: MessageDescriptor = MessageDescriptor::new(
+            "Resource",
+            &[
+                FieldDescriptor::new(1, "attributes", FieldKind::Message(&KEY_VALUE)).repeated(),
+                FieldDescriptor::new(2, "dropped_attributes_count", FieldKind::UInt32),
+            ],
+        );
+
+        static LOG_RECORD: MessageDescriptor = MessageDescriptor::new(
+            "LogRecord",
+            &[
+                FieldDescriptor::new(1, "time_unix_nano", FieldKind::Fixed64),
+                FieldDescriptor::new(11, "observed_time_unix_nano", FieldKind::Fixed64),
+                FieldDescriptor::new(2, "severity_number", FieldKind::Enum),
+                FieldDescriptor::new(3, "severity_text", FieldKind::String),
+                FieldDescriptor::new(5, "body", FieldKind::Message(&ANY_VALUE)),
+                FieldDescriptor::new(6, "attributes", FieldKind::Message(&KEY_VALUE)).repeated(),
+                FieldDescriptor::new(7, "dropped_attributes_count", FieldKind::UInt32),
+                FieldDescriptor::new(8, "flags", FieldKind::Fixed32),
+                FieldDescriptor::new(9, "trace_id", FieldKind::Bytes),
+                FieldDescriptor::new(10, "span_id", FieldKind::Bytes),
+            ],
+        );
+
+        static SCOPE_LOGS: MessageDescriptor = MessageDescriptor::new(
+            "ScopeLogs",
+            &[
+                FieldDescriptor::new(1, "scope", FieldKind::Message(&INSTRUMENTATION_SCOPE)),
+                FieldDescriptor::new(2, "log_records", FieldKind::Message(&LOG_RECORD)).repeated(),
+                FieldDescriptor::new(3, "schema_url", FieldKind::String),
+            ],
+        );
+
+        static RESOURCE_LOGS: MessageDescriptor = MessageDescriptor::new(
+            "ResourceLogs",
+            &[
+                FieldDescriptor::new(1, "resource", FieldKind::Message(&RESOURCE)),
+                FieldDescriptor::new(2, "scope_logs", FieldKind::Message(&SCOPE_LOGS)).repeated(),
+                FieldDescriptor::new(3, "schema_url", FieldKind::String),
+            ],
+        );
+
+        static EXPORT_LOGS_SERVICE_REQUEST: MessageDescriptor = MessageDescriptor::new(
+            "ExportLogsServiceRequest",
+            &[
+                FieldDescriptor::new(1, "resource_logs", FieldKind::Message(&RESOURCE_LOGS))
+                    .repeated(),
+            ],
+        );
+
+        let prost = data_prost::export_logs_service_request();
+        let encoded = prost.encode_to_vec();
+
+        let sval = sval_protobuf::stream_to_protobuf(TypedMessage::new(
+            &encoded,
+            &EXPORT_LOGS_SERVICE_REQUEST,
+        ))
+        .to_vec()
+        .into_owned();
+
+        let decoded_prost = crate::protos::opentelemetry::proto::collector::logs::v1::ExportLogsServiceRequest::decode(std::io::Cursor::new(&sval)).expect("failed to decode");
+
+        assert_eq!(prost, decoded_prost);
+    }
+
+    #[test]
+    fn export_logs_service_request_view() {
+        use sval_protobuf::decode::MessageView;
+
+        let encoded = sval_protobuf::stream_to_protobuf(data_sval::export_logs_service_request());
+        let request = encoded.view();
+
+        let mut severity_texts = Vec::new();
+        let mut service_name = None;
+
+        for resource_logs in request.get_repeated::<MessageView>(1) {
+            let resource_logs = resource_logs.unwrap();
+
+            // ResourceLogs.resource.attributes
+            for attribute in resource_logs
+                .get::<MessageView>(1)
+                .unwrap()
+                .get_repeated::<MessageView>(1)
+            {
+                let attribute = attribute.unwrap();
+
+                if attribute.get::<&str>(1).unwrap() == "service.name" {
+                    service_name = Some(
+                        attribute
+                            .get::<MessageView>(2)
+                            .unwrap()
+                            .get::<&str>(1)
+                            .unwrap(),
+                    );
+                }
+            }
+
+            // ResourceLogs.scope_logs.log_records.severity_text
+            for scope_logs in resource_logs.get_repeated::<MessageView>(2) {
+                for log_record in scope_logs.unwrap().get_repeated::<MessageView>(2) {
+                    severity_texts.push(log_record.unwrap().get::<&str>(3).unwrap());
+                }
+            }
+        }
+
+        assert_eq!(Some("sval_protobuf_tests"), service_name);
+        assert_eq!(vec!["Info", "Debug", "Error"], severity_texts);
+    }
+
+    #[test]
+    fn export_logs_service_request_read_nested() {
+        use sval_protobuf::{decode::ProtoBufReader, raw::Payload};
+
+        let encoded = sval_protobuf::stream_to_protobuf(data_sval::export_logs_service_request());
+
+        let mut reader = ProtoBufReader::new(&encoded);
+        let mut severity_texts = Vec::new();
+
+        // ExportLogsServiceRequest.resource_logs
+        while let Some(resource_logs) = reader.next_field().unwrap() {
+            assert_eq!(1, resource_logs.number());
+            reader.begin_len().unwrap();
+
+            while let Some(field) = reader.next_field().unwrap() {
+                // ResourceLogs.scope_logs
+                if field.number() != 2 {
+                    continue;
+                }
+
+                reader.begin_len().unwrap();
+
+                while let Some(field) = reader.next_field().unwrap() {
+                    // ScopeLogs.log_records
+                    if field.number() != 2 {
+                        continue;
+                    }
+
+                    reader.begin_len().unwrap();
+
+                    while let Some(field) = reader.next_field().unwrap() {
+                        // LogRecord.severity_text
+                        if let (3, Payload::Len(text)) = (field.number(), field.payload()) {
+                            severity_texts
+                                .push(String::from_utf8(text.to_vec().into_owned()).unwrap());
+                        }
+                    }
+
+                    reader.end_len().unwrap();
+                }
+
+                reader.end_len().unwrap();
+            }
+
+            reader.end_len().unwrap();
+        }
+
+        assert_eq!(0, reader.depth());
+        assert_eq!(vec!["Info", "Debug", "Error"], severity_texts);
+    }
 }